@@ -0,0 +1,60 @@
+use crate::{Board, MoveData, Piece};
+
+/// Whether the game is over in a position. Repetitions depend on the game
+/// history, so they are left to the caller.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameStatus {
+    Ongoing,
+    // The side to move is mated
+    Checkmate,
+    Stalemate,
+    FiftyMoveRule,
+    InsufficientMaterial,
+}
+
+impl Board {
+    pub fn get_legal_moves(&mut self) -> Vec<MoveData> {
+        let mut moves = self.get_pseudo_legal_moves();
+        moves.retain(|m| {
+            self.make_move(m);
+            let legal = !self.is_king_left_in_check();
+            self.unmake_move(m);
+            legal
+        });
+        moves
+    }
+
+    /// Neither side can mate: only kings, a single knight or bishop, or
+    /// bishops all on squares of the same color
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        // Bishops on light and on dark squares
+        let mut bishops = [0; 2];
+        for (sq, p) in self.data.iter().enumerate() {
+            match p {
+                Some((_, Piece::Pawn | Piece::Rook | Piece::Queen)) => return false,
+                Some((_, Piece::Knight)) => knights += 1,
+                Some((_, Piece::Bishop)) => bishops[(sq / 8 + sq % 8) % 2] += 1,
+                _ => (),
+            }
+        }
+        knights + bishops[0] + bishops[1] <= 1
+            || knights == 0 && (bishops[0] == 0 || bishops[1] == 0)
+    }
+
+    pub fn game_status(&mut self) -> GameStatus {
+        if self.get_legal_moves().is_empty() {
+            return match self.is_player_mated() {
+                true => GameStatus::Checkmate,
+                false => GameStatus::Stalemate,
+            };
+        }
+        if self.half_moves >= 100 {
+            return GameStatus::FiftyMoveRule;
+        }
+        if self.is_insufficient_material() {
+            return GameStatus::InsufficientMaterial;
+        }
+        GameStatus::Ongoing
+    }
+}
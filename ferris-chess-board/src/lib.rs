@@ -1,9 +1,12 @@
+pub use game_status::GameStatus;
 pub use squares::Square;
 use std::{collections::HashSet, vec};
 use zobrist::{Castling, Zobrist, ZobristData};
 
 mod cache;
+mod game_status;
 pub mod perft;
+mod see;
 mod squares;
 mod zobrist;
 
@@ -377,6 +380,74 @@ impl Board {
         }
     }
 
+    pub fn to_fen(&self) -> String {
+        let mut pieces = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let Some((color, piece)) = self.data[rank * 8 + file] else {
+                    empty += 1;
+                    continue;
+                };
+                if empty > 0 {
+                    pieces.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                let c = match piece {
+                    Piece::Pawn => 'p',
+                    Piece::Knight => 'n',
+                    Piece::Bishop => 'b',
+                    Piece::Rook => 'r',
+                    Piece::Queen => 'q',
+                    Piece::King => 'k',
+                };
+                pieces.push(match color {
+                    Color::White => c.to_ascii_uppercase(),
+                    Color::Black => c,
+                });
+            }
+            if empty > 0 {
+                pieces.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                pieces.push('/');
+            }
+        }
+
+        let mut castling: String = [
+            (self.castling_w_00, 'K'),
+            (self.castling_w_000, 'Q'),
+            (self.castling_b_00, 'k'),
+            (self.castling_b_000, 'q'),
+        ]
+        .iter()
+        .filter(|(allowed, _)| *allowed)
+        .map(|(_, c)| *c)
+        .collect();
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        // The en passant square is behind the pawn that just moved two squares
+        let ep_square = match self.ep_target {
+            Some(file) => {
+                let rank = if self.black_to_move { '3' } else { '6' };
+                format!("{}{}", (b'a' + file as u8) as char, rank)
+            }
+            None => "-".to_string(),
+        };
+
+        format!(
+            "{} {} {} {} {} {}",
+            pieces,
+            if self.black_to_move { 'b' } else { 'w' },
+            castling,
+            ep_square,
+            self.half_moves,
+            self.full_moves
+        )
+    }
+
     pub fn print(&self) {
         for rank_idx in (0..8).rev() {
             print!("{} ", rank_idx + 1);
@@ -1644,6 +1715,59 @@ impl Board {
         moves
     }
 
+    /// Puts piece on the square, or empties it for None, keeping the piece
+    /// lists, king positions and hashes up to date
+    pub fn set_piece(&mut self, sq: usize, piece: Option<(Color, Piece)>) {
+        if let Some(old) = self.data[sq] {
+            self.zobrist.invert_piece(sq, old);
+            match old {
+                (Color::White, p) => {
+                    self.pieces_w.remove(&sq);
+                    if p == Piece::King {
+                        self.king_pos_w = None;
+                    }
+                }
+                (Color::Black, p) => {
+                    self.pieces_b.remove(&sq);
+                    if p == Piece::King {
+                        self.king_pos_b = None;
+                    }
+                }
+            }
+        }
+
+        self.data[sq] = piece;
+        if let Some(new) = piece {
+            self.zobrist.invert_piece(sq, new);
+            match new {
+                (Color::White, p) => {
+                    self.pieces_w.insert(sq);
+                    if p == Piece::King {
+                        self.king_pos_w = Some(sq);
+                    }
+                }
+                (Color::Black, p) => {
+                    self.pieces_b.insert(sq);
+                    if p == Piece::King {
+                        self.king_pos_b = Some(sq);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get_king_pos(&self, color: Color) -> Option<usize> {
+        match color {
+            Color::White => self.king_pos_w,
+            Color::Black => self.king_pos_b,
+        }
+    }
+
+    /// Whether either side may still castle
+    pub fn has_castling_rights(&self) -> bool {
+        self.castling_w_00 || self.castling_w_000 || self.castling_b_00 || self.castling_b_000
+    }
+
     pub fn is_player_mated(&self) -> bool {
         let king_pos = match self.black_to_move {
             false => self.king_pos_w,
@@ -0,0 +1,163 @@
+use crate::{Board, Color, MoveData, MoveType, Piece};
+
+fn promotion_piece(move_type: MoveType) -> Option<Piece> {
+    match move_type {
+        MoveType::QueenPromotion => Some(Piece::Queen),
+        MoveType::RookPromotion => Some(Piece::Rook),
+        MoveType::BishopPromotion => Some(Piece::Bishop),
+        MoveType::KnightPromotion => Some(Piece::Knight),
+        _ => None,
+    }
+}
+
+impl Board {
+    /// Squares of the pieces of color that attack pos
+    pub fn get_attackers(&self, pos: usize, color: Color) -> Vec<usize> {
+        let occupied = self.data.map(|p| p.is_some());
+        self.get_attackers_with_occupancy(pos, color, &occupied)
+    }
+
+    /// Squares attacked by the piece on pos, including squares holding
+    /// pieces of its own color
+    pub fn get_attacks(&self, pos: usize) -> Vec<usize> {
+        let Some((color, piece)) = self.data[pos] else {
+            return vec![];
+        };
+
+        let mut attacks = vec![];
+        let mut add_rays = |rays: &Vec<Vec<usize>>| {
+            for ray in rays {
+                for sq in ray {
+                    attacks.push(*sq);
+                    if self.data[*sq].is_some() {
+                        break;
+                    }
+                }
+            }
+        };
+
+        match piece {
+            Piece::Rook => add_rays(&self.cache.rook_rays[pos]),
+            Piece::Bishop => add_rays(&self.cache.bishop_rays[pos]),
+            Piece::Queen => {
+                add_rays(&self.cache.rook_rays[pos]);
+                add_rays(&self.cache.bishop_rays[pos]);
+            }
+            Piece::Knight => attacks.extend(&self.cache.knight_targets[pos]),
+            Piece::King => attacks.extend(&self.cache.neighbor_targets[pos]),
+            Piece::Pawn => {
+                let (left, right) = match color {
+                    Color::White => (pos + 7, pos + 9),
+                    Color::Black => (pos.wrapping_sub(9), pos.wrapping_sub(7)),
+                };
+                let file = pos % 8;
+                if file > 0 && left < 64 {
+                    attacks.push(left);
+                }
+                if file < 7 && right < 64 {
+                    attacks.push(right);
+                }
+            }
+        }
+        attacks
+    }
+
+    /// Attackers when only the squares set in occupied hold pieces, so
+    /// sliders attack through pieces that were removed from the board
+    fn get_attackers_with_occupancy(
+        &self,
+        pos: usize,
+        color: Color,
+        occupied: &[bool; 64],
+    ) -> Vec<usize> {
+        let mut attackers = vec![];
+        let is_piece =
+            |sq: usize, piece: Piece| occupied[sq] && self.data[sq] == Some((color, piece));
+
+        for (rays, slider) in [
+            (&self.cache.rook_rays[pos], Piece::Rook),
+            (&self.cache.bishop_rays[pos], Piece::Bishop),
+        ] {
+            for ray in rays {
+                if let Some(sq) = ray.iter().find(|sq| occupied[**sq]) {
+                    if is_piece(*sq, slider) || is_piece(*sq, Piece::Queen) {
+                        attackers.push(*sq);
+                    }
+                }
+            }
+        }
+
+        attackers.extend(
+            self.cache.knight_targets[pos]
+                .iter()
+                .filter(|sq| is_piece(**sq, Piece::Knight)),
+        );
+        attackers.extend(
+            self.cache.neighbor_targets[pos]
+                .iter()
+                .filter(|sq| is_piece(**sq, Piece::King)),
+        );
+
+        // Pawns capture diagonally forward, so they attack pos from the rank behind it
+        let (left, right) = match color {
+            Color::White => (pos.checked_sub(9), pos.checked_sub(7)),
+            Color::Black => (Some(pos + 7), Some(pos + 9)),
+        };
+        let file = pos % 8;
+        let pawn_squares = [left.filter(|_| file > 0), right.filter(|_| file < 7)];
+        attackers.extend(
+            pawn_squares
+                .into_iter()
+                .flatten()
+                .filter(|sq| *sq < 64 && is_piece(*sq, Piece::Pawn)),
+        );
+
+        attackers
+    }
+
+    /// Static exchange evaluation: the material the side to move wins with m
+    /// when both sides keep capturing on its target square with their least
+    /// valuable piece. Pins are not considered.
+    pub fn see(&self, m: &MoveData) -> i32 {
+        let (mut color, _) = self.data[m.start_pos].expect("No piece on expected square");
+        let mut occupied = self.data.map(|p| p.is_some());
+        occupied[m.start_pos] = false;
+        if m.move_type == MoveType::EnPassant {
+            // The captured pawn is next to the target square, on the start rank
+            occupied[m.start_pos - m.start_pos % 8 + m.end_pos % 8] = false;
+        }
+
+        let promotion = promotion_piece(m.move_type);
+        let mut on_target = promotion.unwrap_or(m.piece) as i32;
+        let mut gains = vec![
+            m.capture.map_or(0, |cap| cap as i32)
+                + promotion.map_or(0, |p| p as i32 - Piece::Pawn as i32),
+        ];
+
+        loop {
+            color = match color {
+                Color::White => Color::Black,
+                Color::Black => Color::White,
+            };
+            let attacker = self
+                .get_attackers_with_occupancy(m.end_pos, color, &occupied)
+                .into_iter()
+                .min_by_key(|sq| self.data[*sq].unwrap().1 as i32);
+
+            let Some(attacker) = attacker else {
+                break;
+            };
+            gains.push(on_target - gains.last().unwrap());
+            on_target = self.data[attacker].unwrap().1 as i32;
+            occupied[attacker] = false;
+        }
+
+        // Either side may stop capturing when continuing loses material
+        while gains.len() > 1 {
+            let gain = gains.pop().unwrap();
+            let previous = gains.last_mut().unwrap();
+            *previous = -(-*previous).max(gain);
+        }
+        gains[0]
+    }
+}
@@ -35,6 +35,8 @@ pub struct Zobrist {
     // 8: File of en passant target square
     board_rnd_nums: [u64; 12 * 64 + 1 + 4 + 8],
     pub hash: u64,
+    // Key of the pawns only, for caching pawn structure evaluations
+    pub pawn_hash: u64,
 }
 
 pub struct ZobristData {
@@ -52,6 +54,7 @@ impl Zobrist {
         let mut board_rnd_nums: [u64; 12 * 64 + 1 + 4 + 8] = [0; 781];
 
         let mut hash = 0;
+        let mut pawn_hash = 0;
 
         for num in &mut board_rnd_nums {
             *num = rand::thread_rng().gen_range(0..=u64::MAX);
@@ -61,6 +64,9 @@ impl Zobrist {
             if let Some(p) = piece {
                 let piece_offset = get_piece_idx(*p);
                 hash ^= board_rnd_nums[idx * 12 + piece_offset];
+                if p.1 == Piece::Pawn {
+                    pawn_hash ^= board_rnd_nums[idx * 12 + piece_offset];
+                }
             }
         }
 
@@ -101,11 +107,16 @@ impl Zobrist {
         Zobrist {
             board_rnd_nums,
             hash,
+            pawn_hash,
         }
     }
 
     pub fn invert_piece(&mut self, idx: usize, piece: (Color, Piece)) {
-        self.hash ^= self.board_rnd_nums[idx * 12 + get_piece_idx(piece)];
+        let rnd_num = self.board_rnd_nums[idx * 12 + get_piece_idx(piece)];
+        self.hash ^= rnd_num;
+        if piece.1 == Piece::Pawn {
+            self.pawn_hash ^= rnd_num;
+        }
     }
 
     pub fn invert_black_to_move(&mut self) {
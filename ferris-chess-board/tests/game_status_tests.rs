@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod game_status_tests {
+
+    use ferris_chess_board::*;
+
+    #[test]
+    fn start_position_is_ongoing_with_twenty_moves() {
+        let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(board.get_legal_moves().len(), 20);
+        assert_eq!(board.game_status(), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn mated_and_stalemated_sides_have_no_moves() {
+        let mut mate = Board::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(mate.game_status(), GameStatus::Checkmate);
+
+        let mut stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(stalemate.game_status(), GameStatus::Stalemate);
+    }
+
+    #[test]
+    fn fifty_moves_without_progress_draw() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80");
+        assert_eq!(board.game_status(), GameStatus::FiftyMoveRule);
+    }
+
+    #[test]
+    fn lone_minor_pieces_cannot_mate() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K3 w - - 0 1",
+            // Bishops on dark squares only
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        ] {
+            let mut board = Board::from_fen(fen);
+            assert_eq!(
+                board.game_status(),
+                GameStatus::InsufficientMaterial,
+                "{}",
+                fen
+            );
+        }
+
+        for fen in [
+            "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2BBK3 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        ] {
+            let mut board = Board::from_fen(fen);
+            assert_eq!(board.game_status(), GameStatus::Ongoing, "{}", fen);
+        }
+    }
+}
@@ -463,4 +463,103 @@ mod make_move_tests {
         board.make_move(&bishop_move);
         assert_eq!(board.half_moves, 9);
     }
+
+    #[test]
+    fn pawn_hash_only_changes_on_pawn_moves() {
+        let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let pawn_hash_init = board.zobrist.pawn_hash;
+
+        let knight_move = MoveData {
+            start_pos: Square::G1,
+            end_pos: Square::F3,
+            piece: Piece::Knight,
+            move_type: MoveType::Regular,
+            capture: None,
+        };
+        board.make_move(&knight_move);
+        assert_eq!(board.zobrist.pawn_hash, pawn_hash_init);
+
+        let pawn_move = MoveData {
+            start_pos: Square::E7,
+            end_pos: Square::E5,
+            piece: Piece::Pawn,
+            move_type: MoveType::Regular,
+            capture: None,
+        };
+        board.make_move(&pawn_move);
+        assert_ne!(board.zobrist.pawn_hash, pawn_hash_init);
+
+        board.unmake_move(&pawn_move);
+        assert_eq!(board.zobrist.pawn_hash, pawn_hash_init);
+    }
+
+    #[test]
+    fn pawn_hash_removes_promoted_pawn() {
+        let mut board = Board::from_fen("8/4k2P/8/8/8/4K3/8/8 w - - 0 1");
+        let promotion = MoveData {
+            start_pos: Square::H7,
+            end_pos: Square::H8,
+            piece: Piece::Pawn,
+            move_type: MoveType::QueenPromotion,
+            capture: None,
+        };
+        board.make_move(&promotion);
+        // No pawns are left on the board
+        assert_eq!(board.zobrist.pawn_hash, 0);
+    }
+
+    #[test]
+    fn fen_round_trips_after_moves() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/1P6/8/3pP3/8/8/8/R3K2R w Kq d6 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ] {
+            assert_eq!(Board::from_fen(fen).to_fen(), fen);
+        }
+
+        let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        for uci_move in ["e2e4", "g8f6", "e4e5", "d7d5"] {
+            let m = MoveData::from_uci(&uci_move.to_string(), &board);
+            board.make_move(&m);
+        }
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkb1r/ppp1pppp/5n2/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 3"
+        );
+    }
+
+    #[test]
+    fn set_piece_matches_the_board_read_from_fen() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        board.set_piece(12, None);
+        board.set_piece(4, None);
+        board.set_piece(3, Some((Color::White, Piece::King)));
+        board.set_piece(48, Some((Color::White, Piece::Pawn)));
+        board.set_piece(60, Some((Color::Black, Piece::Queen)));
+        board.set_piece(59, Some((Color::Black, Piece::King)));
+
+        let mut expected = Board::from_fen("3kq3/P7/8/8/8/8/8/3K4 w - - 0 1");
+        assert_eq!(board.to_fen(), expected.to_fen());
+        assert_eq!(board.get_king_pos(Color::White), Some(3));
+        assert_eq!(board.get_king_pos(Color::Black), Some(59));
+        assert_eq!(board.pieces_w, expected.pieces_w);
+        assert_eq!(board.pieces_b, expected.pieces_b);
+        assert_eq!(
+            board.get_legal_moves().len(),
+            expected.get_legal_moves().len()
+        );
+    }
+
+    #[test]
+    fn castling_rights_are_lost_by_moving_the_king() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert!(board.has_castling_rights());
+        let king_move = MoveData::from_uci(&"e1f1".to_string(), &board);
+        board.make_move(&king_move);
+        assert!(!board.has_castling_rights());
+        board.unmake_move(&king_move);
+        assert!(board.has_castling_rights());
+        assert!(!Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").has_castling_rights());
+    }
 }
@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod see_tests {
+
+    use ferris_chess_board::*;
+
+    #[test]
+    fn see_wins_undefended_pawn() {
+        let board = Board::from_fen("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1");
+        let m = MoveData::from_uci(&"e1e5".to_string(), &board);
+        assert_eq!(board.see(&m), Piece::Pawn as i32);
+    }
+
+    #[test]
+    fn see_loses_knight_for_pawn_with_x_ray_defenders() {
+        let board = Board::from_fen("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1");
+        let m = MoveData::from_uci(&"d3e5".to_string(), &board);
+        assert_eq!(board.see(&m), Piece::Pawn as i32 - Piece::Knight as i32);
+    }
+
+    #[test]
+    fn see_of_quiet_move_to_attacked_square() {
+        let board = Board::from_fen("4k3/8/3p4/8/4N3/8/8/4K3 w - - 0 1");
+        let attacked = MoveData::from_uci(&"e4c5".to_string(), &board);
+        assert_eq!(board.see(&attacked), -(Piece::Knight as i32));
+        let safe = MoveData::from_uci(&"e4f6".to_string(), &board);
+        assert_eq!(board.see(&safe), 0);
+    }
+
+    #[test]
+    fn attackers_include_pawns_and_knights_but_not_blocked_sliders() {
+        let board = Board::from_fen("4k3/8/8/3p4/4P3/2N5/8/4QK1B w - - 0 1");
+        let mut attackers = board.get_attackers(Square::D5, Color::White);
+        attackers.sort();
+        assert_eq!(attackers, vec![Square::C3, Square::E4]);
+        let attackers = board.get_attackers(Square::E4, Color::Black);
+        assert_eq!(attackers, vec![Square::D5]);
+    }
+
+    #[test]
+    fn attacks_of_sliders_stop_at_the_first_piece() {
+        let board = Board::from_fen("4k3/8/8/8/P7/8/8/R3K3 w - - 0 1");
+        let mut attacks = board.get_attacks(Square::A1);
+        attacks.sort();
+        let expected = vec![
+            Square::B1,
+            Square::C1,
+            Square::D1,
+            Square::E1,
+            Square::A2,
+            Square::A3,
+            Square::A4,
+        ];
+        assert_eq!(attacks, expected);
+
+        let mut pawn_attacks = board.get_attacks(Square::A4);
+        pawn_attacks.sort();
+        assert_eq!(pawn_attacks, vec![Square::B5]);
+    }
+}
@@ -2,12 +2,20 @@ pub mod uci;
 use crate::uci::Uci;
 use clap::{Parser, Subcommand};
 use ferris_chess_board::{self, perft::perft, Board};
-use ferris_chess_engine::Engine;
+use ferris_chess_engine::datagen::{self, DatagenOptions};
+use ferris_chess_engine::eval_params::EvalParams;
+use ferris_chess_engine::evaluator::load_evaluator;
+use ferris_chess_engine::tuner::{load_positions, Tuner};
+use ferris_chess_engine::{Engine, GoCommand};
 use std::cell::RefCell;
-use std::io::Write;
+use std::io::{BufWriter, Write};
 use std::process;
 use std::sync::{Arc, Mutex};
-use std::{fs::File, io, panic, time::Instant};
+use std::{
+    fs::{self, File},
+    io, panic,
+    time::{Duration, Instant},
+};
 
 #[derive(Subcommand, Debug)]
 enum Command {
@@ -18,6 +26,63 @@ enum Command {
     },
     /// Runs the perft performance test using transposition table
     TtPerft { depth: u8 },
+    /// Measures time to depth on a fixed set of positions, with one thread
+    /// and then with the given number of threads
+    Bench {
+        /// The depth searched in every position
+        #[arg(short, long, default_value_t = 5)]
+        depth: usize,
+        /// Number of search threads
+        #[arg(short, long, default_value_t = 1)]
+        threads: usize,
+    },
+    /// Fits the evaluation parameters to game results (Texel tuning)
+    Tune {
+        /// Positions labeled with results, as EPD with c9 "1-0" or FEN;result lines
+        positions: String,
+        /// File the tuned parameters are written to after every pass
+        #[arg(short, long, default_value = "tuned.txt")]
+        output: String,
+        /// Parameters to start from instead of the built-in defaults
+        #[arg(short, long)]
+        params: Option<String>,
+        /// Maximum number of passes over all parameters
+        #[arg(long, default_value_t = 100)]
+        passes: usize,
+        /// Amount each parameter is moved by in the local search
+        #[arg(short, long, default_value_t = 1)]
+        step: i32,
+        /// Number of threads evaluating positions
+        #[arg(short, long, default_value_t = 1)]
+        threads: usize,
+    },
+    /// Generates training data from fixed-node self-play games
+    Datagen {
+        /// File the positions are written to, one FEN;score;result line each
+        #[arg(short, long, default_value = "data.txt")]
+        output: String,
+        /// Number of games to play
+        #[arg(short, long, default_value_t = 100)]
+        games: usize,
+        /// Nodes searched for every move
+        #[arg(short, long, default_value_t = 5000)]
+        nodes: usize,
+        /// Number of games played in parallel
+        #[arg(short, long, default_value_t = 1)]
+        threads: usize,
+        /// Positions to start games from, one FEN or EPD per line
+        #[arg(short, long)]
+        book: Option<String>,
+        /// Random moves played from the starting position
+        #[arg(long, default_value_t = 8)]
+        random_plies: usize,
+        /// Seed of the random openings
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+        /// Network or evaluation parameters to play with instead of PeSTO
+        #[arg(long)]
+        eval_file: Option<String>,
+    },
     /// Start the engine in UCI mode (default)
     Uci,
     /// Used during development for debugging
@@ -83,6 +148,43 @@ fn main() {
             }
             tt_perft_results(&mut engine, depth)
         }
+        Some(Command::Bench { depth, threads }) => bench(depth, threads),
+        Some(Command::Tune {
+            positions,
+            output,
+            params,
+            passes,
+            step,
+            threads,
+        }) => {
+            if let Err(e) = tune(&positions, &output, params, passes, step, threads) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        Some(Command::Datagen {
+            output,
+            games,
+            nodes,
+            threads,
+            book,
+            random_plies,
+            seed,
+            eval_file,
+        }) => {
+            let options = DatagenOptions {
+                games,
+                nodes,
+                threads,
+                random_plies,
+                seed,
+                ..DatagenOptions::default()
+            };
+            if let Err(e) = datagen(&output, options, book, eval_file) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
         Some(Command::Uci) => handle_uci(&mut engine.board),
         Some(Command::Debug) => debug_board(&mut engine.board),
         None => handle_uci(&mut engine.board),
@@ -120,6 +222,163 @@ fn tt_perft_results(engine: &mut Engine, depth: u8) {
     );
 }
 
+// Positions searched by bench, from the opening to the endgame
+const BENCH_FENS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "rnbqkb1r/pp2pppp/3p1n2/8/3NP3/8/PPP2PPP/RNBQKB1R w KQkq - 1 5",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+];
+
+/// Nodes and time to reach the depth in every bench position
+fn time_to_depth(go_cmd: &GoCommand, threads: usize) -> Vec<(usize, Duration)> {
+    BENCH_FENS
+        .iter()
+        .map(|fen| {
+            let mut engine = Engine::new(fen);
+            engine.set_threads(threads);
+            engine.set_print_info(false);
+
+            let timing = Instant::now();
+            engine.iter_deepening(go_cmd);
+            let elapsed = timing.elapsed();
+            (engine.search_info().map_or(0, |info| info.nodes), elapsed)
+        })
+        .collect()
+}
+
+/// Runs the bench positions with a single thread and, when more threads are
+/// given, again with all of them to compare the time to depth
+fn bench(depth: usize, threads: usize) {
+    let go_cmd = GoCommand::new(&format!("go depth {}", depth));
+    let mut runs = vec![(1, time_to_depth(&go_cmd, 1))];
+    if threads > 1 {
+        runs.push((threads, time_to_depth(&go_cmd, threads)));
+    }
+
+    for (threads, results) in &runs {
+        println!("Time to depth {} with {} thread(s)", depth, threads);
+        for ((nodes, elapsed), fen) in results.iter().zip(BENCH_FENS) {
+            println!("{:>12} nodes {:>10.3?} {}", nodes, elapsed, fen);
+        }
+        let total_nodes: usize = results.iter().map(|(nodes, _)| nodes).sum();
+        let elapsed: Duration = results.iter().map(|(_, elapsed)| *elapsed).sum();
+        println!(
+            "Total: {} nodes Time: {:?} Nodes / second: {}",
+            total_nodes,
+            elapsed,
+            (total_nodes as f64 / elapsed.as_secs_f64()) as usize
+        );
+    }
+
+    if let [(_, single), (threads, parallel)] = &runs[..] {
+        println!("Time to depth speedup with {} threads", threads);
+        for (((_, one), (_, many)), fen) in single.iter().zip(parallel).zip(BENCH_FENS) {
+            println!("{:>8.2}x {}", one.as_secs_f64() / many.as_secs_f64(), fen);
+        }
+        let total = |results: &[(usize, Duration)]| -> f64 {
+            results
+                .iter()
+                .map(|(_, elapsed)| elapsed.as_secs_f64())
+                .sum()
+        };
+        println!("Total: {:.2}x", total(single) / total(parallel));
+    }
+}
+
+fn tune(
+    positions_path: &str,
+    output: &str,
+    params_path: Option<String>,
+    passes: usize,
+    step: i32,
+    threads: usize,
+) -> Result<(), String> {
+    let mut params = match params_path {
+        Some(path) => EvalParams::load(&path)?,
+        None => EvalParams::default(),
+    };
+    let positions = load_positions(positions_path)?;
+    if positions.is_empty() {
+        return Err(format!("No positions in {}", positions_path));
+    }
+    println!("Loaded {} positions", positions.len());
+
+    let mut tuner = Tuner::new(positions, threads);
+    let k = tuner.fit_k(&params);
+    println!("K: {:.3} Error: {:.6}", k, tuner.error(&params, k));
+
+    let timing = Instant::now();
+    tuner.tune(&mut params, k, step, passes, |pass, error, params| {
+        println!(
+            "Pass {} Error: {:.6} Time: {:?}",
+            pass,
+            error,
+            timing.elapsed()
+        );
+        // Saved after every pass so long runs can be stopped at any time
+        if let Err(e) = params.save(output) {
+            eprintln!("Error: {}", e);
+        }
+    });
+    params.save(output)?;
+    println!("Tuned parameters written to {}", output);
+    Ok(())
+}
+
+fn datagen(
+    output: &str,
+    mut options: DatagenOptions,
+    book: Option<String>,
+    eval_file: Option<String>,
+) -> Result<(), String> {
+    if let Some(path) = book {
+        let text = fs::read_to_string(&path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+        options.book = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.split(';').next().unwrap_or_default().to_string())
+            .collect();
+    }
+    let mut engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    if let Some(path) = eval_file {
+        engine.set_evaluator(load_evaluator(&path)?);
+    }
+
+    let file = File::create(output).map_err(|e| format!("Can't write {}: {}", output, e))?;
+    let mut writer = BufWriter::new(file);
+    let mut write_result = Ok(());
+    let mut games = 0;
+    let mut positions = 0;
+    let timing = Instant::now();
+
+    datagen::generate(&engine, &options, |game| {
+        games += 1;
+        positions += game.len();
+        for position in game {
+            if write_result.is_ok() {
+                write_result = writeln!(writer, "{}", position.to_line());
+            }
+        }
+        if games % 10 == 0 || games == options.games {
+            println!(
+                "Games: {}/{} Positions: {} Time: {:?}",
+                games,
+                options.games,
+                positions,
+                timing.elapsed()
+            );
+        }
+    })?;
+    write_result
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Can't write {}: {}", output, e))?;
+    println!("Positions written to {}", output);
+    Ok(())
+}
+
 fn debug_board(board: &mut Board) {
     board.print();
     let moves = board.get_pseudo_legal_moves();
@@ -1,22 +1,58 @@
-use std::{io::BufRead, sync::mpsc, thread};
+use std::{
+    io::BufRead,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::{self, JoinHandle},
+};
 extern crate rand;
 use ferris_chess_board::{Board, MoveData};
-use ferris_chess_engine::{Engine, GoCommand};
+use ferris_chess_engine::{
+    bitbase, evaluator::load_evaluator, pesto::PestoEvaluator, syzygy::SyzygyTables,
+    time_management::DEFAULT_MOVE_OVERHEAD, transposition_table::DEFAULT_HASH_MB, Engine,
+    GoCommand,
+};
 
 pub struct Uci {
-    engine: Engine,
+    // Moved to the search thread while a search is running
+    engine: Option<Engine>,
+    search_thread: Option<JoinHandle<Engine>>,
+    stop: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
 }
 
 impl Uci {
     pub fn new() -> Self {
+        let engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let stop = engine.stop_handle();
+        let pondering = engine.ponder_handle();
+
         Uci {
-            engine: Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            engine: Some(engine),
+            search_thread: None,
+            stop,
+            pondering,
+        }
+    }
+
+    /// Stops a running search, if any, and takes the engine back from the search thread
+    fn engine(&mut self) -> &mut Engine {
+        if let Some(search_thread) = self.search_thread.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.engine = Some(search_thread.join().expect("Search thread panicked"));
         }
+        self.engine.as_mut().unwrap()
     }
 
     pub fn start_read_stdin_loop(&mut self, _board: &mut Board) {
         let (tx, rx) = mpsc::channel();
 
+        // Generate the KPK bitbase while the GUI sets up, rather than in the
+        // first search that reaches the endgame
+        thread::spawn(bitbase::kpk);
+
         // Start a new thread for reading from stdin
         let io_thread = thread::spawn(move || {
             for line in std::io::stdin().lock().lines() {
@@ -33,17 +69,16 @@ impl Uci {
     }
 
     fn start_handle_stdin_loop(&mut self, rx: &mpsc::Receiver<String>) {
-        loop {
-            let input = rx.try_recv();
-            if input.is_err() {
-                continue;
-            }
-            let cmd = input.unwrap();
-
-            if !cmd.is_empty() {
+        // Blocks until the next command. Commands keep being handled while
+        // a search runs on its own thread.
+        while let Ok(cmd) = rx.recv() {
+            if !cmd.trim().is_empty() {
                 self.uci_action(cmd)
             }
         }
+
+        // Input closed
+        self.engine();
     }
 
     fn uci_action(&mut self, cmd: String) {
@@ -58,9 +93,10 @@ impl Uci {
             "position" => self.handle_position(&cmd),
             "go" => self.handle_go(&cmd),
             "stop" => self.handle_stop(),
-            "ponderhit" => self.handle_ponderhit(&cmd_parts),
+            "ponderhit" => self.handle_ponderhit(),
             "quit" => self.handle_quit(),
-            "d" => self.engine.board.print(),
+            "d" => self.engine().board.print(),
+            "eval" => self.handle_eval(),
             _ => (), // Ignore invalid inputs (UCI)
         }
     }
@@ -68,6 +104,20 @@ impl Uci {
     fn handle_uci(&self) {
         println!("id name Ferris Chess 0.1");
         println!("id author Michael J");
+        println!(
+            "option name Hash type spin default {} min 1 max 4096",
+            DEFAULT_HASH_MB
+        );
+        println!("option name Threads type spin default 1 min 1 max 64");
+        println!("option name Ponder type check default false");
+        println!("option name MultiPV type spin default 1 min 1 max 256");
+        println!("option name Contempt type spin default 0 min -100 max 100");
+        println!("option name EvalFile type string default <empty>");
+        println!("option name SyzygyPath type string default <empty>");
+        println!(
+            "option name Move Overhead type spin default {} min 0 max 5000",
+            DEFAULT_MOVE_OVERHEAD
+        );
         println!("uciok");
     }
 
@@ -83,74 +133,147 @@ impl Uci {
         println!("readyok"); // Required response to isready
     }
 
-    fn handle_setoption(&self, _cmd_parts: &Vec<String>) {
-        println!("Got setoption");
+    fn handle_setoption(&mut self, cmd_parts: &[String]) {
+        // setoption name <id> [value <x>], where the name may contain spaces
+        let value_idx = cmd_parts.iter().position(|p| p == "value");
+        let name_end = value_idx.unwrap_or(cmd_parts.len());
+        let name = cmd_parts
+            .iter()
+            .take(name_end)
+            .skip(2)
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(" ");
+        let value = value_idx.map(|idx| cmd_parts[idx + 1..].join(" "));
+
+        match (name.as_str(), value) {
+            ("Hash", Some(v)) => match v.parse::<usize>() {
+                Ok(size_mb) => self.engine().set_hash_size(size_mb.clamp(1, 4096)),
+                Err(_) => println!("info string Invalid Hash value: {}", v),
+            },
+            ("Threads", Some(v)) => match v.parse::<usize>() {
+                Ok(threads) => self.engine().set_threads(threads.clamp(1, 64)),
+                Err(_) => println!("info string Invalid Threads value: {}", v),
+            },
+            // The GUI decides when to ponder by sending go ponder
+            ("Ponder", Some(_)) => (),
+            ("MultiPV", Some(v)) => match v.parse::<usize>() {
+                Ok(multi_pv) => self.engine().set_multi_pv(multi_pv.clamp(1, 256)),
+                Err(_) => println!("info string Invalid MultiPV value: {}", v),
+            },
+            ("Contempt", Some(v)) => match v.parse::<i32>() {
+                Ok(contempt) => self.engine().set_contempt(contempt.clamp(-100, 100)),
+                Err(_) => println!("info string Invalid Contempt value: {}", v),
+            },
+            ("Move Overhead", Some(v)) => match v.parse::<u64>() {
+                Ok(overhead) => self.engine().set_move_overhead(overhead.min(5000)),
+                Err(_) => println!("info string Invalid Move Overhead value: {}", v),
+            },
+            // An empty path goes back to the built in parameters
+            ("EvalFile", Some(v)) if v.is_empty() || v == "<empty>" => {
+                self.engine().set_evaluator(Box::new(PestoEvaluator::new()))
+            }
+            // A network file or evaluation parameters for PeSTO
+            ("EvalFile", Some(v)) => match load_evaluator(&v) {
+                Ok(evaluator) => {
+                    self.engine().set_evaluator(evaluator);
+                    println!("info string Loaded evaluation from {}", v);
+                }
+                Err(e) => println!("info string {}", e),
+            },
+            ("SyzygyPath", Some(v)) if v.is_empty() || v == "<empty>" => {
+                self.engine().set_syzygy_tables(None)
+            }
+            ("SyzygyPath", Some(v)) => match SyzygyTables::load(&v) {
+                Ok(tables) => {
+                    println!(
+                        "info string Found {} WDL and {} DTZ tables with up to {} pieces",
+                        tables.wdl.len(),
+                        tables.dtz.len(),
+                        tables.max_pieces()
+                    );
+                    self.engine().set_syzygy_tables(Some(tables));
+                }
+                Err(e) => println!("info string {}", e),
+            },
+            _ => println!("info string Unknown option: {}", name),
+        }
+    }
+
+    /// Prints the static evaluation of the current position term by term
+    fn handle_eval(&mut self) {
+        let engine = self.engine();
+        let board = engine.board.clone();
+        println!("{}", engine.eval_trace(&board));
     }
 
     fn handle_ucinewgame(&mut self, _cmd_parts: &Vec<String>) {
-        self.engine.new_game();
+        self.engine().new_game();
     }
 
     fn handle_position(&mut self, cmd: &String) {
         let mut parts = cmd.split("moves").map(|p| p.trim());
         let position_cmd = parts.next().unwrap();
 
+        let engine = self.engine();
+
         if position_cmd == "position startpos" {
-            self.engine.board =
-                Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+            engine.set_position(Board::from_fen(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            ));
         } else if position_cmd.starts_with("position fen") {
             let fen = cmd.strip_prefix("position fen ").unwrap();
-            self.engine.board = Board::from_fen(fen);
+            engine.set_position(Board::from_fen(fen));
         } else {
             panic!("Invalid position command: {}", position_cmd);
         }
 
-        // Clear transposition table on each position
-        self.engine.t_table.clear();
-
         if let Some(m) = parts.next() {
             for uci_move in m.split_ascii_whitespace() {
-                let m = MoveData::from_uci(&uci_move.to_string(), &self.engine.board);
-                self.engine.board.make_move(&m);
+                let m = MoveData::from_uci(&uci_move.to_string(), &engine.board);
+                engine.make_game_move(&m);
             }
         }
     }
 
-    fn handle_go(&mut self, cmd: &String) {
-        /* Add code to parse go options
-        match cmd_parts[1].as_str() {
-            "searchmoves" => println!("Got go searchmoves"),
-            "ponder" => println!("Got go ponder"),
-            "wtime" => println!("Got go wtime"),
-            "btime" => println!("Got go btime"),
-            "winc" => println!("Got go winc"),
-            "binc" => println!("Got go binc"),
-            "movestogo" => println!("Got go movestogo"),
-            "depth" => println!("Got go depth"),
-            "nodes" => println!("Got go nodes"),
-            "mate" => println!("Got go mate"),
-            "movetime" => println!("Got go movetime"),
-            "infinite" => println!("Got go infinite"),
-            _ => (),
-        }
-        */
-
+    fn handle_go(&mut self, cmd: &str) {
         let go_cmd = GoCommand::new(cmd);
-        let m = self.engine.iter_deepening(&go_cmd);
 
-        let uci_move = m.to_uci_move(&self.engine.board);
-        println!("bestmove {}", uci_move);
+        self.engine();
+        let mut engine = self.engine.take().unwrap();
+        self.stop.store(false, Ordering::Relaxed);
+        // Set before the search starts so an early ponderhit is not lost
+        self.pondering.store(go_cmd.ponder, Ordering::Relaxed);
+
+        self.search_thread = Some(thread::spawn(move || {
+            match engine.iter_deepening(&go_cmd) {
+                Some(m) => match engine.ponder_move(&m) {
+                    Some(reply) => println!(
+                        "bestmove {} ponder {}",
+                        m.to_uci_move(&engine.board),
+                        reply.to_uci_move(&engine.board)
+                    ),
+                    None => println!("bestmove {}", m.to_uci_move(&engine.board)),
+                },
+                // Checkmate or stalemate, reported with the null move
+                None => println!("bestmove 0000"),
+            }
+            engine
+        }));
     }
 
     fn handle_stop(&mut self) {
-        self.engine.stop();
+        // Waits for the search thread to report its best move
+        self.engine();
     }
 
-    fn handle_ponderhit(&self, _cmd_parts: &Vec<String>) {
-        println!("Got ponderhit");
+    fn handle_ponderhit(&self) {
+        // The pondering search continues under the time control of its go command
+        self.pondering.store(false, Ordering::Relaxed);
     }
 
-    fn handle_quit(&self) {
-        panic!("Shutting down engine");
+    fn handle_quit(&mut self) {
+        self.engine();
+        process::exit(0);
     }
 }
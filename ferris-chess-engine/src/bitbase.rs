@@ -0,0 +1,185 @@
+use ferris_chess_board::{Board, Color, MoveData, MoveType, Piece};
+use std::sync::OnceLock;
+
+// Positions are stored with white as the side with the pawn, on files a to d
+// and ranks 2 to 7
+const PAWN_SQUARES: usize = 24;
+const POSITIONS: usize = 2 * PAWN_SQUARES * 64 * 64;
+
+fn index(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    let pawn_idx = (pawn / 8 - 1) * 4 + pawn % 8;
+    ((usize::from(!white_to_move) * PAWN_SQUARES + pawn_idx) * 64 + white_king) * 64 + black_king
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+/// Won and drawn positions of king and pawn against king
+pub struct KpkBitbase {
+    wins: Vec<u64>,
+}
+
+/// Outcome of a move that leaves the bitbase, None for a move to another
+/// position of the bitbase
+fn exit_outcome(board: &mut Board, m: &MoveData) -> Option<Outcome> {
+    match m.move_type {
+        _ if m.capture.is_some() => Some(Outcome::Draw),
+        MoveType::QueenPromotion | MoveType::RookPromotion => {
+            // Won unless black is stalemated or takes the new piece
+            let replies = board.get_legal_moves();
+            if replies.is_empty() {
+                match board.is_player_mated() {
+                    true => Some(Outcome::Win),
+                    false => Some(Outcome::Draw),
+                }
+            } else if replies.iter().any(|r| r.capture.is_some()) {
+                Some(Outcome::Draw)
+            } else {
+                Some(Outcome::Win)
+            }
+        }
+        MoveType::BishopPromotion | MoveType::KnightPromotion => Some(Outcome::Draw),
+        _ => None,
+    }
+}
+
+impl KpkBitbase {
+    /// Classifies every position by its legal moves, then repeatedly
+    /// resolves the positions whose moves lead to known outcomes, working
+    /// back from mates, promotions and captures of the pawn. Positions never
+    /// resolved are draws.
+    pub fn generate() -> Self {
+        let mut outcomes = vec![Outcome::Invalid; POSITIONS];
+        // Positions reachable in one move from each unresolved position
+        let mut successors: Vec<Vec<u32>> = vec![vec![]; POSITIONS];
+        let mut boards = [
+            Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1"),
+            Board::from_fen("8/8/8/8/8/8/8/8 b - - 0 1"),
+        ];
+
+        for (board, white_to_move) in boards.iter_mut().zip([true, false]) {
+            // The side to move reaches its best outcome with any move to it,
+            // the other outcome only once all moves lead to it
+            let (best, worst) = match white_to_move {
+                true => (Outcome::Win, Outcome::Draw),
+                false => (Outcome::Draw, Outcome::Win),
+            };
+
+            for pawn in (8..56).filter(|sq| sq % 8 < 4) {
+                board.set_piece(pawn, Some((Color::White, Piece::Pawn)));
+                for white_king in (0..64).filter(|sq| *sq != pawn) {
+                    board.set_piece(white_king, Some((Color::White, Piece::King)));
+                    for black_king in (0..64).filter(|sq| *sq != pawn && *sq != white_king) {
+                        board.set_piece(black_king, Some((Color::Black, Piece::King)));
+
+                        let idx = index(white_to_move, white_king, black_king, pawn);
+                        if !board.is_king_left_in_check() {
+                            let moves = board.get_legal_moves();
+                            outcomes[idx] = match moves.is_empty() {
+                                true if board.is_player_mated() => worst,
+                                true => Outcome::Draw,
+                                false => Outcome::Unknown,
+                            };
+
+                            for m in &moves {
+                                board.make_move(m);
+                                let outcome = exit_outcome(board, m);
+                                let next = index(
+                                    !white_to_move,
+                                    board.get_king_pos(Color::White).unwrap(),
+                                    board.get_king_pos(Color::Black).unwrap(),
+                                    if m.piece == Piece::Pawn {
+                                        m.end_pos
+                                    } else {
+                                        pawn
+                                    },
+                                );
+                                board.unmake_move(m);
+
+                                match outcome {
+                                    Some(o) if o == best => outcomes[idx] = best,
+                                    Some(_) => (),
+                                    None => successors[idx].push(next as u32),
+                                }
+                            }
+                            if outcomes[idx] == Outcome::Unknown && successors[idx].is_empty() {
+                                outcomes[idx] = worst;
+                            }
+                        }
+
+                        board.set_piece(black_king, None);
+                    }
+                    board.set_piece(white_king, None);
+                }
+                board.set_piece(pawn, None);
+            }
+        }
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for idx in 0..POSITIONS {
+                if outcomes[idx] != Outcome::Unknown {
+                    continue;
+                }
+                let (best, worst) = match idx < POSITIONS / 2 {
+                    true => (Outcome::Win, Outcome::Draw),
+                    false => (Outcome::Draw, Outcome::Win),
+                };
+                let next = successors[idx].iter().map(|n| outcomes[*n as usize]);
+                if next.clone().any(|o| o == best) {
+                    outcomes[idx] = best;
+                    changed = true;
+                } else if next.clone().all(|o| o == worst) {
+                    outcomes[idx] = worst;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut wins = vec![0; POSITIONS / 64];
+        for (idx, outcome) in outcomes.iter().enumerate() {
+            if *outcome == Outcome::Win {
+                wins[idx / 64] |= 1 << (idx % 64);
+            }
+        }
+        KpkBitbase { wins }
+    }
+
+    /// Whether the side with the pawn wins
+    pub fn is_win(
+        &self,
+        strong: Color,
+        strong_to_move: bool,
+        strong_king: usize,
+        weak_king: usize,
+        pawn: usize,
+    ) -> bool {
+        // Mirror the position so the pawn is a white pawn on files a to d
+        let ranks = match strong {
+            Color::White => 0,
+            Color::Black => 56,
+        };
+        let files = if pawn % 8 > 3 { 7 } else { 0 };
+        let flip = |sq: usize| sq ^ ranks ^ files;
+
+        let idx = index(
+            strong_to_move,
+            flip(strong_king),
+            flip(weak_king),
+            flip(pawn),
+        );
+        self.wins[idx / 64] & (1 << (idx % 64)) != 0
+    }
+}
+
+/// The KPK bitbase, generated the first time it is needed
+pub fn kpk() -> &'static KpkBitbase {
+    static KPK: OnceLock<KpkBitbase> = OnceLock::new();
+    KPK.get_or_init(KpkBitbase::generate)
+}
@@ -0,0 +1,247 @@
+use crate::tuner::parse_board;
+use crate::{Engine, GoCommand, Score};
+use ferris_chess_board::{Board, GameStatus, MoveType};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Settings of a self-play data generation run
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatagenOptions {
+    pub games: usize,
+    // Nodes searched for every move
+    pub nodes: usize,
+    pub threads: usize,
+    // Positions games start from, the start position when empty
+    pub book: Vec<String>,
+    // Random moves played from the book position before recording starts
+    pub random_plies: usize,
+    pub seed: u64,
+    // A game is won once the score stays beyond win_score for win_plies plies
+    pub win_score: i32,
+    pub win_plies: usize,
+    // A game is drawn once the score stays within draw_score for draw_plies
+    // plies, from draw_min_ply on
+    pub draw_score: i32,
+    pub draw_plies: usize,
+    pub draw_min_ply: usize,
+    // Longer games are drawn
+    pub max_plies: usize,
+}
+
+impl Default for DatagenOptions {
+    fn default() -> Self {
+        DatagenOptions {
+            games: 100,
+            nodes: 5000,
+            threads: 1,
+            book: vec![],
+            random_plies: 8,
+            seed: 1,
+            win_score: 1000,
+            win_plies: 4,
+            draw_score: 10,
+            draw_plies: 10,
+            draw_min_ply: 60,
+            max_plies: 400,
+        }
+    }
+}
+
+/// A quiet position from a self-play game with the search score and the
+/// game result, both from the point of view of white
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataPosition {
+    pub fen: String,
+    pub score: i32,
+    // 1.0 for a white win, 0.5 for a draw and 0.0 for a black win
+    pub result: f64,
+}
+
+impl DataPosition {
+    /// `<fen>;<score>;<result>`, readable by the tuner
+    pub fn to_line(&self) -> String {
+        format!("{};{};{:.1}", self.fen, self.score, self.result)
+    }
+}
+
+/// xorshift64* generator, so games can be replayed from the seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // splitmix64 spreads nearby seeds and avoids the zero state
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Rng((z ^ (z >> 31)) | 1)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 32) as usize % n
+    }
+}
+
+// Transposition table size of every thread
+const DATAGEN_HASH_MB: usize = 16;
+
+// Openings tried before giving up, as book positions may be game over
+const OPENING_ATTEMPTS: usize = 1000;
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// A book position followed by random moves that doesn't end the game
+fn opening(options: &DatagenOptions, rng: &mut Rng) -> Result<Board, String> {
+    for _ in 0..OPENING_ATTEMPTS {
+        let fen = match options.book.len() {
+            0 => START_FEN,
+            n => &options.book[rng.below(n)],
+        };
+        let mut board = parse_board(fen)?;
+        for _ in 0..options.random_plies {
+            let moves = board.get_legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            board.make_move(&moves[rng.below(moves.len())]);
+        }
+        if board.game_status() == GameStatus::Ongoing {
+            return Ok(board);
+        }
+    }
+    Err("No opening leads to a game in progress".to_string())
+}
+
+fn white_relative(board: &Board, score: i32) -> i32 {
+    if board.black_to_move {
+        -score
+    } else {
+        score
+    }
+}
+
+/// Plays one game with the engine against itself. Positions in check and
+/// positions whose best move is a capture or a promotion are not recorded.
+fn play_game(
+    engine: &mut Engine,
+    options: &DatagenOptions,
+    rng: &mut Rng,
+) -> Result<Vec<DataPosition>, String> {
+    let go_cmd = GoCommand::new(&format!("go nodes {}", options.nodes));
+    engine.new_game();
+    engine.set_position(opening(options, rng)?);
+
+    let mut hashes = vec![engine.board.zobrist.hash];
+    let mut positions: Vec<(String, i32)> = vec![];
+    // Positive while white is winning, negative while black is
+    let mut win_streak: i32 = 0;
+    let mut draw_streak = 0;
+
+    let result = loop {
+        match engine.board.game_status() {
+            GameStatus::Checkmate if engine.board.black_to_move => break 1.0,
+            GameStatus::Checkmate => break 0.0,
+            GameStatus::Ongoing => (),
+            _ => break 0.5,
+        }
+        let hash = engine.board.zobrist.hash;
+        let ply = hashes.len() - 1;
+        if hashes.iter().filter(|h| **h == hash).count() >= 3 || ply >= options.max_plies {
+            break 0.5;
+        }
+
+        let Some(m) = engine.iter_deepening(&go_cmd) else {
+            break 0.5;
+        };
+        let score = engine.search_info().map(|info| info.score);
+        let cp = match score {
+            Some(Score::CentiPawns(cp)) => cp,
+            // The side to move mates or gets mated
+            Some(Score::Mate(n)) => {
+                break if (n > 0) != engine.board.black_to_move {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            None => break 0.5,
+        };
+        let white_cp = white_relative(&engine.board, cp);
+
+        let is_tactical =
+            m.capture.is_some() || !matches!(m.move_type, MoveType::Regular | MoveType::Castling);
+        if !engine.board.is_player_mated() && !is_tactical {
+            positions.push((engine.board.to_fen(), white_cp));
+        }
+
+        win_streak = match white_cp {
+            cp if cp >= options.win_score => win_streak.max(0) + 1,
+            cp if cp <= -options.win_score => win_streak.min(0) - 1,
+            _ => 0,
+        };
+        if win_streak.unsigned_abs() as usize >= options.win_plies {
+            break if win_streak > 0 { 1.0 } else { 0.0 };
+        }
+        draw_streak = match ply >= options.draw_min_ply && cp.abs() <= options.draw_score {
+            true => draw_streak + 1,
+            false => 0,
+        };
+        if draw_streak >= options.draw_plies {
+            break 0.5;
+        }
+
+        engine.make_game_move(&m);
+        hashes.push(engine.board.zobrist.hash);
+    };
+
+    Ok(positions
+        .into_iter()
+        .map(|(fen, score)| DataPosition { fen, score, result })
+        .collect())
+}
+
+/// Plays options.games self-play games on options.threads threads, each with
+/// its own copy of engine. on_game is called on the calling thread with the
+/// positions of every finished game.
+pub fn generate<F>(engine: &Engine, options: &DatagenOptions, mut on_game: F) -> Result<(), String>
+where
+    F: FnMut(Vec<DataPosition>),
+{
+    for fen in &options.book {
+        parse_board(fen)?;
+    }
+
+    let next_game = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..options.threads.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+            let mut engine = engine.clone();
+            engine.set_threads(1);
+            engine.set_hash_size(DATAGEN_HASH_MB);
+            engine.set_print_info(false);
+            s.spawn(move || loop {
+                let game = next_game.fetch_add(1, Ordering::Relaxed);
+                if game >= options.games {
+                    break;
+                }
+                let mut rng = Rng::new(options.seed ^ game as u64);
+                let positions = play_game(&mut engine, options, &mut rng);
+                if sender.send(positions).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for positions in receiver {
+            on_game(positions?);
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,296 @@
+use crate::bitbase;
+use crate::pawn_structure::relative_rank;
+use ferris_chess_board::{Board, Color, Piece};
+use std::fmt;
+
+// Added to positions the strong side is sure to win, above any ordinary
+// evaluation but far below the mate scores
+pub const KNOWN_WIN: i32 = 10000;
+
+// Scale factors are out of SCALE_NORMAL
+pub const SCALE_NORMAL: i32 = 64;
+
+const PIECES: [Piece; 5] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+];
+
+/// Numbers of pawns, knights, bishops, rooks and queens of white at index 0
+/// and of black at 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialSignature {
+    pub counts: [[u8; 5]; 2],
+}
+
+impl MaterialSignature {
+    pub fn of(board: &Board) -> Self {
+        let mut counts = [[0; 5]; 2];
+        for (color_counts, pieces) in counts.iter_mut().zip([&board.pieces_w, &board.pieces_b]) {
+            for sq in pieces {
+                if let Some((_, piece)) = board.data[*sq] {
+                    if let Some(idx) = PIECES.iter().position(|p| *p == piece) {
+                        color_counts[idx] += 1;
+                    }
+                }
+            }
+        }
+        MaterialSignature { counts }
+    }
+
+    /// The signature with the colors swapped
+    pub fn flipped(&self) -> Self {
+        MaterialSignature {
+            counts: [self.counts[1], self.counts[0]],
+        }
+    }
+}
+
+impl fmt::Display for MaterialSignature {
+    /// Pieces of white then of black from the king down, such as KBNK
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for counts in self.counts {
+            write!(f, "K")?;
+            for (idx, letter) in ['P', 'N', 'B', 'R', 'Q'].iter().enumerate().rev() {
+                for _ in 0..counts[idx] {
+                    write!(f, "{}", letter)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndgameKind {
+    // Evaluated by their own functions
+    Kxk,
+    Kbnk,
+    Kpk,
+    // The evaluation is scaled down towards a draw
+    Krkr,
+    OppositeBishops,
+    Kbpk,
+}
+
+/// An endgame recognized by its material, with the side that is trying to win
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Endgame {
+    pub kind: EndgameKind,
+    pub strong: Color,
+}
+
+/// Chebyshev distance, the number of king moves between two squares
+fn distance(a: usize, b: usize) -> i32 {
+    (a % 8).abs_diff(b % 8).max((a / 8).abs_diff(b / 8)) as i32
+}
+
+fn is_dark_square(sq: usize) -> bool {
+    (sq % 8 + sq / 8).is_multiple_of(2)
+}
+
+/// From 0 in the center to 120 in the corners
+fn push_to_edge(sq: usize) -> i32 {
+    let (file, rank) = (sq % 8, sq / 8);
+    let center_distance = (2 * file as i32 - 7).abs() / 2 + (2 * rank as i32 - 7).abs() / 2;
+    let edge_distance = file.min(7 - file).min(rank).min(7 - rank) as i32;
+    10 * center_distance + 20 * (3 - edge_distance)
+}
+
+/// From 0 for kings on opposite corners to 120 for kings next to each other
+fn push_close(a: usize, b: usize) -> i32 {
+    20 * (7 - distance(a, b))
+}
+
+fn squares_of(board: &Board, color: Color, piece: Piece) -> Vec<usize> {
+    let pieces = match color {
+        Color::White => &board.pieces_w,
+        Color::Black => &board.pieces_b,
+    };
+    let mut squares: Vec<usize> = pieces
+        .iter()
+        .copied()
+        .filter(|sq| board.data[*sq] == Some((color, piece)))
+        .collect();
+    squares.sort_unstable();
+    squares
+}
+
+fn other(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+/// Endgame of the material counts with the strong side at index 0
+fn recognize(board: &Board, counts: &[[u8; 5]; 2], strong: Color) -> Option<EndgameKind> {
+    let non_pawn_material: i32 = PIECES
+        .iter()
+        .zip(counts[0])
+        .skip(1)
+        .map(|(piece, count)| *piece as i32 * count as i32)
+        .sum();
+
+    match counts {
+        [[0, 1, 1, 0, 0], [0, 0, 0, 0, 0]] => Some(EndgameKind::Kbnk),
+        [[1, 0, 0, 0, 0], [0, 0, 0, 0, 0]] => Some(EndgameKind::Kpk),
+        [_, [0, 0, 0, 0, 0]] if non_pawn_material >= Piece::Rook as i32 => Some(EndgameKind::Kxk),
+        [[pawns, 0, 1, 0, 0], [0, 0, 0, 0, 0]] if *pawns > 0 => Some(EndgameKind::Kbpk),
+        [[0, 0, 0, 1, 0], [0, 0, 0, 1, 0]] => Some(EndgameKind::Krkr),
+        [[_, 0, 1, 0, 0], [_, 0, 1, 0, 0]] => {
+            let strong_bishop = squares_of(board, strong, Piece::Bishop);
+            let weak_bishop = squares_of(board, other(strong), Piece::Bishop);
+            let opposite = is_dark_square(strong_bishop[0]) != is_dark_square(weak_bishop[0]);
+            opposite.then_some(EndgameKind::OppositeBishops)
+        }
+        _ => None,
+    }
+}
+
+impl Endgame {
+    /// The endgame the material on board is recognized as, if any
+    pub fn probe(board: &Board) -> Option<Self> {
+        let signature = MaterialSignature::of(board);
+        if let Some(kind) = recognize(board, &signature.counts, Color::White) {
+            return Some(Endgame {
+                kind,
+                strong: Color::White,
+            });
+        }
+        recognize(board, &signature.flipped().counts, Color::Black).map(|kind| Endgame {
+            kind,
+            strong: Color::Black,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            EndgameKind::Kxk => "KXK",
+            EndgameKind::Kbnk => "KBNK",
+            EndgameKind::Kpk => "KPK",
+            EndgameKind::Krkr => "KRKR",
+            EndgameKind::OppositeBishops => "Opposite bishops",
+            EndgameKind::Kbpk => "KBPK",
+        }
+    }
+
+    /// Replaces or scales eval, the evaluation of board from the point of
+    /// view of the side to move
+    pub fn evaluate(&self, board: &Board, eval: i32) -> i32 {
+        let strong_to_move = board.black_to_move == (self.strong == Color::Black);
+        let strong_eval = if strong_to_move { eval } else { -eval };
+        let (Some(strong_king), Some(weak_king)) = (
+            board.get_king_pos(self.strong),
+            board.get_king_pos(other(self.strong)),
+        ) else {
+            return eval;
+        };
+
+        let score = match self.kind {
+            EndgameKind::Kxk => self.kxk(board, strong_king, weak_king, strong_eval),
+            EndgameKind::Kbnk => self.kbnk(board, strong_king, weak_king),
+            EndgameKind::Kpk => self.kpk(board, strong_king, weak_king),
+            _ => strong_eval * self.scale_factor(board, weak_king) / SCALE_NORMAL,
+        };
+        if strong_to_move {
+            score
+        } else {
+            -score
+        }
+    }
+
+    fn material(&self, board: &Board) -> i32 {
+        PIECES
+            .iter()
+            .map(|piece| *piece as i32 * squares_of(board, self.strong, *piece).len() as i32)
+            .sum()
+    }
+
+    /// Drives the lone king to the edge and the strong king towards it, on
+    /// top of the evaluation of the strong side
+    fn kxk(&self, board: &Board, strong_king: usize, weak_king: usize, strong_eval: i32) -> i32 {
+        let count = |piece| squares_of(board, self.strong, piece).len();
+        let score = strong_eval + push_to_edge(weak_king) + push_close(strong_king, weak_king);
+
+        // Bishops of a single color, even after an underpromotion, can't mate
+        let bishops = squares_of(board, self.strong, Piece::Bishop);
+        let both_colors = bishops.iter().any(|sq| is_dark_square(*sq))
+            && bishops.iter().any(|sq| !is_dark_square(*sq));
+        let can_mate = count(Piece::Queen) > 0
+            || count(Piece::Rook) > 0
+            || both_colors
+            || count(Piece::Bishop) > 0 && count(Piece::Knight) > 0;
+        if can_mate {
+            score + KNOWN_WIN
+        } else if count(Piece::Pawn) == 0 {
+            // Such as two knights, which can't force mate
+            0
+        } else {
+            score
+        }
+    }
+
+    /// Mate can only be forced in a corner of the color of the bishop
+    fn kbnk(&self, board: &Board, strong_king: usize, weak_king: usize) -> i32 {
+        let bishop = squares_of(board, self.strong, Piece::Bishop)[0];
+        let corners = match is_dark_square(bishop) {
+            true => [0, 63],
+            false => [7, 56],
+        };
+        let corner_distance = corners
+            .iter()
+            .map(|c| distance(weak_king, *c))
+            .min()
+            .unwrap();
+
+        KNOWN_WIN
+            + self.material(board)
+            + 40 * (7 - corner_distance)
+            + push_close(strong_king, weak_king)
+    }
+
+    /// Exactly won or drawn, looked up in the bitbase
+    fn kpk(&self, board: &Board, strong_king: usize, weak_king: usize) -> i32 {
+        let pawn = squares_of(board, self.strong, Piece::Pawn)[0];
+        let strong_to_move = board.black_to_move == (self.strong == Color::Black);
+        if bitbase::kpk().is_win(self.strong, strong_to_move, strong_king, weak_king, pawn) {
+            KNOWN_WIN + Piece::Pawn as i32 + 20 * relative_rank(self.strong, pawn) as i32
+        } else {
+            0
+        }
+    }
+
+    fn scale_factor(&self, board: &Board, weak_king: usize) -> i32 {
+        match self.kind {
+            EndgameKind::Krkr => 4,
+            EndgameKind::OppositeBishops => {
+                let strong_pawns = squares_of(board, self.strong, Piece::Pawn).len();
+                let weak_pawns = squares_of(board, other(self.strong), Piece::Pawn).len();
+                (16 + 8 * strong_pawns.abs_diff(weak_pawns) as i32).min(SCALE_NORMAL)
+            }
+            EndgameKind::Kbpk => {
+                // A rook pawn promoting on a square the bishop doesn't control
+                // is a draw once the lone king reaches the corner
+                let pawns = squares_of(board, self.strong, Piece::Pawn);
+                let file = pawns[0] % 8;
+                let promotion = match self.strong {
+                    Color::White => 56 + file,
+                    Color::Black => file,
+                };
+                let bishop = squares_of(board, self.strong, Piece::Bishop)[0];
+                let is_wrong_rook_pawn = (file == 0 || file == 7)
+                    && pawns.iter().all(|sq| sq % 8 == file)
+                    && is_dark_square(bishop) != is_dark_square(promotion);
+                if is_wrong_rook_pawn && distance(weak_king, promotion) <= 1 {
+                    0
+                } else {
+                    SCALE_NORMAL
+                }
+            }
+            _ => SCALE_NORMAL,
+        }
+    }
+}
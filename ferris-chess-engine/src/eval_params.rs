@@ -0,0 +1,412 @@
+use std::fs;
+
+/// Weights of the PeSTO evaluation and its pawn structure, mobility, king
+/// safety and positional terms. Pairs are middlegame and endgame values.
+/// Parameters are saved as a text file of `name = [values]` lines (a subset
+/// of TOML), and a file only needs to list the values it changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    // Piece values in the order pawn, knight, bishop, rook, queen, king
+    pub mg_piece_values: [i32; 6],
+    pub eg_piece_values: [i32; 6],
+    // Piece-square tables as seen by white, with a8 at index 0
+    pub mg_pawn_table: [i32; 64],
+    pub eg_pawn_table: [i32; 64],
+    pub mg_knight_table: [i32; 64],
+    pub eg_knight_table: [i32; 64],
+    pub mg_bishop_table: [i32; 64],
+    pub eg_bishop_table: [i32; 64],
+    pub mg_rook_table: [i32; 64],
+    pub eg_rook_table: [i32; 64],
+    pub mg_queen_table: [i32; 64],
+    pub eg_queen_table: [i32; 64],
+    pub mg_king_table: [i32; 64],
+    pub eg_king_table: [i32; 64],
+
+    // Pawn structure penalties per pawn
+    pub doubled: [i32; 2],
+    pub isolated: [i32; 2],
+    pub backward: [i32; 2],
+    // Pawn bonuses indexed by the rank of the pawn as seen from its own side
+    pub connected_mg: [i32; 8],
+    pub connected_eg: [i32; 8],
+    pub passed_mg: [i32; 8],
+    pub passed_eg: [i32; 8],
+    // Added for passed pawns with no pieces on the squares in front of them
+    pub free_path_mg: [i32; 8],
+    pub free_path_eg: [i32; 8],
+
+    // Bonus per safe square above the number of squares a piece typically reaches
+    pub knight_mobility: [i32; 2],
+    pub bishop_mobility: [i32; 2],
+    pub rook_mobility: [i32; 2],
+    pub queen_mobility: [i32; 2],
+
+    // Attack units of a knight, bishop, rook and queen per attacked square
+    // of the enemy king zone
+    pub king_attack_units: [i32; 4],
+    // Percentage of the attack units that counts, by number of attackers
+    pub king_attack_scale: [i32; 8],
+    // Centipawns per attack unit
+    pub king_attack_weight: [i32; 2],
+    // Bonus for an own pawn in front of the king, by its distance in ranks
+    pub pawn_shield_mg: [i32; 3],
+    pub pawn_shield_eg: [i32; 3],
+    // Penalty for an enemy pawn in front of the king, by its distance in ranks
+    pub pawn_storm_mg: [i32; 5],
+    pub pawn_storm_eg: [i32; 5],
+    // Files next to the king without own pawns, or without any pawns
+    pub king_semi_open_file: [i32; 2],
+    pub king_open_file: [i32; 2],
+
+    pub bishop_pair: [i32; 2],
+    pub rook_open_file: [i32; 2],
+    pub rook_semi_open_file: [i32; 2],
+    pub queen_open_file: [i32; 2],
+    pub queen_semi_open_file: [i32; 2],
+    pub rook_on_seventh: [i32; 2],
+    pub knight_outpost: [i32; 2],
+    pub bishop_outpost: [i32; 2],
+    // A piece attacked by a less valuable enemy piece, or attacked and undefended
+    pub threat_by_lesser: [i32; 2],
+    pub hanging: [i32; 2],
+    // Bonus for the side to move
+    pub tempo: [i32; 2],
+}
+
+#[rustfmt::skip]
+const MG_PAWN_TABLE: [i32; 64] = [
+    0,   0,   0,   0,   0,   0,  0,   0,
+   98, 134,  61,  95,  68, 126, 34, -11,
+   -6,   7,  26,  31,  65,  56, 25, -20,
+  -14,  13,   6,  21,  23,  12, 17, -23,
+  -27,  -2,  -5,  12,  17,   6, 10, -25,
+  -26,  -4,  -4, -10,   3,   3, 33, -12,
+  -35,  -1, -20, -23, -15,  24, 38, -22,
+    0,   0,   0,   0,   0,   0,  0,   0,
+];
+
+#[rustfmt::skip]
+const EG_PAWN_TABLE: [i32; 64] = [
+    0,   0,   0,   0,   0,   0,   0,   0,
+  178, 173, 158, 134, 147, 132, 165, 187,
+   94, 100,  85,  67,  56,  53,  82,  84,
+   32,  24,  13,   5,  -2,   4,  17,  17,
+   13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+    4,   7,  -6,   1,   0,  -5,  -1,  -8,
+   13,   8,   8,  10,  13,   0,   2,  -7,
+    0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const MG_KNIGHT_TABLE: [i32; 64] = [
+    -167, -89, -34, -49,  61, -97, -15, -107,
+     -73, -41,  72,  36,  23,  62,   7,  -17,
+     -47,  60,  37,  65,  84, 129,  73,   44,
+      -9,  17,  19,  53,  37,  69,  18,   22,
+     -13,   4,  16,  13,  28,  19,  21,   -8,
+     -23,  -9,  12,  10,  19,  17,  25,  -16,
+     -29, -53, -12,  -3,  -1,  18, -14,  -19,
+    -105, -21, -58, -33, -17, -28, -19,  -23,
+];
+
+#[rustfmt::skip]
+const EG_KNIGHT_TABLE: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+#[rustfmt::skip]
+const MG_BISHOP_TABLE: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+#[rustfmt::skip]
+const EG_BISHOP_TABLE: [i32; 64] = [
+    -14, -21, -11,  -8, -7,  -9, -17, -24,
+     -8,  -4,   7, -12, -3, -13,  -4, -14,
+      2,  -8,   0,  -1, -2,   6,   0,   4,
+     -3,   9,  12,   9, 14,  10,   3,   2,
+     -6,   3,  13,  19,  7,  10,  -3,  -9,
+    -12,  -3,   8,  10, 13,   3,  -7, -15,
+    -14, -18,  -7,  -1,  4,  -9, -15, -27,
+    -23,  -9, -23,  -5, -9, -16,  -5, -17,
+];
+
+#[rustfmt::skip]
+const MG_ROOK_TABLE: [i32; 64] = [
+    32,  42,  32,  51, 63,  9,  31,  43,
+    27,  32,  58,  62, 80, 67,  26,  44,
+    -5,  19,  26,  36, 17, 45,  61,  16,
+   -24, -11,   7,  26, 24, 35,  -8, -20,
+   -36, -26, -12,  -1,  9, -7,   6, -23,
+   -45, -25, -16, -17,  3,  0,  -5, -33,
+   -44, -16, -20,  -9, -1, 11,  -6, -71,
+   -19, -13,   1,  17, 16,  7, -37, -26,
+];
+
+#[rustfmt::skip]
+const EG_ROOK_TABLE: [i32; 64] = [
+    13, 10, 18, 15, 12,  12,   8,   5,
+    11, 13, 13, 11, -3,   3,   8,   3,
+     7,  7,  7,  5,  4,  -3,  -5,  -3,
+     4,  3, 13,  1,  2,   1,  -1,   2,
+     3,  5,  8,  4, -5,  -6,  -8, -11,
+    -4,  0, -5, -1, -7, -12,  -8, -16,
+    -6, -6,  0,  2, -9,  -9, -11,  -3,
+    -9,  2,  3, -1, -5, -13,   4, -20,
+];
+
+#[rustfmt::skip]
+const MG_QUEEN_TABLE: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+#[rustfmt::skip]
+const EG_QUEEN_TABLE: [i32; 64] = [
+    -9,  22,  22,  27,  27,  19,  10,  20,
+   -17,  20,  32,  41,  58,  25,  30,   0,
+   -20,   6,   9,  49,  47,  35,  19,   9,
+     3,  22,  24,  45,  57,  40,  57,  36,
+   -18,  28,  19,  47,  31,  34,  39,  23,
+   -16, -27,  15,   6,   9,  17,  10,   5,
+   -22, -23, -30, -16, -16, -23, -36, -32,
+   -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+#[rustfmt::skip]
+const MG_KING_TABLE: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+#[rustfmt::skip]
+const EG_KING_TABLE: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43
+];
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams {
+            mg_piece_values: [82, 337, 365, 477, 1025, 0],
+            eg_piece_values: [94, 281, 297, 512, 936, 0],
+            mg_pawn_table: MG_PAWN_TABLE,
+            eg_pawn_table: EG_PAWN_TABLE,
+            mg_knight_table: MG_KNIGHT_TABLE,
+            eg_knight_table: EG_KNIGHT_TABLE,
+            mg_bishop_table: MG_BISHOP_TABLE,
+            eg_bishop_table: EG_BISHOP_TABLE,
+            mg_rook_table: MG_ROOK_TABLE,
+            eg_rook_table: EG_ROOK_TABLE,
+            mg_queen_table: MG_QUEEN_TABLE,
+            eg_queen_table: EG_QUEEN_TABLE,
+            mg_king_table: MG_KING_TABLE,
+            eg_king_table: EG_KING_TABLE,
+            doubled: [-10, -25],
+            isolated: [-8, -15],
+            backward: [-9, -12],
+            connected_mg: [0, 4, 6, 10, 18, 35, 60, 0],
+            connected_eg: [0, 2, 3, 6, 12, 25, 40, 0],
+            passed_mg: [0, 5, 5, 10, 25, 45, 80, 0],
+            passed_eg: [0, 10, 15, 25, 45, 80, 130, 0],
+            free_path_mg: [0, 0, 0, 5, 10, 20, 35, 0],
+            free_path_eg: [0, 0, 5, 10, 25, 45, 70, 0],
+            knight_mobility: [4, 4],
+            bishop_mobility: [5, 5],
+            rook_mobility: [2, 4],
+            queen_mobility: [1, 2],
+            king_attack_units: [2, 2, 3, 5],
+            king_attack_scale: [0, 0, 50, 75, 88, 94, 97, 99],
+            king_attack_weight: [8, 2],
+            pawn_shield_mg: [0, 15, 8],
+            pawn_shield_eg: [0, 0, 0],
+            pawn_storm_mg: [0, 0, -25, -15, -5],
+            pawn_storm_eg: [0, 0, -5, 0, 0],
+            king_semi_open_file: [-15, 0],
+            king_open_file: [-25, 0],
+            bishop_pair: [30, 50],
+            rook_open_file: [40, 15],
+            rook_semi_open_file: [20, 10],
+            queen_open_file: [10, 5],
+            queen_semi_open_file: [5, 5],
+            rook_on_seventh: [20, 35],
+            knight_outpost: [25, 15],
+            bishop_outpost: [15, 5],
+            threat_by_lesser: [-40, -30],
+            hanging: [-30, -20],
+            tempo: [15, 5],
+        }
+    }
+}
+
+impl EvalParams {
+    /// Every parameter by name, in the order they are saved
+    pub fn fields_mut(&mut self) -> Vec<(&'static str, &mut [i32])> {
+        vec![
+            ("mg_piece_values", &mut self.mg_piece_values),
+            ("eg_piece_values", &mut self.eg_piece_values),
+            ("mg_pawn_table", &mut self.mg_pawn_table),
+            ("eg_pawn_table", &mut self.eg_pawn_table),
+            ("mg_knight_table", &mut self.mg_knight_table),
+            ("eg_knight_table", &mut self.eg_knight_table),
+            ("mg_bishop_table", &mut self.mg_bishop_table),
+            ("eg_bishop_table", &mut self.eg_bishop_table),
+            ("mg_rook_table", &mut self.mg_rook_table),
+            ("eg_rook_table", &mut self.eg_rook_table),
+            ("mg_queen_table", &mut self.mg_queen_table),
+            ("eg_queen_table", &mut self.eg_queen_table),
+            ("mg_king_table", &mut self.mg_king_table),
+            ("eg_king_table", &mut self.eg_king_table),
+            ("doubled", &mut self.doubled),
+            ("isolated", &mut self.isolated),
+            ("backward", &mut self.backward),
+            ("connected_mg", &mut self.connected_mg),
+            ("connected_eg", &mut self.connected_eg),
+            ("passed_mg", &mut self.passed_mg),
+            ("passed_eg", &mut self.passed_eg),
+            ("free_path_mg", &mut self.free_path_mg),
+            ("free_path_eg", &mut self.free_path_eg),
+            ("knight_mobility", &mut self.knight_mobility),
+            ("bishop_mobility", &mut self.bishop_mobility),
+            ("rook_mobility", &mut self.rook_mobility),
+            ("queen_mobility", &mut self.queen_mobility),
+            ("king_attack_units", &mut self.king_attack_units),
+            ("king_attack_scale", &mut self.king_attack_scale),
+            ("king_attack_weight", &mut self.king_attack_weight),
+            ("pawn_shield_mg", &mut self.pawn_shield_mg),
+            ("pawn_shield_eg", &mut self.pawn_shield_eg),
+            ("pawn_storm_mg", &mut self.pawn_storm_mg),
+            ("pawn_storm_eg", &mut self.pawn_storm_eg),
+            ("king_semi_open_file", &mut self.king_semi_open_file),
+            ("king_open_file", &mut self.king_open_file),
+            ("bishop_pair", &mut self.bishop_pair),
+            ("rook_open_file", &mut self.rook_open_file),
+            ("rook_semi_open_file", &mut self.rook_semi_open_file),
+            ("queen_open_file", &mut self.queen_open_file),
+            ("queen_semi_open_file", &mut self.queen_semi_open_file),
+            ("rook_on_seventh", &mut self.rook_on_seventh),
+            ("knight_outpost", &mut self.knight_outpost),
+            ("bishop_outpost", &mut self.bishop_outpost),
+            ("threat_by_lesser", &mut self.threat_by_lesser),
+            ("hanging", &mut self.hanging),
+            ("tempo", &mut self.tempo),
+        ]
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut params = self.clone();
+        let mut text = String::from("# Ferris Chess evaluation parameters\n");
+        for (name, values) in params.fields_mut() {
+            let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            if values.len() > 8 {
+                // Tables are written one rank per line
+                let rows: Vec<String> = values.chunks(8).map(|row| row.join(", ")).collect();
+                text += &format!("{} = [\n    {},\n]\n", name, rows.join(",\n    "));
+            } else {
+                text += &format!("{} = [{}]\n", name, values.join(", "));
+            }
+        }
+        text
+    }
+
+    /// Parameters from text written by to_text. Parameters missing from the
+    /// text keep their default values.
+    pub fn from_text(text: &str) -> Result<EvalParams, String> {
+        let mut params = EvalParams::default();
+
+        // Arrays may span several lines, so statements end at the closing bracket
+        let mut statements = vec![];
+        let mut statement = String::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            statement += line;
+            statement += " ";
+            if statement.matches('[').count() == statement.matches(']').count() {
+                if !statement.trim().is_empty() {
+                    statements.push(statement.trim().to_string());
+                }
+                statement.clear();
+            }
+        }
+        if !statement.trim().is_empty() {
+            return Err(format!("Unclosed array: {}", statement.trim()));
+        }
+
+        let mut fields = params.fields_mut();
+        for statement in statements {
+            let (name, value) = statement
+                .split_once('=')
+                .ok_or(format!("Expected name = value: {}", statement))?;
+            let name = name.trim();
+            let values = value
+                .trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<i32>())
+                .collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| format!("Invalid number in {}", name))?;
+
+            let (_, field) = fields
+                .iter_mut()
+                .find(|(field_name, _)| *field_name == name)
+                .ok_or(format!("Unknown parameter: {}", name))?;
+            if field.len() != values.len() {
+                return Err(format!(
+                    "{} needs {} values, got {}",
+                    name,
+                    field.len(),
+                    values.len()
+                ));
+            }
+            field.copy_from_slice(&values);
+        }
+        drop(fields);
+
+        Ok(params)
+    }
+
+    pub fn load(path: &str) -> Result<EvalParams, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+        EvalParams::from_text(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("Can't write {}: {}", path, e))
+    }
+}
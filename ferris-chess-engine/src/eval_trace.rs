@@ -0,0 +1,93 @@
+use ferris_chess_board::Board;
+use std::fmt;
+
+/// Contribution of one evaluation term
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceTerm {
+    pub name: &'static str,
+    // Middlegame and endgame score of white at index 0 and black at 1, each
+    // from its own point of view
+    pub sides: [(i32, i32); 2],
+}
+
+impl TraceTerm {
+    /// Middlegame and endgame score from the point of view of white
+    pub fn total(&self) -> (i32, i32) {
+        let [(w_mg, w_eg), (b_mg, b_eg)] = self.sides;
+        (w_mg - b_mg, w_eg - b_eg)
+    }
+}
+
+/// Breakdown of a static evaluation into its terms, printed by the UCI eval
+/// command
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalTrace {
+    pub terms: Vec<TraceTerm>,
+    // Weights of the middlegame and endgame scores out of 24, None for
+    // evaluations that are not tapered
+    pub phase: Option<(i32, i32)>,
+    // Name of the recognized endgame whose knowledge is applied to the score
+    pub endgame: Option<&'static str>,
+    // Evaluation from the point of view of white
+    pub white_score: i32,
+    // Evaluation from the point of view of the side to move
+    pub score: i32,
+}
+
+impl EvalTrace {
+    /// Trace of an evaluation without separate terms, such as a network
+    pub fn from_score(board: &Board, score: i32) -> Self {
+        EvalTrace {
+            terms: vec![],
+            phase: None,
+            endgame: None,
+            white_score: if board.black_to_move { -score } else { score },
+            score,
+        }
+    }
+
+    /// Sum of the terms from the point of view of white
+    pub fn total(&self) -> (i32, i32) {
+        self.terms.iter().fold((0, 0), |(mg, eg), term| {
+            let (term_mg, term_eg) = term.total();
+            (mg + term_mg, eg + term_eg)
+        })
+    }
+}
+
+impl fmt::Display for EvalTrace {
+    /// Table of the terms in centipawns, similar to the eval command of Stockfish
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let separator = " -------------+-------------+-------------+-------------";
+        if !self.terms.is_empty() {
+            writeln!(f, "         Term |    White    |    Black    |    Total")?;
+            writeln!(f, "              |   MG    EG  |   MG    EG  |   MG    EG")?;
+            writeln!(f, "{}", separator)?;
+            for term in &self.terms {
+                let [(w_mg, w_eg), (b_mg, b_eg)] = term.sides;
+                let (mg, eg) = term.total();
+                writeln!(
+                    f,
+                    " {:>12} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}",
+                    term.name, w_mg, w_eg, b_mg, b_eg, mg, eg
+                )?;
+            }
+            writeln!(f, "{}", separator)?;
+            let (mg, eg) = self.total();
+            writeln!(
+                f,
+                " {:>12} |             |             | {:>5} {:>5}",
+                "Total", mg, eg
+            )?;
+            writeln!(f)?;
+        }
+        if let Some((mg_phase, eg_phase)) = self.phase {
+            writeln!(f, "Phase: mg {} eg {} of 24", mg_phase, eg_phase)?;
+        }
+        if let Some(endgame) = self.endgame {
+            writeln!(f, "Endgame: {}", endgame)?;
+        }
+        writeln!(f, "Evaluation: {} (white side)", self.white_score)?;
+        write!(f, "Evaluation: {} (side to move)", self.score)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::eval_params::EvalParams;
+use crate::eval_trace::EvalTrace;
+use crate::nnue::{Network, NnueEvaluator};
+use crate::pesto::PestoEvaluator;
+use ferris_chess_board::{Board, Color, MoveData, Piece};
+use std::fs;
+
+/// Static evaluation of the positions reached in search. Scores are in
+/// centipawns from the point of view of the side to move. Every search
+/// thread evaluates with its own copy, so implementations may keep caches.
+pub trait Evaluator: Send {
+    fn evaluate(&mut self, board: &Board) -> i32;
+
+    /// Called before m is made on board in search, for evaluators updating
+    /// their state incrementally
+    fn make_move(&mut self, _board: &Board, _m: &MoveData) {}
+
+    /// Called after m is unmade, board being the position it was made from
+    fn unmake_move(&mut self, _board: &Board, _m: &MoveData) {}
+
+    /// Replaces the parameters of evaluators built from EvalParams, so the
+    /// tuner can try parameters without creating an evaluator each time
+    fn set_params(&mut self, _params: &EvalParams) {}
+
+    /// The evaluation split into its terms. Evaluators without separate
+    /// terms only report the score.
+    fn trace(&mut self, board: &Board) -> EvalTrace {
+        EvalTrace::from_score(board, self.evaluate(board))
+    }
+
+    /// Boxed copy, for the engine clones of the search threads
+    fn clone_box(&self) -> Box<dyn Evaluator>;
+}
+
+impl Clone for Box<dyn Evaluator> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Counts material only, using the piece values of the board crate
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        let score: i32 = board
+            .data
+            .iter()
+            .flatten()
+            .filter(|(_, piece)| *piece != Piece::King)
+            .map(|(color, piece)| match color {
+                Color::White => *piece as i32,
+                Color::Black => -(*piece as i32),
+            })
+            .sum();
+
+        if board.black_to_move {
+            -score
+        } else {
+            score
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Evaluator> {
+        Box::new(*self)
+    }
+}
+
+/// Evaluator for an EvalFile: a network file starting with the NNUE magic
+/// bytes, or PeSTO evaluation parameters otherwise
+pub fn load_evaluator(path: &str) -> Result<Box<dyn Evaluator>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    if Network::is_network(&bytes) {
+        let network = Network::from_bytes(&bytes)?;
+        return Ok(Box::new(NnueEvaluator::new(network)));
+    }
+    let text = String::from_utf8(bytes).map_err(|_| format!("{} is not a text file", path))?;
+    let params = EvalParams::from_text(&text)?;
+    Ok(Box::new(PestoEvaluator::with_params(params)))
+}
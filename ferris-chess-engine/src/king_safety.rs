@@ -0,0 +1,107 @@
+use crate::eval_params::EvalParams;
+use ferris_chess_board::{Board, Color, Piece};
+
+/// Enemy pieces attacking squares around a king
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KingAttacks {
+    pub attackers: usize,
+    // Sum over the attackers of their weight times the attacked zone squares
+    pub units: i32,
+}
+
+/// Ranks from the king to the pawn of the given color closest to it in front
+/// of the king on file, if any
+fn pawn_distance(
+    board: &Board,
+    king_pos: usize,
+    king_color: Color,
+    file: usize,
+    pawn_color: Color,
+) -> Option<usize> {
+    let king_rank = king_pos / 8;
+    let ranks: Vec<usize> = match king_color {
+        Color::White => (king_rank + 1..8).collect(),
+        Color::Black => (0..king_rank).rev().collect(),
+    };
+    ranks
+        .into_iter()
+        .find(|rank| board.data[rank * 8 + file] == Some((pawn_color, Piece::Pawn)))
+        .map(|rank| rank.abs_diff(king_rank))
+}
+
+/// Safety of the king of color from its point of view: the attacks on its
+/// zone, its pawn shield, enemy pawn storms and open files next to it
+fn king_safety(
+    board: &Board,
+    color: Color,
+    attacks: KingAttacks,
+    params: &EvalParams,
+) -> (i32, i32) {
+    let Some(king_pos) = board.get_king_pos(color) else {
+        return (0, 0);
+    };
+    let enemy = match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+
+    let scale_idx = attacks.attackers.min(params.king_attack_scale.len() - 1);
+    let scale = params.king_attack_scale[scale_idx];
+    let mut mg = -attacks.units * params.king_attack_weight[0] * scale / 100;
+    let mut eg = -attacks.units * params.king_attack_weight[1] * scale / 100;
+
+    let king_file = king_pos % 8;
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let shield = pawn_distance(board, king_pos, color, file, color);
+        let storm = pawn_distance(board, king_pos, color, file, enemy);
+
+        if let Some(d) = shield.filter(|d| *d < params.pawn_shield_mg.len()) {
+            mg += params.pawn_shield_mg[d];
+            eg += params.pawn_shield_eg[d];
+        }
+        if let Some(d) = storm.filter(|d| *d < params.pawn_storm_mg.len()) {
+            mg += params.pawn_storm_mg[d];
+            eg += params.pawn_storm_eg[d];
+        }
+
+        let own_pawn_on_file =
+            (0..8).any(|rank| board.data[rank * 8 + file] == Some((color, Piece::Pawn)));
+        let enemy_pawn_on_file =
+            (0..8).any(|rank| board.data[rank * 8 + file] == Some((enemy, Piece::Pawn)));
+        if !own_pawn_on_file {
+            let [file_mg, file_eg] = if enemy_pawn_on_file {
+                params.king_semi_open_file
+            } else {
+                params.king_open_file
+            };
+            mg += file_mg;
+            eg += file_eg;
+        }
+    }
+
+    (mg, eg)
+}
+
+/// King safety of white at index 0 and black at 1, each from its own point
+/// of view. king_attacks holds the attacks on the white king at index 0 and
+/// on the black king at 1.
+pub fn king_safety_scores(
+    board: &Board,
+    king_attacks: &[KingAttacks; 2],
+    params: &EvalParams,
+) -> [(i32, i32); 2] {
+    [
+        king_safety(board, Color::White, king_attacks[0], params),
+        king_safety(board, Color::Black, king_attacks[1], params),
+    ]
+}
+
+/// King safety of both sides from the point of view of white
+pub fn evaluate_king_safety(
+    board: &Board,
+    king_attacks: &[KingAttacks; 2],
+    params: &EvalParams,
+) -> (i32, i32) {
+    let [(w_mg, w_eg), (b_mg, b_eg)] = king_safety_scores(board, king_attacks, params);
+    (w_mg - b_mg, w_eg - b_eg)
+}
@@ -1,326 +1,257 @@
-use ferris_chess_board::{Board, Color, MoveData, MoveType, Piece};
-use regex::Regex;
+use ferris_chess_board::{Board, MoveData, MoveType, Piece};
 use std::{
+    cmp::Reverse,
     collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
     thread,
-    time::{Duration, Instant},
+    time::Duration,
 };
 
+pub mod bitbase;
+pub mod datagen;
+pub mod endgame;
+pub mod eval_params;
+pub mod eval_trace;
+pub mod evaluator;
+pub mod king_safety;
+pub mod mobility;
+pub mod nnue;
+pub mod pawn_structure;
+pub mod pesto;
+pub mod positional;
+pub mod search_params;
+pub mod syzygy;
+pub mod time_management;
 pub mod transposition_table;
-use transposition_table::{NodeType, TTableData, TranspositonTable};
+pub mod tuner;
+use eval_trace::EvalTrace;
+use evaluator::Evaluator;
+use pesto::PestoEvaluator;
+use search_params::SearchParams;
+use syzygy::{SyzygyTables, Wdl, TB_WIN};
+use time_management::{TimeManager, DEFAULT_MOVE_OVERHEAD};
+use transposition_table::{NodeType, TTableData, TranspositonTable, DEFAULT_HASH_MB};
 
 pub const MATED_VALUE: i32 = i32::MIN / 2;
 
+// Upper bound on search ply, used to tell mate scores from regular scores
+pub const MAX_PLY: usize = 128;
+
+// Scores at or beyond this magnitude are mate scores
+const MATE_BOUND: i32 = -MATED_VALUE - MAX_PLY as i32;
+
+/// Mate scores are relative to the root during search. Convert them to be
+/// relative to the current node before storing them in the transposition table.
+fn score_to_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply
+    } else if score <= -MATE_BOUND {
+        score - ply
+    } else {
+        score
+    }
+}
+
+/// Inverse of score_to_tt for a score probed at the given ply
+fn score_from_tt(score: i32, ply: i32) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply
+    } else if score <= -MATE_BOUND {
+        score + ply
+    } else {
+        score
+    }
+}
+
+// Deepest iteration searched when go has no depth limit
+const MAX_DEPTH: usize = 50;
+
+/// Search limits from a UCI go command. Times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct GoCommand {
-    wtime: usize,
-    btime: usize,
-    movestogo: usize,
-    max_depth: usize,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: u64,
+    pub binc: u64,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+    pub depth: Option<usize>,
+    pub nodes: Option<usize>,
+    pub mate: Option<usize>,
+    pub infinite: bool,
+    pub ponder: bool,
+    // Root moves in UCI notation the search is restricted to (all if empty)
+    pub searchmoves: Vec<String>,
 }
 
 impl GoCommand {
-    pub fn new(go_input: &String) -> Self {
-        // Set some default values in case go command doesn't include them
-        let mut wtime: usize = 6000;
-        let mut btime: usize = 6000;
-        let mut movestogo = 1;
-
-        let wtime_re = Regex::new(r"wtime \d*").unwrap();
-        let btime_re = Regex::new(r"btime \d*").unwrap();
-        let movestogo_re = Regex::new(r"movestogo \d*").unwrap();
-
-        if let Some(wtime_match) = wtime_re.find(&go_input) {
-            wtime = wtime_match
-                .as_str()
-                .split_ascii_whitespace()
-                .nth(1)
-                .unwrap()
-                .parse()
-                .unwrap();
-        }
-
-        if let Some(btime_match) = btime_re.find(&go_input) {
-            btime = btime_match
-                .as_str()
-                .split_ascii_whitespace()
-                .nth(1)
-                .unwrap()
-                .parse()
-                .unwrap();
-        }
-
-        if let Some(movestogo_match) = movestogo_re.find(&go_input) {
-            movestogo = movestogo_match
-                .as_str()
-                .split_ascii_whitespace()
-                .nth(1)
-                .unwrap()
-                .parse()
-                .unwrap();
-        }
-
-        GoCommand {
-            wtime,
-            btime,
-            movestogo,
-            max_depth: 50,
+    pub fn new(go_input: &str) -> Self {
+        let mut go_cmd = GoCommand::default();
+        let mut tokens = go_input.split_ascii_whitespace().skip(1).peekable();
+
+        while let Some(token) = tokens.next() {
+            match token {
+                "wtime" => go_cmd.wtime = tokens.next().and_then(|t| t.parse().ok()),
+                "btime" => go_cmd.btime = tokens.next().and_then(|t| t.parse().ok()),
+                "winc" => go_cmd.winc = tokens.next().and_then(|t| t.parse().ok()).unwrap_or(0),
+                "binc" => go_cmd.binc = tokens.next().and_then(|t| t.parse().ok()).unwrap_or(0),
+                "movestogo" => go_cmd.movestogo = tokens.next().and_then(|t| t.parse().ok()),
+                "movetime" => go_cmd.movetime = tokens.next().and_then(|t| t.parse().ok()),
+                "depth" => go_cmd.depth = tokens.next().and_then(|t| t.parse().ok()),
+                "nodes" => go_cmd.nodes = tokens.next().and_then(|t| t.parse().ok()),
+                "mate" => go_cmd.mate = tokens.next().and_then(|t| t.parse().ok()),
+                "infinite" => go_cmd.infinite = true,
+                "ponder" => go_cmd.ponder = true,
+                "searchmoves" => {
+                    // Moves are listed until the next keyword
+                    while let Some(m) = tokens.next_if(|t| Self::is_uci_move(t)) {
+                        go_cmd.searchmoves.push(m.to_string());
+                    }
+                }
+                _ => (), // Ignore unknown tokens (UCI)
+            }
+        }
+
+        // Plain go without any limit searches until stopped
+        if go_cmd.wtime.is_none()
+            && go_cmd.btime.is_none()
+            && go_cmd.movetime.is_none()
+            && go_cmd.depth.is_none()
+            && go_cmd.nodes.is_none()
+            && go_cmd.mate.is_none()
+        {
+            go_cmd.infinite = true;
         }
+
+        go_cmd
+    }
+
+    fn is_uci_move(token: &str) -> bool {
+        let b = token.as_bytes();
+        (b.len() == 4 || b.len() == 5)
+            && (b'a'..=b'h').contains(&b[0])
+            && (b'1'..=b'8').contains(&b[1])
+            && (b'a'..=b'h').contains(&b[2])
+            && (b'1'..=b'8').contains(&b[3])
+    }
+
+    fn max_depth(&self) -> usize {
+        self.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH)
     }
 }
 
+#[derive(Clone)]
 pub struct Engine {
-    mg_pawn_table_b: [i32; 64],
-    eg_pawn_table_b: [i32; 64],
-    mg_knight_table_b: [i32; 64],
-    eg_knight_table_b: [i32; 64],
-    mg_bishop_table_b: [i32; 64],
-    eg_bishop_table_b: [i32; 64],
-    mg_rook_table_b: [i32; 64],
-    eg_rook_table_b: [i32; 64],
-    mg_queen_table_b: [i32; 64],
-    eg_queen_table_b: [i32; 64],
-    mg_king_table_b: [i32; 64],
-    eg_king_table_b: [i32; 64],
-
-    mg_pawn_table_w: [i32; 64],
-    eg_pawn_table_w: [i32; 64],
-    mg_knight_table_w: [i32; 64],
-    eg_knight_table_w: [i32; 64],
-    mg_bishop_table_w: [i32; 64],
-    eg_bishop_table_w: [i32; 64],
-    mg_rook_table_w: [i32; 64],
-    eg_rook_table_w: [i32; 64],
-    mg_queen_table_w: [i32; 64],
-    eg_queen_table_w: [i32; 64],
-    mg_king_table_w: [i32; 64],
-    eg_king_table_w: [i32; 64],
-
+    // Static evaluation of the positions reached in search
+    evaluator: Box<dyn Evaluator>,
     mvv_lva_table: HashMap<(Piece, Piece), i32>,
-    is_stopped: bool,
-    stop_time: Instant,
+    // Shared with the UCI thread so a running search can be stopped
+    stop: Arc<AtomicBool>,
+    // Set by the UCI thread for go ponder and cleared on ponderhit
+    pondering: Arc<AtomicBool>,
+    time_manager: TimeManager,
+    move_overhead: u64,
+    node_limit: Option<usize>,
+    // Root moves in UCI notation to search, all moves when empty
+    searchmoves: Vec<String>,
+    // Counters for the current go command
+    nodes: usize,
+
+    // Zobrist keys of the positions before the current one, from the game
+    // moves followed by the moves on the current search path
+    history: Vec<u64>,
+    // Moves from the root to the current node, used to recognize recaptures
+    search_path: Vec<MoveData>,
+    // Draw score in centipawns from the point of view of the side to move at the root
+    contempt: i32,
+    root_black_to_move: bool,
+    // Number of best root moves searched with full windows (UCI MultiPV option)
+    multi_pv: usize,
+    // Search threads including the main thread (UCI Threads option)
+    threads: usize,
+    // 0 for the main thread, helper threads are numbered from 1
+    thread_id: usize,
+    // Nodes searched by the helper threads of the current search
+    helper_nodes: Arc<AtomicUsize>,
+    search_params: SearchParams,
+    // UCI info lines are printed during search unless switched off
+    print_info: bool,
+    // Endgame tables probed during search (UCI SyzygyPath option)
+    syzygy: Option<Arc<SyzygyTables>>,
 
     pub board: Board,
-    pub t_table: TranspositonTable,
+    // Shared by all search threads
+    pub t_table: Arc<TranspositonTable>,
+    // Triangular PV table: row ply holds the principal variation found
+    // from the node at that ply, built from the row below it
+    pv_table: Vec<Vec<MoveData>>,
     pv: Vec<MoveData>,
-
+    search_info: Option<SearchInfo>,
 }
 
-#[derive(PartialEq, Copy, Clone)]
-enum Score {
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Score {
     CentiPawns(i32),
     Mate(i32),
 }
 
-#[derive(PartialEq, Copy, Clone)]
-pub struct SearchInfo {
-    depth: usize,
-    nodes: usize,
-    time: usize,
-    score: Score,
-    move_data: MoveData,
-}
+impl Score {
+    /// Converts a root search score to centipawns or mate in full moves.
+    /// A negative mate value means the side to move is getting mated.
+    pub fn from_value(value: i32) -> Self {
+        if value >= MATE_BOUND {
+            Score::Mate((-MATED_VALUE - value + 1) / 2)
+        } else if value <= -MATE_BOUND {
+            Score::Mate((MATED_VALUE - value) / 2)
+        } else {
+            Score::CentiPawns(value)
+        }
+    }
 
-fn mg_piece_weight(piece: Piece) -> i32 {
-    match piece {
-        Piece::Pawn => 82,
-        Piece::Knight => 337,
-        Piece::Bishop => 365,
-        Piece::Rook => 477,
-        Piece::Queen => 1025,
-        Piece::King => 0,
+    /// Inverse of from_value, up to the ply of mates
+    fn value(&self) -> i32 {
+        match *self {
+            Score::CentiPawns(cp) => cp,
+            Score::Mate(m) if m > 0 => -MATED_VALUE - (2 * m - 1),
+            Score::Mate(m) => MATED_VALUE - 2 * m,
+        }
     }
 }
 
-fn eg_piece_weight(piece: Piece) -> i32 {
-    match piece {
-        Piece::Pawn => 94,
-        Piece::Knight => 281,
-        Piece::Bishop => 297,
-        Piece::Rook => 512,
-        Piece::Queen => 936,
-        Piece::King => 0,
+impl fmt::Display for Score {
+    /// Score in UCI info format
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::CentiPawns(cp) => write!(f, "cp {}", cp),
+            Score::Mate(m) => write!(f, "mate {}", m),
+        }
     }
 }
 
-fn mirror_table(t: &[i32; 64]) -> [i32; 64] {
-    let mut mirrored: [i32; 64] = [0; 64];
-    for i in 0..64 {
-        mirrored[i] = t[i ^ 56];
-    }
-    mirrored
+/// A root move with its score and principal variation
+#[derive(Debug, PartialEq, Clone)]
+pub struct PvLine {
+    pub score: Score,
+    pub moves: Vec<MoveData>,
 }
 
-fn get_game_phase_table(t: &[i32; 64], corr: i32) -> [i32; 64] {
-    let mut corrected: [i32; 64] = [0; 64];
-    for i in 0..64 {
-        corrected[i] = t[i] + corr;
-    }
-    corrected
+#[derive(Debug, PartialEq, Clone)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub nodes: usize,
+    pub time: usize,
+    pub score: Score,
+    pub move_data: MoveData,
+    // The MultiPV best root moves sorted by score, starting with the best line
+    pub lines: Vec<PvLine>,
 }
 
 impl Engine {
     pub fn new(start_fen: &str) -> Engine {
-        #[rustfmt::skip]
-        let mg_pawn_table: [i32; 64] = [
-            0,   0,   0,   0,   0,   0,  0,   0,
-           98, 134,  61,  95,  68, 126, 34, -11,
-           -6,   7,  26,  31,  65,  56, 25, -20,
-          -14,  13,   6,  21,  23,  12, 17, -23,
-          -27,  -2,  -5,  12,  17,   6, 10, -25,
-          -26,  -4,  -4, -10,   3,   3, 33, -12,
-          -35,  -1, -20, -23, -15,  24, 38, -22,
-            0,   0,   0,   0,   0,   0,  0,   0,
-        ];
-
-        #[rustfmt::skip]
-        let eg_pawn_table: [i32; 64] = [
-            0,   0,   0,   0,   0,   0,   0,   0,
-          178, 173, 158, 134, 147, 132, 165, 187,
-           94, 100,  85,  67,  56,  53,  82,  84,
-           32,  24,  13,   5,  -2,   4,  17,  17,
-           13,   9,  -3,  -7,  -7,  -8,   3,  -1,
-            4,   7,  -6,   1,   0,  -5,  -1,  -8,
-           13,   8,   8,  10,  13,   0,   2,  -7,
-            0,   0,   0,   0,   0,   0,   0,   0,
-        ];
-
-        #[rustfmt::skip]
-        let mg_knight_table: [i32; 64] = [
-            -167, -89, -34, -49,  61, -97, -15, -107,
-             -73, -41,  72,  36,  23,  62,   7,  -17,
-             -47,  60,  37,  65,  84, 129,  73,   44,
-              -9,  17,  19,  53,  37,  69,  18,   22,
-             -13,   4,  16,  13,  28,  19,  21,   -8,
-             -23,  -9,  12,  10,  19,  17,  25,  -16,
-             -29, -53, -12,  -3,  -1,  18, -14,  -19,
-            -105, -21, -58, -33, -17, -28, -19,  -23,
-        ];
-
-        #[rustfmt::skip]
-        let eg_knight_table: [i32; 64] = [
-            -58, -38, -13, -28, -31, -27, -63, -99,
-            -25,  -8, -25,  -2,  -9, -25, -24, -52,
-            -24, -20,  10,   9,  -1,  -9, -19, -41,
-            -17,   3,  22,  22,  22,  11,   8, -18,
-            -18,  -6,  16,  25,  16,  17,   4, -18,
-            -23,  -3,  -1,  15,  10,  -3, -20, -22,
-            -42, -20, -10,  -5,  -2, -20, -23, -44,
-            -29, -51, -23, -15, -22, -18, -50, -64,
-        ];
-
-        #[rustfmt::skip]
-        let mg_bishop_table: [i32; 64] = [
-            -29,   4, -82, -37, -25, -42,   7,  -8,
-            -26,  16, -18, -13,  30,  59,  18, -47,
-            -16,  37,  43,  40,  35,  50,  37,  -2,
-             -4,   5,  19,  50,  37,  37,   7,  -2,
-             -6,  13,  13,  26,  34,  12,  10,   4,
-              0,  15,  15,  15,  14,  27,  18,  10,
-              4,  15,  16,   0,   7,  21,  33,   1,
-            -33,  -3, -14, -21, -13, -12, -39, -21,
-        ];
-
-        #[rustfmt::skip]
-        let eg_bishop_table: [i32; 64] = [
-            -14, -21, -11,  -8, -7,  -9, -17, -24,
-             -8,  -4,   7, -12, -3, -13,  -4, -14,
-              2,  -8,   0,  -1, -2,   6,   0,   4,
-             -3,   9,  12,   9, 14,  10,   3,   2,
-             -6,   3,  13,  19,  7,  10,  -3,  -9,
-            -12,  -3,   8,  10, 13,   3,  -7, -15,
-            -14, -18,  -7,  -1,  4,  -9, -15, -27,
-            -23,  -9, -23,  -5, -9, -16,  -5, -17,
-        ];
-
-        #[rustfmt::skip]
-        let mg_rook_table: [i32; 64] = [
-            32,  42,  32,  51, 63,  9,  31,  43,
-            27,  32,  58,  62, 80, 67,  26,  44,
-            -5,  19,  26,  36, 17, 45,  61,  16,
-           -24, -11,   7,  26, 24, 35,  -8, -20,
-           -36, -26, -12,  -1,  9, -7,   6, -23,
-           -45, -25, -16, -17,  3,  0,  -5, -33,
-           -44, -16, -20,  -9, -1, 11,  -6, -71,
-           -19, -13,   1,  17, 16,  7, -37, -26,
-        ];
-
-        #[rustfmt::skip]
-        let eg_rook_table: [i32; 64] = [
-            13, 10, 18, 15, 12,  12,   8,   5,
-            11, 13, 13, 11, -3,   3,   8,   3,
-             7,  7,  7,  5,  4,  -3,  -5,  -3,
-             4,  3, 13,  1,  2,   1,  -1,   2,
-             3,  5,  8,  4, -5,  -6,  -8, -11,
-            -4,  0, -5, -1, -7, -12,  -8, -16,
-            -6, -6,  0,  2, -9,  -9, -11,  -3,
-            -9,  2,  3, -1, -5, -13,   4, -20,
-        ];
-
-        #[rustfmt::skip]
-        let mg_queen_table: [i32; 64] = [
-            -28,   0,  29,  12,  59,  44,  43,  45,
-            -24, -39,  -5,   1, -16,  57,  28,  54,
-            -13, -17,   7,   8,  29,  56,  47,  57,
-            -27, -27, -16, -16,  -1,  17,  -2,   1,
-             -9, -26,  -9, -10,  -2,  -4,   3,  -3,
-            -14,   2, -11,  -2,  -5,   2,  14,   5,
-            -35,  -8,  11,   2,   8,  15,  -3,   1,
-             -1, -18,  -9,  10, -15, -25, -31, -50,
-        ];
-
-        #[rustfmt::skip]
-        let eg_queen_table: [i32; 64] = [
-            -9,  22,  22,  27,  27,  19,  10,  20,
-           -17,  20,  32,  41,  58,  25,  30,   0,
-           -20,   6,   9,  49,  47,  35,  19,   9,
-             3,  22,  24,  45,  57,  40,  57,  36,
-           -18,  28,  19,  47,  31,  34,  39,  23,
-           -16, -27,  15,   6,   9,  17,  10,   5,
-           -22, -23, -30, -16, -16, -23, -36, -32,
-           -33, -28, -22, -43,  -5, -32, -20, -41,
-        ];
-
-        #[rustfmt::skip]
-        let mg_king_table: [i32; 64] = [
-            -65,  23,  16, -15, -56, -34,   2,  13,
-             29,  -1, -20,  -7,  -8,  -4, -38, -29,
-             -9,  24,   2, -16, -20,   6,  22, -22,
-            -17, -20, -12, -27, -30, -25, -14, -36,
-            -49,  -1, -27, -39, -46, -44, -33, -51,
-            -14, -14, -22, -46, -44, -30, -15, -27,
-              1,   7,  -8, -64, -43, -16,   9,   8,
-            -15,  36,  12, -54,   8, -28,  24,  14,
-        ];
-
-        #[rustfmt::skip]
-        let eg_king_table: [i32; 64] = [
-            -74, -35, -18, -18, -11,  15,   4, -17,
-            -12,  17,  14,  17,  17,  38,  23,  11,
-             10,  17,  23,  15,  20,  45,  44,  13,
-             -8,  22,  24,  27,  26,  33,  26,   3,
-            -18,  -4,  21,  24,  27,  23,   9, -11,
-            -19,  -3,  11,  21,  23,  16,   7,  -9,
-            -27, -11,   4,  13,  14,   4,  -5, -17,
-            -53, -34, -21, -11, -28, -14, -24, -43
-        ];
-
-        let mg_pawn_table_b = get_game_phase_table(&mg_pawn_table, mg_piece_weight(Piece::Pawn));
-        let eg_pawn_table_b = get_game_phase_table(&eg_pawn_table, eg_piece_weight(Piece::Pawn));
-        let mg_knight_table_b =
-            get_game_phase_table(&mg_knight_table, mg_piece_weight(Piece::Knight));
-        let eg_knight_table_b =
-            get_game_phase_table(&eg_knight_table, eg_piece_weight(Piece::Knight));
-        let mg_bishop_table_b =
-            get_game_phase_table(&mg_bishop_table, mg_piece_weight(Piece::Bishop));
-        let eg_bishop_table_b =
-            get_game_phase_table(&eg_bishop_table, eg_piece_weight(Piece::Bishop));
-        let mg_rook_table_b = get_game_phase_table(&mg_rook_table, mg_piece_weight(Piece::Rook));
-        let eg_rook_table_b = get_game_phase_table(&eg_rook_table, eg_piece_weight(Piece::Rook));
-        let mg_queen_table_b = get_game_phase_table(&mg_queen_table, mg_piece_weight(Piece::Queen));
-        let eg_queen_table_b = get_game_phase_table(&eg_queen_table, eg_piece_weight(Piece::Queen));
-        let mg_king_table_b = get_game_phase_table(&mg_king_table, mg_piece_weight(Piece::King));
-        let eg_king_table_b = get_game_phase_table(&eg_king_table, eg_piece_weight(Piece::King));
-
         let board = ferris_chess_board::Board::from_fen(start_fen);
 
         let mvv_lva_table = HashMap::from([
@@ -362,71 +293,73 @@ impl Engine {
             ((Piece::Pawn, Piece::King), 29),
         ]);
 
-        let t_table = TranspositonTable::new();
+        let t_table = Arc::new(TranspositonTable::new(DEFAULT_HASH_MB));
 
         Engine {
-            mg_pawn_table_w: mirror_table(&mg_pawn_table_b),
-            eg_pawn_table_w: mirror_table(&eg_pawn_table_b),
-            mg_knight_table_w: mirror_table(&mg_knight_table_b),
-            eg_knight_table_w: mirror_table(&eg_knight_table_b),
-            mg_bishop_table_w: mirror_table(&mg_bishop_table_b),
-            eg_bishop_table_w: mirror_table(&eg_bishop_table_b),
-            mg_rook_table_w: mirror_table(&mg_rook_table_b),
-            eg_rook_table_w: mirror_table(&eg_rook_table_b),
-            mg_queen_table_w: mirror_table(&mg_queen_table_b),
-            eg_queen_table_w: mirror_table(&eg_queen_table_b),
-            mg_king_table_w: mirror_table(&mg_king_table_b),
-            eg_king_table_w: mirror_table(&eg_king_table_b),
-            mg_pawn_table_b,
-            eg_pawn_table_b,
-            mg_knight_table_b,
-            eg_knight_table_b,
-            mg_bishop_table_b,
-            eg_bishop_table_b,
-            mg_rook_table_b,
-            eg_rook_table_b,
-            mg_queen_table_b,
-            eg_queen_table_b,
-            mg_king_table_b,
-            eg_king_table_b,
+            evaluator: Box::new(PestoEvaluator::new()),
             mvv_lva_table,
-            is_stopped: false,
-            stop_time: Instant::now(),
+            stop: Arc::new(AtomicBool::new(false)),
+            pondering: Arc::new(AtomicBool::new(false)),
+            time_manager: TimeManager::new(&GoCommand::default(), false, 0),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            node_limit: None,
+            searchmoves: vec![],
+            nodes: 0,
+            history: vec![],
+            search_path: vec![],
+            contempt: 0,
+            root_black_to_move: false,
+            multi_pv: 1,
+            threads: 1,
+            thread_id: 0,
+            helper_nodes: Arc::new(AtomicUsize::new(0)),
+            search_params: SearchParams::default(),
+            print_info: true,
+            syzygy: None,
             board,
             t_table,
+            pv_table: vec![vec![]; MAX_PLY + 1],
             pv: vec![],
+            search_info: None,
         }
     }
 
-    fn update_pv(&mut self, depth: usize) {
-        self.pv = vec![];
+    /// The PV at ply becomes m followed by the PV of the child node
+    fn update_pv(&mut self, ply: usize, m: MoveData) {
+        let (parents, children) = self.pv_table.split_at_mut(ply + 1);
+        let pv = &mut parents[ply];
+        pv.clear();
+        pv.push(m);
+        pv.extend_from_slice(&children[0]);
+    }
 
-        // Use depth limit to ensure not getting stuck in an infinite loop of PV nodes
-        for _ in 1..=depth {
-            if let Some(pos) = self.t_table.get_pv_move_data(self.board.zobrist.hash) {
-                if pos.node_type == NodeType::Exact {
-                    if let Some(best_move) = pos.best_move {
-                        self.pv.push(best_move);
-                        self.board.make_move(&best_move);
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
+    fn print_search_info(&self, info: &SearchInfo) {
+        if !self.print_info {
+            return;
         }
-
-        for rev_mv in self.pv.iter().rev() {
-            self.board.unmake_move(rev_mv);
+        let hash_permill = self.t_table.hashfull();
+
+        for (idx, line) in info.lines.iter().enumerate() {
+            let uci_moves = line
+                .moves
+                .iter()
+                .map(|m| m.to_uci_move(&self.board))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            println!(
+                "info depth {} multipv {} score {} nodes {} time {} hashfull {} pv {}",
+                info.depth,
+                idx + 1,
+                line.score,
+                info.nodes,
+                info.time,
+                hash_permill,
+                uci_moves
+            );
         }
     }
 
-    fn print_pv(&self) {
-        let uci_moves = self.pv.iter().map(|m|m.to_uci_move(&self.board)).collect::<Vec<String>>().join(" ");
-        println!("info multipv 1 pv {}", uci_moves);
-    }
-
     pub fn tt_perft(&mut self, depth: u8) -> usize {
         let moves = self.board.get_pseudo_legal_moves();
         let mut nodes = 0;
@@ -465,225 +398,639 @@ impl Engine {
         nodes
     }
 
-    pub fn stop(&mut self) {
-        self.is_stopped = true;
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Flag that stops the search when set, for use from another thread
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Flag that is set while a go ponder search runs on the opponent's time.
+    /// Clearing it (ponderhit) switches the running search to normal time control.
+    pub fn ponder_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pondering)
+    }
+
+    fn is_pondering(&mut self) -> bool {
+        if self.time_manager.is_pondering() && !self.pondering.load(Ordering::Relaxed) {
+            self.time_manager.ponderhit();
+        }
+        self.time_manager.is_pondering()
+    }
+
+    fn should_stop(&mut self) -> bool {
+        self.is_pondering();
+        self.is_stopped()
+            || self.time_manager.hard_limit_reached()
+            || self.node_limit.is_some_and(|limit| self.nodes >= limit)
     }
 
     pub fn new_game(&mut self) {
-        self.is_stopped = false;
+        self.stop.store(false, Ordering::Relaxed);
+        self.t_table.clear();
+    }
+
+    /// Sets the position to search from and clears the game history
+    pub fn set_position(&mut self, board: Board) {
+        self.board = board;
+        self.history.clear();
+    }
+
+    /// Plays a game move on the board, remembering the position for repetition detection
+    pub fn make_game_move(&mut self, m: &MoveData) {
+        self.history.push(self.board.zobrist.hash);
+        self.board.make_move(m);
+    }
+
+    /// Sets how much (in centipawns) the engine prefers avoiding a draw (UCI Contempt option)
+    pub fn set_contempt(&mut self, contempt: i32) {
+        self.contempt = contempt;
+    }
+
+    /// Draw by the fifty-move rule or by repeating any earlier position since
+    /// the last capture or pawn move (twofold repetition)
+    fn is_draw(&mut self) -> bool {
+        // A mate on the move completing the 50 moves still counts
+        if self.board.half_moves >= 100 {
+            return !self.board.is_player_mated() || !self.board.get_legal_moves().is_empty();
+        }
+
+        let hash = self.board.zobrist.hash;
+        self.history
+            .iter()
+            .rev()
+            .take(self.board.half_moves)
+            .skip(1)
+            .step_by(2)
+            .any(|h| *h == hash)
     }
 
-    fn init_time(&mut self, go_cmd: &GoCommand) {
-        self.stop_time = Instant::now()
-            + Duration::from_millis(
-                match self.board.black_to_move {
-                    false => go_cmd.wtime - 1000,
-                    true => go_cmd.btime - 1000,
-                } as u64
-                    / go_cmd.movestogo as u64,
-            )
+    fn draw_score(&self) -> i32 {
+        if self.board.black_to_move == self.root_black_to_move {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// Resizes the transposition table to size_mb (UCI Hash option). Clears all entries.
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.t_table = Arc::new(TranspositonTable::new(size_mb));
+    }
+
+    /// Number of best root moves to report (UCI MultiPV option)
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = multi_pv.max(1);
+    }
+
+    /// Number of threads searching in parallel (UCI Threads option)
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Margins of the static eval based pruning
+    pub fn set_search_params(&mut self, search_params: SearchParams) {
+        self.search_params = search_params;
+    }
+
+    /// Switches the info lines printed during search on or off
+    pub fn set_print_info(&mut self, print_info: bool) {
+        self.print_info = print_info;
+    }
+
+    /// Endgame tables probed during search (UCI SyzygyPath option), None to
+    /// stop probing
+    pub fn set_syzygy_tables(&mut self, tables: Option<SyzygyTables>) {
+        self.syzygy = tables.map(Arc::new);
+    }
+
+    /// Replaces the static evaluation, PeSTO by default
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.evaluator = evaluator;
+    }
+
+    /// Result of the last completed iteration of the previous search
+    pub fn search_info(&self) -> Option<&SearchInfo> {
+        self.search_info.as_ref()
+    }
+
+    /// Time kept in reserve for communication delays with the GUI (UCI Move Overhead option)
+    pub fn set_move_overhead(&mut self, move_overhead: u64) {
+        self.move_overhead = move_overhead;
+    }
+
+    fn init_search(&mut self, go_cmd: &GoCommand) {
+        self.time_manager =
+            TimeManager::new(go_cmd, self.board.black_to_move, self.move_overhead);
+        self.node_limit = go_cmd.nodes;
+        self.searchmoves = go_cmd.searchmoves.clone();
+        self.nodes = 0;
+        self.helper_nodes.store(0, Ordering::Relaxed);
+        self.t_table.new_search();
+    }
+
+    /// True when a mate score satisfies go mate (mate in at most n moves for the side to move)
+    fn is_requested_mate(go_cmd: &GoCommand, score: Score) -> bool {
+        match (go_cmd.mate, score) {
+            (Some(n), Score::Mate(m)) => m > 0 && m as usize <= n,
+            _ => false,
+        }
+    }
+
+    /// Copy of the engine searching alongside the main thread (Lazy SMP).
+    /// It has no limits of its own and runs until stop is set.
+    fn helper(&self, thread_id: usize, stop: &Arc<AtomicBool>) -> Engine {
+        let mut helper = self.clone();
+        helper.thread_id = thread_id;
+        helper.stop = Arc::clone(stop);
+        helper.time_manager = TimeManager::new(
+            &GoCommand {
+                infinite: true,
+                ..GoCommand::default()
+            },
+            false,
+            0,
+        );
+        helper.node_limit = None;
+        helper
+    }
+
+    /// Searches with helper threads sharing the transposition table when
+    /// Threads is above 1. The main thread decides when to stop and reports
+    /// the deepest result of all threads. None when the side to move is
+    /// checkmated or stalemated.
+    pub fn iter_deepening(&mut self, go_cmd: &GoCommand) -> Option<MoveData> {
+        self.init_search(go_cmd);
+
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        let mut helpers: Vec<Engine> = (1..self.threads)
+            .map(|thread_id| self.helper(thread_id, &helpers_stop))
+            .collect();
+
+        let (info, helper_infos) = thread::scope(|s| {
+            let handles: Vec<_> = helpers
+                .iter_mut()
+                .map(|helper| s.spawn(move || helper.search_iterations(go_cmd)))
+                .collect();
+
+            let info = self.search_iterations(go_cmd);
+
+            // Under go infinite the best move may only be reported after stop,
+            // while pondering only after stop or ponderhit
+            while (go_cmd.infinite || self.is_pondering()) && !self.is_stopped() {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            helpers_stop.store(true, Ordering::Relaxed);
+            let helper_infos: Vec<Option<SearchInfo>> = handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Helper thread panicked"))
+                .collect();
+
+            (info, helper_infos)
+        });
+
+        let Some(mut best) = info else {
+            self.pv.clear();
+            self.search_info = None;
+            return None;
+        };
+        for helper_info in helper_infos.into_iter().flatten() {
+            if helper_info.depth > best.depth
+                || (helper_info.depth == best.depth
+                    && helper_info.score.value() > best.score.value())
+            {
+                best = SearchInfo {
+                    nodes: self.total_nodes(),
+                    time: self.time_manager.elapsed_ms(),
+                    ..helper_info
+                };
+                self.print_search_info(&best);
+            }
+        }
+
+        // Including the nodes of the helpers' last iterations
+        best.nodes = self.total_nodes();
+        self.pv = best.lines[0].moves.clone();
+        self.search_info = Some(best);
+        self.search_info.as_ref().map(|info| info.move_data)
     }
 
-    pub fn iter_deepening(&mut self, go_cmd: &GoCommand) -> MoveData {
-        self.init_time(go_cmd);
+    /// The endgame tables when they have the pieces of the position. The
+    /// tables don't cover positions with castling rights.
+    fn covering_tables(&self) -> Option<Arc<SyzygyTables>> {
+        let tables = self.syzygy.as_ref()?;
+        let pieces = self.board.pieces_w.len() + self.board.pieces_b.len();
+        if pieces > tables.max_pieces() || self.board.has_castling_rights() {
+            return None;
+        }
+        Some(tables.clone())
+    }
+
+    fn total_nodes(&self) -> usize {
+        self.nodes + self.helper_nodes()
+    }
 
+    /// Nodes searched by the helper threads during the last search
+    pub fn helper_nodes(&self) -> usize {
+        self.helper_nodes.load(Ordering::Relaxed)
+    }
+
+    /// Iterative deepening until a limit is reached. Returns the result of
+    /// the last completed iteration.
+    fn search_iterations(&mut self, go_cmd: &GoCommand) -> Option<SearchInfo> {
         let mut info: Option<SearchInfo> = None;
+        let mut reported_nodes = 0;
 
-        for depth in 1..=go_cmd.max_depth {
-            if let Some(search_info) = self.root_alpha_beta(depth) {
-                // Add small delay for now to allow multiple infos to be processed by GUI in
-                // first couple of plys. TODO: Add concurrency or asynchronous handling
-                thread::sleep(Duration::from_millis(10));
+        for depth in 1..=go_cmd.max_depth() {
+            // Helpers skip every other depth, half of them the odd and half
+            // the even ones, so threads spread over different depths
+            if self.thread_id > 0 && depth > 1 && (depth + self.thread_id) % 2 == 1 {
+                continue;
+            }
 
-                self.update_pv(depth);
-                info = Some(search_info);
+            let result = self.root_alpha_beta(depth);
 
-                match search_info.score {
-                    Score::CentiPawns(cp) => {
-                        let hash_permill = ((self.t_table.entries as f64
-                            / self.t_table.data.len() as f64)
-                            * 1000.0) as u64;
-
-                        println!(
-                            "info depth {} nodes {} time {} score cp {} hashfull {}",
-                            search_info.depth,
-                            search_info.nodes,
-                            search_info.time,
-                            cp,
-                            hash_permill
-                        );
-                        self.print_pv();
+            if self.thread_id > 0 {
+                self.helper_nodes
+                    .fetch_add(self.nodes - reported_nodes, Ordering::Relaxed);
+                reported_nodes = self.nodes;
+            }
 
-                    }
-                    Score::Mate(m) => {
-                        println!(
-                            "info depth {} nodes {} time {} score mate {}",
-                            search_info.depth, search_info.nodes, search_info.time, m
-                        );
-                        self.print_pv();
-                        break;
-                    }
+            if let Some(search_info) = result {
+                if self.thread_id == 0 {
+                    self.pv = search_info.lines[0].moves.clone();
+                    self.print_search_info(&search_info);
                 }
 
+                let score = search_info.score;
+                info = Some(search_info);
 
+                if let Score::Mate(_) = score {
+                    if go_cmd.mate.is_none() || Self::is_requested_mate(go_cmd, score) {
+                        break;
+                    }
+                }
             }
 
-            if self.is_stopped && info != None {
+            // Don't start another iteration that is unlikely to finish in time
+            if info.is_some() && (self.should_stop() || self.time_manager.soft_limit_reached()) {
                 break;
             }
         }
 
-        info.unwrap().move_data
+        info
     }
 
+    /// Expected reply to best_move from the principal variation, if known
+    pub fn ponder_move(&self, best_move: &MoveData) -> Option<MoveData> {
+        match self.pv.as_slice() {
+            [first, reply, ..] if first == best_move => Some(*reply),
+            _ => None,
+        }
+    }
+
+    /// Ties are broken by the squares of the moves, so the order doesn't
+    /// depend on the order the board generates them in
     fn mvv_lva(&self, moves: &mut Vec<MoveData>) {
         moves.sort_unstable_by_key(|x| {
-            if let Some(cap) = x.capture {
-                return *self.mvv_lva_table.get(&(x.piece, cap)).unwrap();
-            }
-            10000
+            let score = match x.capture {
+                Some(cap) => *self.mvv_lva_table.get(&(x.piece, cap)).unwrap(),
+                None => 10000,
+            };
+            (score, x.start_pos, x.end_pos, x.move_type as u8)
         })
     }
 
-    pub fn root_alpha_beta(&mut self, depth: usize) -> Option<SearchInfo> {
-        let start: Instant = Instant::now();
-        let mut nodes = 0;
-        let mut t_table_hits = 0;
+    /// Sorts captures by MVV-LVA and moves the transposition table move to the front
+    fn order_moves(&self, moves: &mut Vec<MoveData>, tt_move: Option<MoveData>) {
+        self.mvv_lva(moves);
+        if let Some(tt_move) = tt_move {
+            if let Some(idx) = moves.iter().position(|m| *m == tt_move) {
+                moves[..=idx].rotate_right(1);
+            }
+        }
+    }
 
-        let mut alpha = i32::MIN + 1;
+    /// Searches the root position to the given depth. With MultiPV set to n,
+    /// the n best moves are found one after another, each with a full window
+    /// over the moves not picked yet.
+    pub fn root_alpha_beta(&mut self, depth: usize) -> Option<SearchInfo> {
         let beta = i32::MAX - 1;
-        let mut search_info: Option<SearchInfo> = None;
-        let mut legal_moves = 0;
+        self.root_black_to_move = self.board.black_to_move;
+
+        let tt_move = self
+            .t_table
+            .get_pv_move_data(self.board.zobrist.hash)
+            .and_then(|tt_data| tt_data.best_move);
 
         let mut moves = self.board.get_pseudo_legal_moves();
-        self.mvv_lva(&mut moves);
+        if !self.searchmoves.is_empty() {
+            moves.retain(|m| self.searchmoves.contains(&m.to_uci_move(&self.board)));
+        }
+        self.order_moves(&mut moves, tt_move);
+
+        let board = &mut self.board;
+        moves.retain(|m| {
+            board.make_move(m);
+            let legal = !board.is_king_left_in_check();
+            board.unmake_move(m);
+            legal
+        });
 
-        for m in &moves {
-            self.board.make_move(&m);
-            if !self.board.is_king_left_in_check() {
-                //let ab_score = -self.alpha_beta(depth, 1, -beta, -alpha, &mut nodes, &mut t_table_hits);
-
-                let ab_score: i32 = match self.t_table.get(self.board.zobrist.hash, depth) {
-                    Some(tt_data) => {
-                        if tt_data.node_type == NodeType::Exact
-                            || tt_data.node_type == NodeType::LowerBound
-                        {
-                            t_table_hits += 1;
-                            tt_data.score
-                        } else {
-                            -self.alpha_beta(depth, 1, -beta, -alpha, &mut nodes, &mut t_table_hits)
-                        }
-                    }
-                    None => {
-                        -self.alpha_beta(depth, 1, -beta, -alpha, &mut nodes, &mut t_table_hits)
-                    }
-                };
+        // With the position in the endgame tables only the moves keeping
+        // the best result are searched, the quickest wins when winning
+        if let Some(tables) = self.covering_tables() {
+            if let Some(best) = tables.best_root_moves(&mut self.board, &moves) {
+                moves = best;
+            }
+        }
 
-                legal_moves += 1;
+        // Helpers try the moves after the best one in a different order
+        if self.thread_id > 0 && moves.len() > 2 {
+            let rotation = self.thread_id % (moves.len() - 1);
+            moves[1..].rotate_left(rotation);
+        }
 
-                if ab_score > alpha {
-                    let score = match (
-                        ab_score.abs_diff(MATED_VALUE) < 50,
-                        ab_score.abs_diff(-MATED_VALUE) < 50,
-                    ) {
-                        (true, false) => Score::Mate((MATED_VALUE - ab_score - 1) / 2),
-                        (false, true) => Score::Mate(((-MATED_VALUE) - ab_score + 1) / 2),
-                        _ => Score::CentiPawns(ab_score),
-                    };
+        // Checkmate or stalemate
+        if moves.is_empty() {
+            return None;
+        }
 
-                    alpha = ab_score;
+        // Best root moves found so far with their scores and PVs
+        let mut lines: Vec<(i32, Vec<MoveData>)> = vec![];
 
-                    search_info = Some(SearchInfo {
-                        depth,
-                        nodes,
-                        time: start.elapsed().as_millis() as usize,
-                        score,
-                        move_data: m.clone(),
-                    });
+        for _ in 0..self.multi_pv.min(moves.len()) {
+            let mut alpha = i32::MIN + 1;
+            let mut best_pv: Option<Vec<MoveData>> = None;
 
+            for m in &moves {
+                if lines.iter().any(|(_, pv)| pv[0] == *m) {
+                    continue;
                 }
-            }
-            self.board.unmake_move(&m);
 
-            // Always complete a search of depth 1
-            if depth > 1 && (self.is_stopped || Instant::now() > self.stop_time) {
-                return None;
-            }
-        }
+                self.history.push(self.board.zobrist.hash);
+                self.search_path.push(*m);
+                self.make_search_move(m);
+                let score = -self.alpha_beta(depth, 1, -beta, -alpha, 0);
+                self.unmake_search_move(m);
+                self.search_path.pop();
+                self.history.pop();
+
+                // Always complete a search of depth 1
+                if depth > 1 && self.should_stop() {
+                    return None;
+                }
 
-        if legal_moves == 0 {
-            if self.board.is_player_mated() {
-                return None;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(0, *m);
+                    best_pv = Some(self.pv_table[0].clone());
+                }
             }
+
+            lines.push((alpha, best_pv.unwrap()));
         }
 
-        println!(
-            "depth: {}, nodes: {} t_table entries: {} t_table hits: {}",
-            depth, nodes, self.t_table.entries, t_table_hits
-        );
+        lines.sort_by_key(|(score, _)| Reverse(*score));
+        let best_score = lines[0].0;
+        let best_move = lines[0].1[0];
 
         self.t_table.insert(TTableData {
             zobrist: self.board.zobrist.hash,
-            best_move: Some(search_info.unwrap().move_data),
+            best_move: Some(best_move),
             depth,
-            score: alpha,
-            node_type: NodeType::Exact
+            score: best_score,
+            node_type: NodeType::Exact,
         });
 
-        search_info
+        let lines = lines
+            .into_iter()
+            .map(|(score, moves)| PvLine {
+                score: Score::from_value(score),
+                moves,
+            })
+            .collect();
+
+        Some(SearchInfo {
+            depth,
+            nodes: self.total_nodes(),
+            time: self.time_manager.elapsed_ms(),
+            score: Score::from_value(best_score),
+            move_data: best_move,
+            lines,
+        })
     }
 
+    /// Extensions is the number of plies the line to this node was extended by
     pub fn alpha_beta(
         &mut self,
         depth: usize,
         ply: i32,
         mut alpha: i32,
         mut beta: i32,
-        nodes: &mut usize,
-        t_table_hits: &mut usize,
+        extensions: usize,
     ) -> i32 {
         let alpha_orig = alpha;
         let mut best_move: Option<MoveData> = None;
 
-        if let Some(node) = self.t_table.get(self.board.zobrist.hash, depth) {
-            *t_table_hits += 1;
-            if node.node_type == NodeType::Exact {
-                return node.score;
-            } else if node.node_type == NodeType::LowerBound {
-                alpha = alpha.max(node.score);
-            } else if node.node_type == NodeType::UpperBound {
-                beta = beta.min(node.score);
-            }
-            if alpha >= beta {
-                return node.score;
+        // Nodes returning before a move raises alpha end the PV
+        self.pv_table[ply as usize].clear();
+
+        if self.is_draw() {
+            return self.draw_score();
+        }
+
+        // Mate distance pruning: no line from here can beat a mate found closer to the root
+        alpha = alpha.max(MATED_VALUE + ply);
+        beta = beta.min(-MATED_VALUE - ply - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+
+        let tt_entry = self.t_table.get_pv_move_data(self.board.zobrist.hash);
+
+        if let Some(node) = tt_entry.filter(|node| depth <= node.depth) {
+            let tt_score = score_from_tt(node.score, ply);
+            // Only scores outside the window are returned. An exact score inside
+            // it would cut the PV short, and narrowing the window with a bound
+            // would let a cutoff inside the parent's window end the PV too.
+            let cutoff = match node.node_type {
+                NodeType::Exact => tt_score <= alpha || tt_score >= beta,
+                NodeType::LowerBound => tt_score >= beta,
+                NodeType::UpperBound => tt_score <= alpha,
+            };
+            if cutoff {
+                return tt_score;
             }
         }
 
-        *nodes += 1;
+        self.nodes += 1;
+
+        // Positions in the endgame tables are scored by their result, wins
+        // closer to the root first and below the mate scores
+        if let Some(tables) = self.covering_tables() {
+            if let Some(wdl) = tables.probe_wdl(&mut self.board) {
+                return match wdl {
+                    Wdl::Win => TB_WIN - ply,
+                    Wdl::Loss => -TB_WIN + ply,
+                    _ => self.draw_score(),
+                };
+            }
+        }
 
         if depth == 1 {
-            return self.quiesce(alpha, beta, nodes);
+            return self.quiesce(alpha, beta, ply, self.search_params.qsearch_checks);
         }
 
+        let params = self.search_params;
+        // Plies left before the quiescence search
+        let plies = depth - 1;
+        let static_eval = if plies <= params.rfp_max_plies.max(params.razor_margins.len())
+            && !self.board.is_player_mated()
+        {
+            Some(self.static_eval())
+        } else {
+            None
+        };
+
+        if let Some(eval) = static_eval {
+            // Reverse futility pruning: far enough above beta that any move is assumed to hold it
+            if plies <= params.rfp_max_plies
+                && beta < MATE_BOUND
+                && eval - params.rfp_margin * plies as i32 >= beta
+            {
+                return beta;
+            }
+
+            // Razoring: far enough below alpha that only captures are looked at
+            if plies <= params.razor_margins.len() && alpha > -MATE_BOUND {
+                let margin = params.razor_margins[plies - 1];
+                if eval + margin <= alpha {
+                    let razor_alpha = if plies == 1 { alpha } else { alpha - margin };
+                    let score =
+                        self.quiesce(razor_alpha, razor_alpha + 1, ply, params.qsearch_checks);
+                    if score <= razor_alpha {
+                        return score;
+                    }
+                }
+            }
+        }
+
+        // Futility pruning: quiet moves that can't raise alpha at the last plies
+        let futility_value = static_eval
+            .filter(|_| plies <= params.futility_margins.len() && alpha > -MATE_BOUND)
+            .map(|eval| eval + params.futility_margins[plies - 1]);
+
+        // Internal iterative reduction: without a hash move the ordering is
+        // poor, so the node is searched a ply shallower. That is cheaper than
+        // internal iterative deepening and the next iteration finds the move
+        // this search stores in the transposition table. At least one ply
+        // is kept before the quiescence search.
+        let tt_move = tt_entry.and_then(|node| node.best_move);
+        let depth = if tt_move.is_none() && plies >= params.iir_min_plies.max(2) {
+            depth - 1
+        } else {
+            depth
+        };
+        let plies = depth - 1;
+
         let mut moves = self.board.get_pseudo_legal_moves();
-        self.mvv_lva(&mut moves);
+        self.order_moves(&mut moves, tt_move);
+
+        let singular_move = match tt_entry {
+            Some(node)
+                if plies >= params.singular_min_plies
+                    && extensions < params.max_extensions
+                    && node.node_type != NodeType::UpperBound
+                    && node.depth + 3 >= depth
+                    && score_from_tt(node.score, ply).abs() < MATE_BOUND =>
+            {
+                let singular_beta =
+                    score_from_tt(node.score, ply) - params.singular_margin * plies as i32;
+                node.best_move.filter(|tt_move| {
+                    self.is_singular(&moves, tt_move, singular_beta, depth, ply, extensions)
+                })
+            }
+            _ => None,
+        };
+        let last_move = self.search_path.last().copied();
 
         let mut legal_moves = 0;
-        let mut max = i32::MIN+1;
+        let mut max = i32::MIN + 1;
 
         for m in moves.drain(..) {
-            self.board.make_move(&m);
+            self.history.push(self.board.zobrist.hash);
+            self.search_path.push(m);
+            self.make_search_move(&m);
             if self.board.is_king_left_in_check() {
-                self.board.unmake_move(&m)
+                self.unmake_search_move(&m);
+                self.search_path.pop();
+                self.history.pop();
             } else {
                 legal_moves += 1;
 
-                let score =
-                    -self.alpha_beta(depth - 1, ply + 1, -beta, -alpha, nodes, t_table_hits);
+                if let Some(value) = futility_value.filter(|value| *value <= alpha) {
+                    if m.capture.is_none()
+                        && !self.is_prom_move(&m)
+                        && !self.board.is_player_mated()
+                    {
+                        self.unmake_search_move(&m);
+                        self.search_path.pop();
+                        self.history.pop();
+                        max = max.max(value);
+                        continue;
+                    }
+                }
 
-                self.board.unmake_move(&m);
+                // Checks, recaptures that complete an even trade and a singular
+                // move are searched one ply deeper
+                let is_recapture = m.capture.is_some()
+                    && last_move.is_some_and(|last| {
+                        last.end_pos == m.end_pos
+                            && last.capture.is_some_and(|cap| {
+                                (cap as i32 - last.piece as i32).abs() <= 10
+                            })
+                    });
+                let extension = usize::from(
+                    extensions < params.max_extensions
+                        && (self.board.is_player_mated()
+                            || is_recapture
+                            || singular_move == Some(m)),
+                );
+
+                let score = -self.alpha_beta(
+                    depth - 1 + extension,
+                    ply + 1,
+                    -beta,
+                    -alpha,
+                    extensions + extension,
+                );
+
+                self.unmake_search_move(&m);
+                self.search_path.pop();
+                self.history.pop();
+
+                // Scores from an interrupted search must not reach the transposition table
+                if self.should_stop() {
+                    return max;
+                }
 
                 if score >= beta {
+                    // Keep the refutation so it is searched first next time
+                    self.t_table.insert(TTableData {
+                        zobrist: self.board.zobrist.hash,
+                        best_move: Some(m),
+                        depth,
+                        score: score_to_tt(beta, ply),
+                        node_type: NodeType::LowerBound,
+                    });
                     return beta;
                 }
 
@@ -692,10 +1039,10 @@ impl Engine {
                     best_move = Some(m);
                 }
 
-                alpha = alpha.max(score);
-            }
-            if self.is_stopped || Instant::now() > self.stop_time {
-                return max;
+                if score > alpha {
+                    alpha = score;
+                    self.update_pv(ply as usize, m);
+                }
             }
         }
 
@@ -703,7 +1050,7 @@ impl Engine {
             if self.board.is_player_mated() {
                 return MATED_VALUE + ply;
             }
-            return 0;
+            return self.draw_score();
         }
 
         let node_type: NodeType = {
@@ -716,18 +1063,75 @@ impl Engine {
             }
         };
 
-        // Replacement strategy - Always replace (for now)
+        // Fail-low nodes have no reliable best move to suggest for ordering
         self.t_table.insert(TTableData {
             zobrist: self.board.zobrist.hash,
-            best_move,
+            best_move: best_move.filter(|_| node_type == NodeType::Exact),
             depth,
-            score: alpha,
+            score: score_to_tt(alpha, ply),
             node_type,
         });
 
         max
     }
 
+    /// True when every move other than tt_move fails low against singular_beta
+    /// in a reduced depth search
+    fn is_singular(
+        &mut self,
+        moves: &[MoveData],
+        tt_move: &MoveData,
+        singular_beta: i32,
+        depth: usize,
+        ply: i32,
+        extensions: usize,
+    ) -> bool {
+        let reduced_depth = (depth / 2).max(1);
+
+        for m in moves.iter().filter(|m| *m != tt_move) {
+            self.history.push(self.board.zobrist.hash);
+            self.search_path.push(*m);
+            self.make_search_move(m);
+            let score = match self.board.is_king_left_in_check() {
+                true => None,
+                false => Some(-self.alpha_beta(
+                    reduced_depth,
+                    ply + 1,
+                    -singular_beta,
+                    -singular_beta + 1,
+                    extensions,
+                )),
+            };
+            self.unmake_search_move(m);
+            self.search_path.pop();
+            self.history.pop();
+
+            if self.should_stop() || score.is_some_and(|score| score >= singular_beta) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Makes a move on the search path, letting the evaluator follow it
+    fn make_search_move(&mut self, m: &MoveData) {
+        self.evaluator.make_move(&self.board, m);
+        self.board.make_move(m);
+    }
+
+    fn unmake_search_move(&mut self, m: &MoveData) {
+        self.board.unmake_move(m);
+        self.evaluator.unmake_move(&self.board, m);
+    }
+
+    fn gives_check(&mut self, m: &MoveData) -> bool {
+        self.board.make_move(m);
+        let check = self.board.is_player_mated();
+        self.board.unmake_move(m);
+        check
+    }
+
     fn is_prom_move(&self, m: &MoveData) -> bool {
         m.move_type == MoveType::QueenPromotion
             || m.move_type == MoveType::RookPromotion
@@ -735,130 +1139,95 @@ impl Engine {
             || m.move_type == MoveType::KnightPromotion
     }
 
-    fn quiesce(&mut self, mut alpha: i32, beta: i32, nodes: &mut usize) -> i32 {
-        *nodes += 1;
-        let stand_pat = self.static_eval();
+    /// Searches captures and queen promotions until the position is quiet,
+    /// plus quiet checks when checks is set. In check all evasions are
+    /// searched instead of standing pat, so mates are found.
+    fn quiesce(&mut self, mut alpha: i32, beta: i32, ply: i32, checks: bool) -> i32 {
+        self.nodes += 1;
+        let in_check = self.board.is_player_mated();
 
-        if stand_pat >= beta {
-            return beta;
-        }
-        if alpha < stand_pat {
-            alpha = stand_pat;
+        let stand_pat = match in_check {
+            true => None,
+            false => Some(self.static_eval()),
+        };
+        if let Some(stand_pat) = stand_pat {
+            if stand_pat >= beta {
+                return beta;
+            }
+            if alpha < stand_pat {
+                alpha = stand_pat;
+            }
         }
 
         let mut moves = self.board.get_pseudo_legal_moves();
+        if !in_check {
+            moves.retain(|m| {
+                m.capture.is_some() || m.move_type == MoveType::QueenPromotion || checks
+            });
+        }
 
         // Add basic sorting of captures
         self.mvv_lva(&mut moves);
 
+        let mut legal_moves = 0;
+
         for m in moves {
-            if let Some(cap) = m.capture {
-                // Delta pruning
-                if stand_pat + cap as i32 + 200 < alpha && !self.is_prom_move(&m) {
+            let is_tactical = m.capture.is_some() || m.move_type == MoveType::QueenPromotion;
+
+            if let Some(stand_pat) = stand_pat {
+                // Skip moves losing material in the exchange on their target square,
+                // captures that can't bring the score close to alpha (delta pruning)
+                // and quiet moves that don't give check
+                let skip = match is_tactical {
+                    true => {
+                        let see = self.board.see(&m);
+                        see < 0 || stand_pat + see + self.search_params.delta_margin < alpha
+                    }
+                    false => !self.gives_check(&m) || self.board.see(&m) < 0,
+                };
+                if skip {
                     continue;
                 }
-
-                self.board.make_move(&m);
-                let score = -self.quiesce(-beta, -alpha, nodes);
-                self.board.unmake_move(&m);
-
-                if score >= beta {
-                    return beta;
-                }
-                if score > alpha {
-                    alpha = score;
-                }
             }
-        }
-        alpha
-    }
-
-    pub fn static_eval(&self) -> i32 {
-        let mut mg_score_w = 0;
-        let mut eg_score_w = 0;
-        let mut mg_score_b = 0;
-        let mut eg_score_b = 0;
-
-        let mut mg_phase = 0;
-
-        for i in &self.board.pieces_w {
-            let p = self.board.data[*i].unwrap();
 
-            mg_phase += self.score_game_phase_pieces(p.1);
-
-            mg_score_w += self.get_mg_score(p, *i);
-            eg_score_w += self.get_eg_score(p, *i);
-        }
-
-        for i in &self.board.pieces_b {
-            let p = self.board.data[*i].unwrap();
-
-            mg_phase += self.score_game_phase_pieces(p.1);
+            self.make_search_move(&m);
+            if self.board.is_king_left_in_check() {
+                self.unmake_search_move(&m);
+                continue;
+            }
+            legal_moves += 1;
 
-            mg_score_b += self.get_mg_score(p, *i);
-            eg_score_b += self.get_eg_score(p, *i);
-        }
+            let score = -self.quiesce(-beta, -alpha, ply + 1, false);
+            self.unmake_search_move(&m);
 
-        // In case of queen promotion, limit mg_score to 24
-        if mg_phase > 24 {
-            mg_phase = 24;
+            if score >= beta {
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+            }
         }
 
-        // Tapered eval: mg_phase + eg_phase = 24
-        // Considers number of remaining non-pawn/king pieces at values 1 (Knight, Bishop), 2 (Rook), 4 (Queen)
-        let eg_phase = 24 - mg_phase;
-
-        let mg_score = mg_score_w - mg_score_b;
-        let eg_score = eg_score_w - eg_score_b;
-
-        if self.board.black_to_move {
-            return -(mg_score * mg_phase + eg_score * eg_phase) / 24;
+        if in_check && legal_moves == 0 {
+            return MATED_VALUE + ply;
         }
-        (mg_score * mg_phase + eg_score * eg_phase) / 24
+        alpha
     }
 
-    fn score_game_phase_pieces(&self, piece: Piece) -> i32 {
-        match piece {
-            Piece::Pawn => 0,
-            Piece::Knight => 1,
-            Piece::Bishop => 1,
-            Piece::Rook => 2,
-            Piece::Queen => 4,
-            Piece::King => 0,
-        }
+    /// Evaluation of the current position from the point of view of the side to move
+    pub fn static_eval(&mut self) -> i32 {
+        self.evaluator.evaluate(&self.board)
     }
 
-    fn get_mg_score(&self, piece: (Color, Piece), square: usize) -> i32 {
-        match piece {
-            (Color::Black, Piece::Pawn) => self.mg_pawn_table_b[square],
-            (Color::Black, Piece::Knight) => self.mg_knight_table_b[square],
-            (Color::Black, Piece::Bishop) => self.mg_bishop_table_b[square],
-            (Color::Black, Piece::Rook) => self.mg_rook_table_b[square],
-            (Color::Black, Piece::Queen) => self.mg_queen_table_b[square],
-            (Color::Black, Piece::King) => self.mg_king_table_b[square],
-            (Color::White, Piece::Pawn) => self.mg_pawn_table_w[square],
-            (Color::White, Piece::Knight) => self.mg_knight_table_w[square],
-            (Color::White, Piece::Bishop) => self.mg_bishop_table_w[square],
-            (Color::White, Piece::Rook) => self.mg_rook_table_w[square],
-            (Color::White, Piece::Queen) => self.mg_queen_table_w[square],
-            (Color::White, Piece::King) => self.mg_king_table_w[square],
-        }
+    /// The static evaluation of board split into its terms
+    pub fn eval_trace(&mut self, board: &Board) -> EvalTrace {
+        self.evaluator.trace(board)
     }
 
-    fn get_eg_score(&self, piece: (Color, Piece), square: usize) -> i32 {
-        match piece {
-            (Color::Black, Piece::Pawn) => self.eg_pawn_table_b[square],
-            (Color::Black, Piece::Knight) => self.eg_knight_table_b[square],
-            (Color::Black, Piece::Bishop) => self.eg_bishop_table_b[square],
-            (Color::Black, Piece::Rook) => self.eg_rook_table_b[square],
-            (Color::Black, Piece::Queen) => self.eg_queen_table_b[square],
-            (Color::Black, Piece::King) => self.eg_king_table_b[square],
-            (Color::White, Piece::Pawn) => self.eg_pawn_table_w[square],
-            (Color::White, Piece::Knight) => self.eg_knight_table_w[square],
-            (Color::White, Piece::Bishop) => self.eg_bishop_table_w[square],
-            (Color::White, Piece::Rook) => self.eg_rook_table_w[square],
-            (Color::White, Piece::Queen) => self.eg_queen_table_w[square],
-            (Color::White, Piece::King) => self.eg_king_table_w[square],
-        }
+    /// Quiescence search score of the current position from the point of view
+    /// of the side to move, the static evaluation once captures are resolved
+    pub fn quiesce_eval(&mut self) -> i32 {
+        let checks = self.search_params.qsearch_checks;
+        self.quiesce(MATED_VALUE, -MATED_VALUE, 0, checks)
     }
 }
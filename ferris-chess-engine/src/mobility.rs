@@ -0,0 +1,96 @@
+use crate::eval_params::EvalParams;
+use crate::king_safety::KingAttacks;
+use ferris_chess_board::{Board, Color, Piece};
+
+// Number of safe squares a knight, bishop, rook and queen typically reach,
+// so an average piece scores about zero
+const TYPICAL_MOBILITY: [i32; 4] = [4, 6, 7, 13];
+
+/// Mobility from the point of view of white, with the attacks on both kings
+/// found along the way
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Mobility {
+    pub mg: i32,
+    pub eg: i32,
+    // Mobility of white at index 0 and black at 1, each from its own point of view
+    pub sides: [(i32, i32); 2],
+    // Attacks on the zone of the white king at index 0 and the black king at 1
+    pub king_attacks: [KingAttacks; 2],
+}
+
+fn color_idx(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+/// Squares next to the king of color, and the king square itself
+pub fn king_zone(board: &Board, color: Color) -> u64 {
+    let Some(king_pos) = board.get_king_pos(color) else {
+        return 0;
+    };
+    board
+        .get_attacks(king_pos)
+        .into_iter()
+        .fold(1 << king_pos, |zone, sq| zone | 1 << sq)
+}
+
+/// Counts the squares each knight, bishop, rook and queen attacks that hold
+/// no piece of its own color and are not attacked by an enemy pawn
+pub fn evaluate_mobility(board: &Board, params: &EvalParams) -> Mobility {
+    let mut pawn_attacks = [0u64; 2];
+    for (sq, p) in board.data.iter().enumerate() {
+        if let Some((color, Piece::Pawn)) = p {
+            for target in board.get_attacks(sq) {
+                pawn_attacks[color_idx(*color)] |= 1 << target;
+            }
+        }
+    }
+    let king_zones = [
+        king_zone(board, Color::White),
+        king_zone(board, Color::Black),
+    ];
+
+    let mut mobility = Mobility::default();
+    for (sq, p) in board.data.iter().enumerate() {
+        let Some((color, piece)) = *p else {
+            continue;
+        };
+        let (weights, piece_idx) = match piece {
+            Piece::Knight => (params.knight_mobility, 0),
+            Piece::Bishop => (params.bishop_mobility, 1),
+            Piece::Rook => (params.rook_mobility, 2),
+            Piece::Queen => (params.queen_mobility, 3),
+            Piece::Pawn | Piece::King => continue,
+        };
+        let typical = TYPICAL_MOBILITY[piece_idx];
+        let enemy = 1 - color_idx(color);
+
+        let mut safe_squares = 0;
+        let mut zone_squares = 0;
+        for target in board.get_attacks(sq) {
+            let own_piece = board.data[target].is_some_and(|(c, _)| c == color);
+            if !own_piece && pawn_attacks[enemy] & (1 << target) == 0 {
+                safe_squares += 1;
+            }
+            if king_zones[enemy] & (1 << target) != 0 {
+                zone_squares += 1;
+            }
+        }
+
+        let side = &mut mobility.sides[color_idx(color)];
+        side.0 += weights[0] * (safe_squares - typical);
+        side.1 += weights[1] * (safe_squares - typical);
+
+        if zone_squares > 0 {
+            let attacks = &mut mobility.king_attacks[enemy];
+            attacks.attackers += 1;
+            attacks.units += params.king_attack_units[piece_idx] * zone_squares;
+        }
+    }
+
+    mobility.mg = mobility.sides[0].0 - mobility.sides[1].0;
+    mobility.eg = mobility.sides[0].1 - mobility.sides[1].1;
+    mobility
+}
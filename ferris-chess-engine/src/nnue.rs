@@ -0,0 +1,495 @@
+//! Efficiently updatable neural network evaluation with a 768 -> N -> 1
+//! architecture. Each side has its own accumulator, the hidden layer seen
+//! from its perspective, updated as moves are made and unmade in search.
+//!
+//! Network files are little-endian:
+//!
+//! | Bytes    | Content                                                   |
+//! |----------|-----------------------------------------------------------|
+//! | 4        | Magic `FCNN`                                              |
+//! | 4        | Format version as u32, currently 1                        |
+//! | 4        | Hidden layer size N as u32                                |
+//! | 768 * 2N | Feature weights as i16, N for each feature in order       |
+//! | 2N       | Hidden layer biases as i16                                |
+//! | 4N       | Output weights as i16, side to move first, then the other |
+//! | 4        | Output bias as i32                                        |
+//!
+//! The feature of a piece seen from a perspective is
+//! `384 * side + 64 * piece + square`. side is 0 for pieces of the
+//! perspective and 1 for the enemy, piece goes from pawn (0) to king (5), and
+//! squares go from a1 (0) to h8 (63), flipped vertically for black.
+//! Hidden values are clipped to 0..=QA before the output layer, and the
+//! output is scaled by SCALE / (QA * QB) to centipawns.
+
+use crate::evaluator::Evaluator;
+use ferris_chess_board::{Board, Color, MoveData, MoveType, Piece, Square};
+use std::{fs, sync::Arc};
+
+pub const MAGIC: &[u8; 4] = b"FCNN";
+pub const VERSION: u32 = 1;
+pub const FEATURES: usize = 768;
+
+/// Quantization of the hidden layer
+pub const QA: i32 = 255;
+/// Quantization of the output weights
+pub const QB: i32 = 64;
+/// Centipawns for an output of 1.0
+pub const SCALE: i32 = 400;
+
+fn piece_idx(piece: Piece) -> usize {
+    match piece {
+        Piece::Pawn => 0,
+        Piece::Knight => 1,
+        Piece::Bishop => 2,
+        Piece::Rook => 3,
+        Piece::Queen => 4,
+        Piece::King => 5,
+    }
+}
+
+/// Feature of a piece on a square seen from perspective
+pub fn feature(perspective: Color, color: Color, piece: Piece, sq: usize) -> usize {
+    let side = usize::from(color != perspective);
+    let sq = match perspective {
+        Color::White => sq,
+        Color::Black => sq ^ 56,
+    };
+    384 * side + 64 * piece_idx(piece) + sq
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    hidden_size: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, String> {
+    let chunk = bytes
+        .get(*pos..*pos + 4)
+        .ok_or("Network file is truncated")?;
+    *pos += 4;
+    Ok(u32::from_le_bytes(chunk.try_into().unwrap()))
+}
+
+fn read_i16s(bytes: &[u8], pos: &mut usize, len: usize) -> Result<Vec<i16>, String> {
+    let chunk = bytes
+        .get(*pos..*pos + 2 * len)
+        .ok_or("Network file is truncated")?;
+    *pos += 2 * len;
+    Ok(chunk
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect())
+}
+
+impl Network {
+    pub fn new(
+        hidden_size: usize,
+        feature_weights: Vec<i16>,
+        feature_biases: Vec<i16>,
+        output_weights: Vec<i16>,
+        output_bias: i32,
+    ) -> Result<Self, String> {
+        if hidden_size == 0 {
+            return Err("Network hidden layer is empty".to_string());
+        }
+        if feature_weights.len() != FEATURES * hidden_size
+            || feature_biases.len() != hidden_size
+            || output_weights.len() != 2 * hidden_size
+        {
+            return Err(format!(
+                "Network layers don't match the hidden layer size {}",
+                hidden_size
+            ));
+        }
+        Ok(Network {
+            hidden_size,
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden_size
+    }
+
+    pub fn is_network(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !Network::is_network(bytes) {
+            return Err("Not a network file".to_string());
+        }
+        let mut pos = MAGIC.len();
+        let version = read_u32(bytes, &mut pos)?;
+        if version != VERSION {
+            return Err(format!("Unsupported network version {}", version));
+        }
+        let hidden_size = read_u32(bytes, &mut pos)? as usize;
+        if hidden_size == 0 || hidden_size > 1 << 16 {
+            return Err(format!("Invalid network hidden layer size {}", hidden_size));
+        }
+
+        let feature_weights = read_i16s(bytes, &mut pos, FEATURES * hidden_size)?;
+        let feature_biases = read_i16s(bytes, &mut pos, hidden_size)?;
+        let output_weights = read_i16s(bytes, &mut pos, 2 * hidden_size)?;
+        let output_bias = read_u32(bytes, &mut pos)? as i32;
+        if pos != bytes.len() {
+            return Err("Network file has trailing data".to_string());
+        }
+
+        Network::new(
+            hidden_size,
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias,
+        )
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend((self.hidden_size as u32).to_le_bytes());
+        for values in [
+            &self.feature_weights,
+            &self.feature_biases,
+            &self.output_weights,
+        ] {
+            bytes.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        }
+        bytes.extend(self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+        Network::from_bytes(&bytes)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|e| format!("Can't write {}: {}", path, e))
+    }
+
+    fn feature_row(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden_size..(feature + 1) * self.hidden_size]
+    }
+
+    /// Accumulators of both perspectives computed from scratch
+    fn refresh(&self, board: &Board, acc: &mut Accumulator) {
+        for (perspective, values) in [Color::White, Color::Black].iter().zip(&mut acc.values) {
+            values.copy_from_slice(&self.feature_biases);
+            for (sq, p) in board.data.iter().enumerate() {
+                if let Some((color, piece)) = *p {
+                    add_row(
+                        values,
+                        self.feature_row(feature(*perspective, color, piece, sq)),
+                    );
+                }
+            }
+        }
+        acc.hash = Some(board.zobrist.hash);
+    }
+
+    /// Score in centipawns from the accumulator of the side to move (us) and
+    /// of the other side (them)
+    pub fn output(&self, us: &[i16], them: &[i16]) -> i32 {
+        let (w_us, w_them) = self.output_weights.split_at(self.hidden_size);
+        let sum = self.output_bias + clipped_dot(us, w_us) + clipped_dot(them, w_them);
+        scale(sum)
+    }
+
+    /// Scalar reference for output, also used where SIMD is unavailable
+    pub fn output_scalar(&self, us: &[i16], them: &[i16]) -> i32 {
+        let (w_us, w_them) = self.output_weights.split_at(self.hidden_size);
+        let sum =
+            self.output_bias + clipped_dot_scalar(us, w_us) + clipped_dot_scalar(them, w_them);
+        scale(sum)
+    }
+}
+
+fn scale(sum: i32) -> i32 {
+    (sum as i64 * SCALE as i64 / (QA * QB) as i64) as i32
+}
+
+fn add_row_scalar(values: &mut [i16], row: &[i16]) {
+    for (v, w) in values.iter_mut().zip(row) {
+        *v = v.wrapping_add(*w);
+    }
+}
+
+fn sub_row_scalar(values: &mut [i16], row: &[i16]) {
+    for (v, w) in values.iter_mut().zip(row) {
+        *v = v.wrapping_sub(*w);
+    }
+}
+
+fn clipped_dot_scalar(values: &[i16], weights: &[i16]) -> i32 {
+    values
+        .iter()
+        .zip(weights)
+        .map(|(v, w)| (*v as i32).clamp(0, QA) * *w as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::QA;
+    use std::arch::x86_64::*;
+
+    // 16 i16 values per 256 bit register
+    const LANES: usize = 16;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_row(values: &mut [i16], row: &[i16]) {
+        let chunks = values.len() / LANES * LANES;
+        for i in (0..chunks).step_by(LANES) {
+            let v = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+            let w = _mm256_loadu_si256(row.as_ptr().add(i) as *const __m256i);
+            _mm256_storeu_si256(
+                values.as_mut_ptr().add(i) as *mut __m256i,
+                _mm256_add_epi16(v, w),
+            );
+        }
+        super::add_row_scalar(&mut values[chunks..], &row[chunks..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_row(values: &mut [i16], row: &[i16]) {
+        let chunks = values.len() / LANES * LANES;
+        for i in (0..chunks).step_by(LANES) {
+            let v = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+            let w = _mm256_loadu_si256(row.as_ptr().add(i) as *const __m256i);
+            _mm256_storeu_si256(
+                values.as_mut_ptr().add(i) as *mut __m256i,
+                _mm256_sub_epi16(v, w),
+            );
+        }
+        super::sub_row_scalar(&mut values[chunks..], &row[chunks..]);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn clipped_dot(values: &[i16], weights: &[i16]) -> i32 {
+        let chunks = values.len() / LANES * LANES;
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(QA as i16);
+        let mut sum = _mm256_setzero_si256();
+        for i in (0..chunks).step_by(LANES) {
+            let v = _mm256_loadu_si256(values.as_ptr().add(i) as *const __m256i);
+            let w = _mm256_loadu_si256(weights.as_ptr().add(i) as *const __m256i);
+            let clipped = _mm256_min_epi16(_mm256_max_epi16(v, zero), max);
+            sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, w));
+        }
+        let mut lanes = [0i32; 8];
+        _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+        lanes.iter().sum::<i32>() + super::clipped_dot_scalar(&values[chunks..], &weights[chunks..])
+    }
+}
+
+fn add_row(values: &mut [i16], row: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 is available and both slices have the same length
+        return unsafe { avx2::add_row(values, row) };
+    }
+    add_row_scalar(values, row)
+}
+
+fn sub_row(values: &mut [i16], row: &[i16]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 is available and both slices have the same length
+        return unsafe { avx2::sub_row(values, row) };
+    }
+    sub_row_scalar(values, row)
+}
+
+fn clipped_dot(values: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safety: AVX2 is available and both slices have the same length
+        return unsafe { avx2::clipped_dot(values, weights) };
+    }
+    clipped_dot_scalar(values, weights)
+}
+
+/// Hidden layer from the white perspective at index 0 and black at 1
+#[derive(Clone, Debug)]
+struct Accumulator {
+    values: [Vec<i16>; 2],
+    // Zobrist key of the position, None until the first use after a move
+    hash: Option<u64>,
+}
+
+// A piece of a color on a square
+type PlacedPiece = (Color, Piece, usize);
+
+/// Pieces a move takes off and puts on the board
+fn move_changes(board: &Board, m: &MoveData) -> (Vec<PlacedPiece>, Vec<PlacedPiece>) {
+    let (us, them) = match board.black_to_move {
+        false => (Color::White, Color::Black),
+        true => (Color::Black, Color::White),
+    };
+    let mut removed = vec![(us, m.piece, m.start_pos)];
+    let mut added = vec![];
+
+    let placed = match m.move_type {
+        MoveType::QueenPromotion => Piece::Queen,
+        MoveType::RookPromotion => Piece::Rook,
+        MoveType::BishopPromotion => Piece::Bishop,
+        MoveType::KnightPromotion => Piece::Knight,
+        _ => m.piece,
+    };
+    added.push((us, placed, m.end_pos));
+
+    match m.move_type {
+        MoveType::EnPassant => {
+            let captured = match us {
+                Color::White => m.end_pos - 8,
+                Color::Black => m.end_pos + 8,
+            };
+            removed.push((them, Piece::Pawn, captured));
+        }
+        MoveType::Castling => {
+            let (from, to) = match m.end_pos {
+                Square::C1 => (Square::A1, Square::D1),
+                Square::G1 => (Square::H1, Square::F1),
+                Square::C8 => (Square::A8, Square::D8),
+                _ => (Square::H8, Square::F8),
+            };
+            removed.push((us, Piece::Rook, from));
+            added.push((us, Piece::Rook, to));
+        }
+        _ => {
+            if let Some(captured) = m.capture {
+                removed.push((them, captured, m.end_pos));
+            }
+        }
+    }
+    (removed, added)
+}
+
+/// Evaluates with a network, keeping a stack of accumulators along the
+/// search path. Positions reached without the move hooks, such as a new
+/// root, are recognized by their zobrist key and computed from scratch.
+#[derive(Clone)]
+pub struct NnueEvaluator {
+    network: Arc<Network>,
+    stack: Vec<Accumulator>,
+    // Number of accumulators in use on the stack
+    len: usize,
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Network) -> Self {
+        NnueEvaluator {
+            network: Arc::new(network),
+            stack: vec![],
+            len: 0,
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        Ok(NnueEvaluator::new(Network::load(path)?))
+    }
+
+    /// Makes sure the top accumulator belongs to board
+    fn sync(&mut self, board: &Board) {
+        let hash = board.zobrist.hash;
+        if let Some(top) = self.len.checked_sub(1).map(|i| &mut self.stack[i]) {
+            match top.hash {
+                Some(h) if h == hash => return,
+                None => {
+                    top.hash = Some(hash);
+                    return;
+                }
+                _ => (),
+            }
+        }
+
+        if self.stack.is_empty() {
+            self.push_empty();
+        }
+        self.network.refresh(board, &mut self.stack[0]);
+        self.len = 1;
+    }
+
+    fn push_empty(&mut self) {
+        let values = vec![0; self.network.hidden_size];
+        self.stack.push(Accumulator {
+            values: [values.clone(), values],
+            hash: None,
+        });
+    }
+
+    /// Evaluation from scratch, without the accumulator stack
+    pub fn evaluate_from_scratch(&self, board: &Board) -> i32 {
+        let values = vec![0; self.network.hidden_size];
+        let mut acc = Accumulator {
+            values: [values.clone(), values],
+            hash: None,
+        };
+        self.network.refresh(board, &mut acc);
+        let [white, black] = &acc.values;
+        match board.black_to_move {
+            false => self.network.output(white, black),
+            true => self.network.output(black, white),
+        }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        self.sync(board);
+        let [white, black] = &self.stack[self.len - 1].values;
+        match board.black_to_move {
+            false => self.network.output(white, black),
+            true => self.network.output(black, white),
+        }
+    }
+
+    fn make_move(&mut self, board: &Board, m: &MoveData) {
+        self.sync(board);
+        if self.len == self.stack.len() {
+            self.push_empty();
+        }
+        let (done, next) = self.stack.split_at_mut(self.len);
+        let (parent, child) = (&done[self.len - 1], &mut next[0]);
+
+        let (removed, added) = move_changes(board, m);
+        for (perspective, (values, parent_values)) in [Color::White, Color::Black]
+            .iter()
+            .zip(child.values.iter_mut().zip(&parent.values))
+        {
+            values.copy_from_slice(parent_values);
+            for (color, piece, sq) in &removed {
+                let row = self
+                    .network
+                    .feature_row(feature(*perspective, *color, *piece, *sq));
+                sub_row(values, row);
+            }
+            for (color, piece, sq) in &added {
+                let row = self
+                    .network
+                    .feature_row(feature(*perspective, *color, *piece, *sq));
+                add_row(values, row);
+            }
+        }
+        child.hash = None;
+        self.len += 1;
+    }
+
+    fn unmake_move(&mut self, _board: &Board, _m: &MoveData) {
+        self.len = self.len.saturating_sub(1);
+    }
+
+    fn clone_box(&self) -> Box<dyn Evaluator> {
+        Box::new(self.clone())
+    }
+}
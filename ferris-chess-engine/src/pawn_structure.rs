@@ -0,0 +1,220 @@
+use crate::eval_params::EvalParams;
+use ferris_chess_board::{Board, Color, Piece};
+
+// Number of entries in the pawn hash table, a power of two
+const PAWN_TABLE_ENTRIES: usize = 1 << 14;
+
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+/// Pawn structure score of a position, from the point of view of white
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PawnEntry {
+    key: u64,
+    pub mg: i32,
+    pub eg: i32,
+    // Squares of the passed pawns of both colors as a bitboard
+    pub passed: u64,
+}
+
+/// Caches pawn structure scores by the pawn zobrist key of the board
+#[derive(Clone)]
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        // A zeroed entry is the correct score for the empty pawn key
+        PawnTable {
+            entries: vec![PawnEntry::default(); PAWN_TABLE_ENTRIES],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+
+    /// Entries are only valid for the parameters they were stored with
+    pub fn probe(&mut self, board: &Board, params: &EvalParams) -> PawnEntry {
+        let key = board.zobrist.pawn_hash;
+        let entry = &mut self.entries[key as usize & (PAWN_TABLE_ENTRIES - 1)];
+        if entry.key != key {
+            *entry = evaluate_pawns(board, params);
+        }
+        *entry
+    }
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn file_mask(file: usize) -> u64 {
+    FILE_A << file
+}
+
+pub(crate) fn adjacent_files(file: usize) -> u64 {
+    let left = if file > 0 { file_mask(file - 1) } else { 0 };
+    let right = if file < 7 { file_mask(file + 1) } else { 0 };
+    left | right
+}
+
+fn rank_mask(rank: usize) -> u64 {
+    0xff << (8 * rank)
+}
+
+/// Ranks in front of rank in the direction the pawns of color move
+pub(crate) fn ranks_ahead(color: Color, rank: usize) -> u64 {
+    match color {
+        Color::White if rank < 7 => !0 << (8 * (rank + 1)),
+        Color::Black => (1 << (8 * rank)) - 1,
+        _ => 0,
+    }
+}
+
+pub(crate) fn relative_rank(color: Color, sq: usize) -> usize {
+    match color {
+        Color::White => sq / 8,
+        Color::Black => 7 - sq / 8,
+    }
+}
+
+/// Squares of the white pawns at index 0 and of the black pawns at 1
+pub(crate) fn pawn_bitboards(board: &Board) -> [u64; 2] {
+    let mut pawns = [0u64; 2];
+    for (sq, p) in board.data.iter().enumerate() {
+        match p {
+            Some((Color::White, Piece::Pawn)) => pawns[0] |= 1 << sq,
+            Some((Color::Black, Piece::Pawn)) => pawns[1] |= 1 << sq,
+            _ => (),
+        }
+    }
+    pawns
+}
+
+/// Pawn terms of the pawns of color from its point of view, with the
+/// squares of its passed pawns
+fn pawns_of_color(color: Color, pawns: &[u64; 2], params: &EvalParams) -> (i32, i32, u64) {
+    let (own, enemy) = match color {
+        Color::White => (pawns[0], pawns[1]),
+        Color::Black => (pawns[1], pawns[0]),
+    };
+    let (mut mg, mut eg) = (0, 0);
+    let mut passed = 0;
+
+    let mut remaining = own;
+    while remaining != 0 {
+        let sq = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
+
+        let file = sq % 8;
+        let rank = sq / 8;
+        let rel_rank = relative_rank(color, sq);
+        let ahead = ranks_ahead(color, rank);
+        let neighbors = own & adjacent_files(file);
+
+        if own & file_mask(file) & ahead != 0 {
+            mg += params.doubled[0];
+            eg += params.doubled[1];
+        }
+
+        if neighbors == 0 {
+            mg += params.isolated[0];
+            eg += params.isolated[1];
+        } else if neighbors & !ahead == 0 {
+            // All neighbors are in front, so none can ever defend it.
+            // Backward when an enemy pawn controls the square in front of it.
+            let stop_rank = match color {
+                Color::White => rank + 2,
+                Color::Black => rank.wrapping_sub(2),
+            };
+            if stop_rank < 8 && enemy & adjacent_files(file) & rank_mask(stop_rank) != 0 {
+                mg += params.backward[0];
+                eg += params.backward[1];
+            }
+        }
+
+        // Side by side with, or defended by, a pawn on a neighboring file
+        let behind_rank = match color {
+            Color::White => rank.wrapping_sub(1),
+            Color::Black => rank + 1,
+        };
+        let supported = behind_rank < 8 && neighbors & rank_mask(behind_rank) != 0;
+        if supported || neighbors & rank_mask(rank) != 0 {
+            mg += params.connected_mg[rel_rank];
+            eg += params.connected_eg[rel_rank];
+        }
+
+        if enemy & (file_mask(file) | adjacent_files(file)) & ahead == 0 {
+            passed |= 1 << sq;
+            mg += params.passed_mg[rel_rank];
+            eg += params.passed_eg[rel_rank];
+        }
+    }
+
+    (mg, eg, passed)
+}
+
+/// Pawn structure of white at index 0 and black at 1, each from its own
+/// point of view, as in evaluate_pawns
+pub fn pawn_scores(board: &Board, params: &EvalParams) -> [(i32, i32); 2] {
+    let pawns = pawn_bitboards(board);
+    [Color::White, Color::Black].map(|color| {
+        let (mg, eg, _) = pawns_of_color(color, &pawns, params);
+        (mg, eg)
+    })
+}
+
+/// Whether a and b have the same terms of evaluate_pawns, so that pawn
+/// table entries stored with one are valid for the other
+pub(crate) fn same_pawn_terms(a: &EvalParams, b: &EvalParams) -> bool {
+    a.doubled == b.doubled
+        && a.isolated == b.isolated
+        && a.backward == b.backward
+        && a.connected_mg == b.connected_mg
+        && a.connected_eg == b.connected_eg
+        && a.passed_mg == b.passed_mg
+        && a.passed_eg == b.passed_eg
+}
+
+/// Doubled, isolated, backward, connected and passed pawn terms for both
+/// sides. The bonus for a free path of passed pawns depends on the other
+/// pieces, so it is left to passed_pawn_path.
+pub fn evaluate_pawns(board: &Board, params: &EvalParams) -> PawnEntry {
+    let pawns = pawn_bitboards(board);
+    let (w_mg, w_eg, w_passed) = pawns_of_color(Color::White, &pawns, params);
+    let (b_mg, b_eg, b_passed) = pawns_of_color(Color::Black, &pawns, params);
+
+    PawnEntry {
+        key: board.zobrist.pawn_hash,
+        mg: w_mg - b_mg,
+        eg: w_eg - b_eg,
+        passed: w_passed | b_passed,
+    }
+}
+
+/// Bonus from the point of view of white for passed pawns whose squares up
+/// to the promotion square are empty
+pub fn passed_pawn_path(board: &Board, passed: u64, params: &EvalParams) -> (i32, i32) {
+    let (mut mg, mut eg) = (0, 0);
+    let mut remaining = passed;
+    while remaining != 0 {
+        let sq = remaining.trailing_zeros() as usize;
+        remaining &= remaining - 1;
+
+        let Some((color, _)) = board.data[sq] else {
+            continue;
+        };
+        let path = file_mask(sq % 8) & ranks_ahead(color, sq / 8);
+        let is_free = (0..64).all(|i| path & (1 << i) == 0 || board.data[i].is_none());
+        if is_free {
+            let rel_rank = relative_rank(color, sq);
+            let sign = if color == Color::White { 1 } else { -1 };
+            mg += sign * params.free_path_mg[rel_rank];
+            eg += sign * params.free_path_eg[rel_rank];
+        }
+    }
+    (mg, eg)
+}
@@ -0,0 +1,445 @@
+use crate::endgame::Endgame;
+use crate::eval_params::EvalParams;
+use crate::eval_trace::{EvalTrace, TraceTerm};
+use crate::evaluator::Evaluator;
+use crate::pawn_structure::{self, pawn_bitboards, PawnTable};
+use crate::positional::{self, PositionalTerms};
+use crate::{king_safety, mobility};
+use ferris_chess_board::{Board, Color, Piece};
+
+fn mirror_table(t: &[i32; 64]) -> [i32; 64] {
+    let mut mirrored: [i32; 64] = [0; 64];
+    for i in 0..64 {
+        mirrored[i] = t[i ^ 56];
+    }
+    mirrored
+}
+
+fn get_game_phase_table(t: &[i32; 64], corr: i32) -> [i32; 64] {
+    let mut corrected: [i32; 64] = [0; 64];
+    for i in 0..64 {
+        corrected[i] = t[i] + corr;
+    }
+    corrected
+}
+
+/// Tapered evaluation with the PeSTO piece-square tables, plus pawn
+/// structure, mobility, king safety and positional terms. Endgames
+/// recognized by their material are evaluated with their own knowledge.
+#[derive(Clone)]
+pub struct PestoEvaluator {
+    mg_pawn_table_b: [i32; 64],
+    eg_pawn_table_b: [i32; 64],
+    mg_knight_table_b: [i32; 64],
+    eg_knight_table_b: [i32; 64],
+    mg_bishop_table_b: [i32; 64],
+    eg_bishop_table_b: [i32; 64],
+    mg_rook_table_b: [i32; 64],
+    eg_rook_table_b: [i32; 64],
+    mg_queen_table_b: [i32; 64],
+    eg_queen_table_b: [i32; 64],
+    mg_king_table_b: [i32; 64],
+    eg_king_table_b: [i32; 64],
+
+    mg_pawn_table_w: [i32; 64],
+    eg_pawn_table_w: [i32; 64],
+    mg_knight_table_w: [i32; 64],
+    eg_knight_table_w: [i32; 64],
+    mg_bishop_table_w: [i32; 64],
+    eg_bishop_table_w: [i32; 64],
+    mg_rook_table_w: [i32; 64],
+    eg_rook_table_w: [i32; 64],
+    mg_queen_table_w: [i32; 64],
+    eg_queen_table_w: [i32; 64],
+    mg_king_table_w: [i32; 64],
+    eg_king_table_w: [i32; 64],
+
+    // Pawn structure scores by pawn zobrist key, one table per search thread
+    pawn_table: PawnTable,
+    positional_terms: PositionalTerms,
+    // Specialized evaluations and scale factors of recognized endgames
+    endgames: bool,
+    params: EvalParams,
+}
+
+impl PestoEvaluator {
+    pub fn new() -> Self {
+        Self::with_params(EvalParams::default())
+    }
+
+    pub fn with_params(params: EvalParams) -> Self {
+        let mut evaluator = PestoEvaluator {
+            mg_pawn_table_b: [0; 64],
+            eg_pawn_table_b: [0; 64],
+            mg_knight_table_b: [0; 64],
+            eg_knight_table_b: [0; 64],
+            mg_bishop_table_b: [0; 64],
+            eg_bishop_table_b: [0; 64],
+            mg_rook_table_b: [0; 64],
+            eg_rook_table_b: [0; 64],
+            mg_queen_table_b: [0; 64],
+            eg_queen_table_b: [0; 64],
+            mg_king_table_b: [0; 64],
+            eg_king_table_b: [0; 64],
+            mg_pawn_table_w: [0; 64],
+            eg_pawn_table_w: [0; 64],
+            mg_knight_table_w: [0; 64],
+            eg_knight_table_w: [0; 64],
+            mg_bishop_table_w: [0; 64],
+            eg_bishop_table_w: [0; 64],
+            mg_rook_table_w: [0; 64],
+            eg_rook_table_w: [0; 64],
+            mg_queen_table_w: [0; 64],
+            eg_queen_table_w: [0; 64],
+            mg_king_table_w: [0; 64],
+            eg_king_table_w: [0; 64],
+            pawn_table: PawnTable::new(),
+            positional_terms: PositionalTerms::default(),
+            endgames: true,
+            params: EvalParams::default(),
+        };
+        evaluator.set_params(&params);
+        evaluator
+    }
+
+    pub fn params(&self) -> &EvalParams {
+        &self.params
+    }
+
+    /// Switches the positional evaluation terms on or off
+    pub fn set_positional_terms(&mut self, positional_terms: PositionalTerms) {
+        self.positional_terms = positional_terms;
+    }
+
+    /// Switches the evaluation of endgames recognized by their material on
+    /// or off
+    pub fn set_endgames(&mut self, endgames: bool) {
+        self.endgames = endgames;
+    }
+
+    fn score_game_phase_pieces(&self, piece: Piece) -> i32 {
+        match piece {
+            Piece::Pawn => 0,
+            Piece::Knight => 1,
+            Piece::Bishop => 1,
+            Piece::Rook => 2,
+            Piece::Queen => 4,
+            Piece::King => 0,
+        }
+    }
+
+    fn get_mg_score(&self, piece: (Color, Piece), square: usize) -> i32 {
+        match piece {
+            (Color::Black, Piece::Pawn) => self.mg_pawn_table_b[square],
+            (Color::Black, Piece::Knight) => self.mg_knight_table_b[square],
+            (Color::Black, Piece::Bishop) => self.mg_bishop_table_b[square],
+            (Color::Black, Piece::Rook) => self.mg_rook_table_b[square],
+            (Color::Black, Piece::Queen) => self.mg_queen_table_b[square],
+            (Color::Black, Piece::King) => self.mg_king_table_b[square],
+            (Color::White, Piece::Pawn) => self.mg_pawn_table_w[square],
+            (Color::White, Piece::Knight) => self.mg_knight_table_w[square],
+            (Color::White, Piece::Bishop) => self.mg_bishop_table_w[square],
+            (Color::White, Piece::Rook) => self.mg_rook_table_w[square],
+            (Color::White, Piece::Queen) => self.mg_queen_table_w[square],
+            (Color::White, Piece::King) => self.mg_king_table_w[square],
+        }
+    }
+
+    fn get_eg_score(&self, piece: (Color, Piece), square: usize) -> i32 {
+        match piece {
+            (Color::Black, Piece::Pawn) => self.eg_pawn_table_b[square],
+            (Color::Black, Piece::Knight) => self.eg_knight_table_b[square],
+            (Color::Black, Piece::Bishop) => self.eg_bishop_table_b[square],
+            (Color::Black, Piece::Rook) => self.eg_rook_table_b[square],
+            (Color::Black, Piece::Queen) => self.eg_queen_table_b[square],
+            (Color::Black, Piece::King) => self.eg_king_table_b[square],
+            (Color::White, Piece::Pawn) => self.eg_pawn_table_w[square],
+            (Color::White, Piece::Knight) => self.eg_knight_table_w[square],
+            (Color::White, Piece::Bishop) => self.eg_bishop_table_w[square],
+            (Color::White, Piece::Rook) => self.eg_rook_table_w[square],
+            (Color::White, Piece::Queen) => self.eg_queen_table_w[square],
+            (Color::White, Piece::King) => self.eg_king_table_w[square],
+        }
+    }
+
+    /// Endgame recognized by the material on board, unless switched off
+    fn endgame(&self, board: &Board) -> Option<Endgame> {
+        match self.endgames {
+            true => Endgame::probe(board),
+            false => None,
+        }
+    }
+
+    /// Tapered evaluation of all terms, before any endgame knowledge
+    fn tapered_eval(&mut self, board: &Board) -> i32 {
+        let mut mg_score_w = 0;
+        let mut eg_score_w = 0;
+        let mut mg_score_b = 0;
+        let mut eg_score_b = 0;
+
+        let mut mg_phase = 0;
+
+        for i in &board.pieces_w {
+            let p = board.data[*i].unwrap();
+
+            mg_phase += self.score_game_phase_pieces(p.1);
+
+            mg_score_w += self.get_mg_score(p, *i);
+            eg_score_w += self.get_eg_score(p, *i);
+        }
+
+        for i in &board.pieces_b {
+            let p = board.data[*i].unwrap();
+
+            mg_phase += self.score_game_phase_pieces(p.1);
+
+            mg_score_b += self.get_mg_score(p, *i);
+            eg_score_b += self.get_eg_score(p, *i);
+        }
+
+        // In case of queen promotion, limit mg_score to 24
+        if mg_phase > 24 {
+            mg_phase = 24;
+        }
+
+        // Tapered eval: mg_phase + eg_phase = 24
+        // Considers number of remaining non-pawn/king pieces at values 1 (Knight, Bishop), 2 (Rook), 4 (Queen)
+        let eg_phase = 24 - mg_phase;
+
+        let pawns = self.pawn_table.probe(board, &self.params);
+        let (path_mg, path_eg) =
+            pawn_structure::passed_pawn_path(board, pawns.passed, &self.params);
+
+        let mobility = mobility::evaluate_mobility(board, &self.params);
+        let (king_mg, king_eg) =
+            king_safety::evaluate_king_safety(board, &mobility.king_attacks, &self.params);
+
+        let (positional_mg, positional_eg) =
+            positional::evaluate_positional(board, &self.positional_terms, &self.params);
+
+        let mg_score =
+            mg_score_w - mg_score_b + pawns.mg + path_mg + mobility.mg + king_mg + positional_mg;
+        let eg_score =
+            eg_score_w - eg_score_b + pawns.eg + path_eg + mobility.eg + king_eg + positional_eg;
+
+        if board.black_to_move {
+            return -(mg_score * mg_phase + eg_score * eg_phase) / 24;
+        }
+        (mg_score * mg_phase + eg_score * eg_phase) / 24
+    }
+}
+
+impl Default for PestoEvaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evaluator for PestoEvaluator {
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        let eval = self.tapered_eval(board);
+        match self.endgame(board) {
+            Some(endgame) => endgame.evaluate(board, eval),
+            None => eval,
+        }
+    }
+
+    /// The pawn table is only cleared when the pawn structure terms it
+    /// caches change
+    fn set_params(&mut self, params: &EvalParams) {
+        if !pawn_structure::same_pawn_terms(&self.params, params) {
+            self.pawn_table.clear();
+        }
+
+        // Tables with the piece values added, indexed by square from black's view
+        let [mg_pawn, mg_knight, mg_bishop, mg_rook, mg_queen, mg_king] = params.mg_piece_values;
+        let [eg_pawn, eg_knight, eg_bishop, eg_rook, eg_queen, eg_king] = params.eg_piece_values;
+        self.mg_pawn_table_b = get_game_phase_table(&params.mg_pawn_table, mg_pawn);
+        self.eg_pawn_table_b = get_game_phase_table(&params.eg_pawn_table, eg_pawn);
+        self.mg_knight_table_b = get_game_phase_table(&params.mg_knight_table, mg_knight);
+        self.eg_knight_table_b = get_game_phase_table(&params.eg_knight_table, eg_knight);
+        self.mg_bishop_table_b = get_game_phase_table(&params.mg_bishop_table, mg_bishop);
+        self.eg_bishop_table_b = get_game_phase_table(&params.eg_bishop_table, eg_bishop);
+        self.mg_rook_table_b = get_game_phase_table(&params.mg_rook_table, mg_rook);
+        self.eg_rook_table_b = get_game_phase_table(&params.eg_rook_table, eg_rook);
+        self.mg_queen_table_b = get_game_phase_table(&params.mg_queen_table, mg_queen);
+        self.eg_queen_table_b = get_game_phase_table(&params.eg_queen_table, eg_queen);
+        self.mg_king_table_b = get_game_phase_table(&params.mg_king_table, mg_king);
+        self.eg_king_table_b = get_game_phase_table(&params.eg_king_table, eg_king);
+
+        self.mg_pawn_table_w = mirror_table(&self.mg_pawn_table_b);
+        self.eg_pawn_table_w = mirror_table(&self.eg_pawn_table_b);
+        self.mg_knight_table_w = mirror_table(&self.mg_knight_table_b);
+        self.eg_knight_table_w = mirror_table(&self.eg_knight_table_b);
+        self.mg_bishop_table_w = mirror_table(&self.mg_bishop_table_b);
+        self.eg_bishop_table_w = mirror_table(&self.eg_bishop_table_b);
+        self.mg_rook_table_w = mirror_table(&self.mg_rook_table_b);
+        self.eg_rook_table_w = mirror_table(&self.eg_rook_table_b);
+        self.mg_queen_table_w = mirror_table(&self.mg_queen_table_b);
+        self.eg_queen_table_w = mirror_table(&self.eg_queen_table_b);
+        self.mg_king_table_w = mirror_table(&self.mg_king_table_b);
+        self.eg_king_table_w = mirror_table(&self.eg_king_table_b);
+        self.params = params.clone();
+    }
+
+    fn trace(&mut self, board: &Board) -> EvalTrace {
+        let params = &self.params;
+        let mut material = [(0, 0); 2];
+        let mut squares = [(0, 0); 2];
+        let mut mg_phase = 0;
+
+        for (sq, p) in board.data.iter().enumerate() {
+            let Some((color, piece)) = *p else {
+                continue;
+            };
+            mg_phase += self.score_game_phase_pieces(piece);
+
+            let idx = match color {
+                Color::White => 0,
+                Color::Black => 1,
+            };
+            let piece_idx = match piece {
+                Piece::Pawn => 0,
+                Piece::Knight => 1,
+                Piece::Bishop => 2,
+                Piece::Rook => 3,
+                Piece::Queen => 4,
+                Piece::King => 5,
+            };
+            let mg_value = params.mg_piece_values[piece_idx];
+            let eg_value = params.eg_piece_values[piece_idx];
+            material[idx].0 += mg_value;
+            material[idx].1 += eg_value;
+            squares[idx].0 += self.get_mg_score((color, piece), sq) - mg_value;
+            squares[idx].1 += self.get_eg_score((color, piece), sq) - eg_value;
+        }
+        let mg_phase = mg_phase.min(24);
+        let eg_phase = 24 - mg_phase;
+
+        // Free paths of the passed pawns of each color, from its own point of view
+        let passed = pawn_structure::evaluate_pawns(board, params).passed;
+        let pawns = pawn_bitboards(board);
+        let (w_path_mg, w_path_eg) =
+            pawn_structure::passed_pawn_path(board, passed & pawns[0], params);
+        let (b_path_mg, b_path_eg) =
+            pawn_structure::passed_pawn_path(board, passed & pawns[1], params);
+
+        let mobility = mobility::evaluate_mobility(board, params);
+
+        let mut terms = vec![
+            TraceTerm {
+                name: "Material",
+                sides: material,
+            },
+            TraceTerm {
+                name: "PSQT",
+                sides: squares,
+            },
+            TraceTerm {
+                name: "Pawns",
+                sides: pawn_structure::pawn_scores(board, params),
+            },
+            TraceTerm {
+                name: "Passed paths",
+                sides: [(w_path_mg, w_path_eg), (-b_path_mg, -b_path_eg)],
+            },
+            TraceTerm {
+                name: "Mobility",
+                sides: mobility.sides,
+            },
+            TraceTerm {
+                name: "King safety",
+                sides: king_safety::king_safety_scores(board, &mobility.king_attacks, params),
+            },
+        ];
+
+        // Each positional term that is switched on, on its own
+        let none = PositionalTerms::none();
+        let enabled = self.positional_terms;
+        for (name, is_on, only) in [
+            (
+                "Bishop pair",
+                enabled.bishop_pair,
+                PositionalTerms {
+                    bishop_pair: true,
+                    ..none
+                },
+            ),
+            (
+                "Open files",
+                enabled.open_files,
+                PositionalTerms {
+                    open_files: true,
+                    ..none
+                },
+            ),
+            (
+                "Seventh rank",
+                enabled.rook_on_seventh,
+                PositionalTerms {
+                    rook_on_seventh: true,
+                    ..none
+                },
+            ),
+            (
+                "Outposts",
+                enabled.outposts,
+                PositionalTerms {
+                    outposts: true,
+                    ..none
+                },
+            ),
+            (
+                "Threats",
+                enabled.threats,
+                PositionalTerms {
+                    threats: true,
+                    ..none
+                },
+            ),
+            (
+                "Tempo",
+                enabled.tempo,
+                PositionalTerms {
+                    tempo: true,
+                    ..none
+                },
+            ),
+        ] {
+            if is_on {
+                terms.push(TraceTerm {
+                    name,
+                    sides: positional::positional_scores(board, &only, params),
+                });
+            }
+        }
+
+        let mut trace = EvalTrace {
+            terms,
+            phase: Some((mg_phase, eg_phase)),
+            endgame: None,
+            white_score: 0,
+            score: 0,
+        };
+        let (mg, eg) = trace.total();
+        trace.white_score = (mg * mg_phase + eg * eg_phase) / 24;
+        trace.score = match board.black_to_move {
+            true => -trace.white_score,
+            false => trace.white_score,
+        };
+
+        if let Some(endgame) = self.endgame(board) {
+            trace.endgame = Some(endgame.name());
+            trace.score = endgame.evaluate(board, trace.score);
+            trace.white_score = match board.black_to_move {
+                true => -trace.score,
+                false => trace.score,
+            };
+        }
+        trace
+    }
+
+    fn clone_box(&self) -> Box<dyn Evaluator> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,197 @@
+use crate::eval_params::EvalParams;
+use crate::pawn_structure::{
+    adjacent_files, file_mask, pawn_bitboards, ranks_ahead, relative_rank,
+};
+use ferris_chess_board::{Board, Color, Piece};
+
+/// Switches for the positional evaluation terms, all on by default
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionalTerms {
+    pub bishop_pair: bool,
+    // Rooks and queens on files without pawns of their own color
+    pub open_files: bool,
+    pub rook_on_seventh: bool,
+    // Knights and bishops defended by a pawn where no enemy pawn can attack them
+    pub outposts: bool,
+    pub threats: bool,
+    pub tempo: bool,
+}
+
+impl Default for PositionalTerms {
+    fn default() -> Self {
+        PositionalTerms {
+            bishop_pair: true,
+            open_files: true,
+            rook_on_seventh: true,
+            outposts: true,
+            threats: true,
+            tempo: true,
+        }
+    }
+}
+
+impl PositionalTerms {
+    /// All terms switched off
+    pub fn none() -> Self {
+        PositionalTerms {
+            bishop_pair: false,
+            open_files: false,
+            rook_on_seventh: false,
+            outposts: false,
+            threats: false,
+            tempo: false,
+        }
+    }
+}
+
+fn color_idx(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+// Knights and bishops count as equally valuable for threats
+fn threat_rank(piece: Piece) -> u8 {
+    match piece {
+        Piece::Pawn => 0,
+        Piece::Knight | Piece::Bishop => 1,
+        Piece::Rook => 2,
+        Piece::Queen => 3,
+        Piece::King => 4,
+    }
+}
+
+/// Positional terms of the pieces of color, from its point of view
+fn positional(
+    board: &Board,
+    color: Color,
+    terms: &PositionalTerms,
+    params: &EvalParams,
+    pawns: &[u64; 2],
+) -> (i32, i32) {
+    let own_pawns = pawns[color_idx(color)];
+    let enemy_pawns = pawns[color_idx(opponent(color))];
+    let enemy_king_rank = board
+        .get_king_pos(opponent(color))
+        .map(|sq| relative_rank(color, sq));
+    let (mut mg, mut eg) = (0, 0);
+    let mut add = |[term_mg, term_eg]: [i32; 2]| {
+        mg += term_mg;
+        eg += term_eg;
+    };
+
+    let mut bishops = 0;
+    for (sq, p) in board.data.iter().enumerate() {
+        let Some((piece_color, piece)) = *p else {
+            continue;
+        };
+        if piece_color != color {
+            continue;
+        }
+        let file = sq % 8;
+        let rank = sq / 8;
+        let rel_rank = relative_rank(color, sq);
+
+        if piece == Piece::Bishop {
+            bishops += 1;
+        }
+
+        if terms.open_files
+            && (piece == Piece::Rook || piece == Piece::Queen)
+            && own_pawns & file_mask(file) == 0
+        {
+            let is_open = enemy_pawns & file_mask(file) == 0;
+            add(match (piece, is_open) {
+                (Piece::Rook, true) => params.rook_open_file,
+                (Piece::Rook, false) => params.rook_semi_open_file,
+                (_, true) => params.queen_open_file,
+                (_, false) => params.queen_semi_open_file,
+            });
+        }
+
+        // The seventh rank matters when it traps the king or holds pawns
+        if terms.rook_on_seventh && piece == Piece::Rook && rel_rank == 6 {
+            let pawns_on_seventh =
+                (0..8).any(|f| board.data[rank * 8 + f] == Some((opponent(color), Piece::Pawn)));
+            if enemy_king_rank == Some(7) || pawns_on_seventh {
+                add(params.rook_on_seventh);
+            }
+        }
+
+        if terms.outposts
+            && (piece == Piece::Knight || piece == Piece::Bishop)
+            && (3..=5).contains(&rel_rank)
+        {
+            let behind_rank = match color {
+                Color::White => rank - 1,
+                Color::Black => rank + 1,
+            };
+            let defended = own_pawns & adjacent_files(file) & (0xff << (8 * behind_rank)) != 0;
+            let attackable = enemy_pawns & adjacent_files(file) & ranks_ahead(color, rank) != 0;
+            if defended && !attackable {
+                add(if piece == Piece::Knight {
+                    params.knight_outpost
+                } else {
+                    params.bishop_outpost
+                });
+            }
+        }
+
+        if terms.threats && piece != Piece::Pawn && piece != Piece::King {
+            let attackers = board.get_attackers(sq, opponent(color));
+            let by_lesser = attackers.iter().any(|attacker| {
+                board.data[*attacker].is_some_and(|(_, a)| threat_rank(a) < threat_rank(piece))
+            });
+            if by_lesser {
+                add(params.threat_by_lesser);
+            } else if !attackers.is_empty() && board.get_attackers(sq, color).is_empty() {
+                add(params.hanging);
+            }
+        }
+    }
+
+    if terms.bishop_pair && bishops >= 2 {
+        add(params.bishop_pair);
+    }
+
+    let to_move = if board.black_to_move {
+        Color::Black
+    } else {
+        Color::White
+    };
+    if terms.tempo && to_move == color {
+        add(params.tempo);
+    }
+
+    (mg, eg)
+}
+
+/// Positional terms of white at index 0 and black at 1, each from its own
+/// point of view
+pub fn positional_scores(
+    board: &Board,
+    terms: &PositionalTerms,
+    params: &EvalParams,
+) -> [(i32, i32); 2] {
+    let pawns = pawn_bitboards(board);
+    [Color::White, Color::Black].map(|color| positional(board, color, terms, params, &pawns))
+}
+
+/// Bishop pair, open files, rooks on the seventh rank, outposts, threats and
+/// tempo for both sides, from the point of view of white
+pub fn evaluate_positional(
+    board: &Board,
+    terms: &PositionalTerms,
+    params: &EvalParams,
+) -> (i32, i32) {
+    let [(w_mg, w_eg), (b_mg, b_eg)] = positional_scores(board, terms, params);
+    (w_mg - b_mg, w_eg - b_eg)
+}
@@ -0,0 +1,63 @@
+/// Tunable margins (in centipawns) of the static eval based pruning, the
+/// limits of the extensions and reductions in alpha_beta and the quiescence
+/// search settings. Depths are counted in plies left before the quiescence
+/// search, so a margin at index 0 applies with one ply left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchParams {
+    // Reverse futility pruning: the node fails high when the static eval
+    // minus rfp_margin per ply left is still at least beta
+    pub rfp_margin: i32,
+    pub rfp_max_plies: usize,
+    // Futility pruning: quiet moves are skipped when the static eval plus
+    // the margin can't raise alpha
+    pub futility_margins: [i32; 2],
+    // Razoring: a node whose static eval plus the margin is at most alpha
+    // is resolved by the quiescence search when that confirms the fail low
+    pub razor_margins: [i32; 2],
+    // Most plies a single line can be extended by (checks, recaptures and
+    // singular moves), so the search can't explode
+    pub max_extensions: usize,
+    // Singular extension: the transposition table move is extended when all
+    // other moves fail low against its score minus singular_margin per ply left
+    pub singular_min_plies: usize,
+    pub singular_margin: i32,
+    // Internal iterative reduction: nodes without a transposition table move
+    // are searched one ply shallower when at least iir_min_plies, and never
+    // fewer than 2, are left
+    pub iir_min_plies: usize,
+    // Quiescence search: captures are skipped when the stand pat score plus
+    // the material they win and delta_margin is below alpha
+    pub delta_margin: i32,
+    // Search quiet checks at the first ply of the quiescence search
+    pub qsearch_checks: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            rfp_margin: 100,
+            rfp_max_plies: 3,
+            futility_margins: [200, 500],
+            razor_margins: [300, 550],
+            max_extensions: 8,
+            singular_min_plies: 4,
+            singular_margin: 25,
+            iir_min_plies: 3,
+            delta_margin: 200,
+            qsearch_checks: true,
+        }
+    }
+}
+
+impl SearchParams {
+    /// Parameters that never prune, used to compare against the defaults
+    pub fn no_pruning() -> Self {
+        SearchParams {
+            rfp_margin: 0,
+            rfp_max_plies: 0,
+            futility_margins: [i32::MAX / 4; 2],
+            razor_margins: [i32::MAX / 4; 2],
+            ..SearchParams::default()
+        }
+    }
+}
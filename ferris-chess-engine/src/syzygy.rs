@@ -0,0 +1,1110 @@
+use ferris_chess_board::{Board, Color, MoveData, Piece};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+// First bytes of the WDL (.rtbw) and DTZ (.rtbz) files
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags of the file header
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// Flags of each compressed table in a file
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+/// Score of a position the tables show as won, less the ply it is found
+/// at. Above any evaluation but below the mate scores.
+pub const TB_WIN: i32 = 20000;
+
+/// Result of a position for the side to move. Cursed wins and blessed
+/// losses are drawn by the fifty-move rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Option<Self> {
+        match value {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+
+    /// The result for the other side
+    pub fn opposite(self) -> Self {
+        Wdl::from_value(-(self as i32)).unwrap()
+    }
+
+    fn sign(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+/// Distance to zeroing of a position whose best move is a capture or pawn
+/// move with the given result
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TableType {
+    Wdl,
+    Dtz,
+}
+
+/// Tables used to compute the index of a position
+struct Maps {
+    // The a1-d1-d4 triangle numbered from 0, the squares on the diagonal last
+    a1d1d4: [usize; 64],
+    // The 462 placements of two kings with the first one in the triangle
+    kk: [[u64; 64]; 10],
+    // Squares below the a1-h8 diagonal numbered from 0 to 27
+    b1h1h7: [u64; 64],
+    binomial: [[u64; 64]; 6],
+    // Squares a2 to h7 numbered from 47 down, the leading pawn is the one
+    // with the highest number
+    pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+/// Rank minus file, 0 on the a1-h8 diagonal and negative below it
+fn off_diagonal(sq: usize) -> i32 {
+    (sq / 8) as i32 - (sq % 8) as i32
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    (a % 8).abs_diff(b % 8).max((a / 8).abs_diff(b / 8))
+}
+
+fn maps() -> &'static Maps {
+    static MAPS: OnceLock<Maps> = OnceLock::new();
+    MAPS.get_or_init(|| {
+        let mut maps = Maps {
+            a1d1d4: [0; 64],
+            kk: [[0; 64]; 10],
+            b1h1h7: [0; 64],
+            binomial: [[0; 64]; 6],
+            pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        for (code, sq) in (0..64).filter(|sq| off_diagonal(*sq) < 0).enumerate() {
+            maps.b1h1h7[sq] = code as u64;
+        }
+
+        let mut code = 0;
+        let mut diagonal = vec![];
+        for sq in [0, 1, 2, 3, 9, 10, 11, 18, 19, 27] {
+            if off_diagonal(sq) < 0 {
+                maps.a1d1d4[sq] = code;
+                code += 1;
+            } else {
+                diagonal.push(sq);
+            }
+        }
+        for sq in diagonal {
+            maps.a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // With the first king on the diagonal the second one is not above
+        // it, and placements with both on the diagonal come last
+        let mut code = 0;
+        let mut both_on_diagonal = vec![];
+        for idx in 0..10 {
+            for first in (0..28).filter(|sq| maps.a1d1d4[*sq] == idx && (idx > 0 || *sq == 1)) {
+                for second in (0..64).filter(|sq| distance(first, *sq) > 1) {
+                    match (off_diagonal(first), off_diagonal(second)) {
+                        (0, off) if off > 0 => (),
+                        (0, 0) => both_on_diagonal.push((idx, second)),
+                        _ => {
+                            maps.kk[idx][second] = code;
+                            code += 1;
+                        }
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            maps.kk[idx][second] = code;
+            code += 1;
+        }
+
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                maps.binomial[k][n] = if k > 0 {
+                    maps.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let sq = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        maps.pawns[sq] = available;
+                        maps.pawns[sq ^ 7] = available.saturating_sub(1);
+                        available = available.saturating_sub(2);
+                    }
+                    maps.lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += maps.binomial[lead_pawns - 1][maps.pawns[sq]];
+                }
+                maps.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+        maps
+    })
+}
+
+/// Piece code used in the files, 1 to 6 for the white pawn to king and 9
+/// to 14 for the black ones
+fn piece_code(color: Color, piece: Piece) -> u8 {
+    let code = match piece {
+        Piece::Pawn => 1,
+        Piece::Knight => 2,
+        Piece::Bishop => 3,
+        Piece::Rook => 4,
+        Piece::Queen => 5,
+        Piece::King => 6,
+    };
+    match color {
+        Color::White => code,
+        Color::Black => code + 8,
+    }
+}
+
+/// What the name of a table tells about the encoding of its positions
+#[derive(Clone, Copy, Debug)]
+struct TableInfo {
+    pieces: usize,
+    has_pawns: bool,
+    // Some side has exactly one piece of a kind other than the king
+    has_unique_pieces: bool,
+    // Both sides have the same pieces
+    symmetric: bool,
+    // Pawns of the side the leading pawns belong to, then of the other side
+    pawn_count: [usize; 2],
+}
+
+impl TableInfo {
+    fn of(name: &str) -> Option<Self> {
+        let pieces = table_pieces(name)?;
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, letter| side.chars().filter(|c| *c == letter).count();
+        let has_unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1));
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        // The side with fewer pawns leads, as it compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(TableInfo {
+            pieces,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            symmetric: white == black,
+            pawn_count: match white_leads {
+                true => [white_pawns, black_pawns],
+                false => [black_pawns, white_pawns],
+            },
+        })
+    }
+
+    fn files(&self) -> usize {
+        if self.has_pawns {
+            4
+        } else {
+            1
+        }
+    }
+}
+
+fn read_le(data: &[u8], pos: usize, len: usize) -> Option<u64> {
+    let bytes = data.get(pos..pos + len)?;
+    Some(bytes.iter().rev().fold(0, |acc, b| acc << 8 | *b as u64))
+}
+
+/// Big endian, reading zeros past the end of the data
+fn read_be(data: &[u8], pos: usize, len: usize) -> u64 {
+    (pos..pos + len).fold(0, |acc, i| acc << 8 | *data.get(i).unwrap_or(&0) as u64)
+}
+
+/// One compressed table of a file, for a side to move and a file of the
+/// leading pawn. The values are Huffman coded symbols, each standing for a
+/// pair of symbols or a single value, in blocks of a fixed size.
+#[derive(Debug, Default)]
+struct PairsData {
+    flags: u8,
+    // Piece codes in the order their squares are encoded
+    pieces: Vec<u8>,
+    // Pieces encoded together, the leading group first
+    group_len: Vec<usize>,
+    // Factors of the group indices, the last one is the table size
+    group_idx: Vec<u64>,
+    block_size: usize,
+    // Positions between entries of the sparse index
+    span: u64,
+    sparse_index_size: usize,
+    block_length_size: usize,
+    blocks_num: usize,
+    // The value itself in single value tables
+    min_sym_len: u8,
+    lowest_sym: Vec<u64>,
+    // Lowest code of each length, left aligned
+    base64: Vec<u64>,
+    // Number of values a symbol stands for, less one
+    symlen: Vec<u8>,
+    // Offsets into the file
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    data: usize,
+    // DTZ only, maps of the stored values for wins, losses, cursed wins
+    // and blessed losses
+    map_idx: [usize; 4],
+}
+
+impl PairsData {
+    /// Splits pieces into groups, and orders the groups by order, which
+    /// holds the position of the leading group and of the other side's
+    /// pawns
+    fn new(
+        info: &TableInfo,
+        pieces: Vec<u8>,
+        order: [usize; 2],
+        file: usize,
+    ) -> Option<Self> {
+        let maps = maps();
+        let mut first_len: i32 = if info.has_pawns {
+            0
+        } else if info.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        let mut group_len = vec![1];
+        for i in 1..pieces.len() {
+            first_len -= 1;
+            if first_len > 0 || pieces[i] == pieces[i - 1] {
+                *group_len.last_mut().unwrap() += 1;
+            } else {
+                group_len.push(1);
+            }
+        }
+
+        let groups = group_len.len();
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - group_len[0] - if both_pawns { group_len[1] } else { 0 };
+        let mut group_idx = vec![0; groups + 1];
+        let mut idx: u64 = 1;
+        let mut k = 0;
+        while next < groups || k == order[0] || k == order[1] {
+            if k == order[0] {
+                group_idx[0] = idx;
+                idx *= if info.has_pawns {
+                    *maps.lead_pawns_size.get(group_len[0])?.get(file)?
+                } else if info.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                group_idx[1] = idx;
+                idx *= maps.binomial.get(group_len[1])?[48 - group_len[0]];
+            } else {
+                *group_idx.get_mut(next)? = idx;
+                idx *= maps.binomial.get(group_len[next])?[free_squares];
+                free_squares -= group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        group_idx[groups] = idx;
+
+        Some(PairsData {
+            pieces,
+            group_len,
+            group_idx,
+            ..PairsData::default()
+        })
+    }
+
+    fn size(&self) -> u64 {
+        *self.group_idx.last().unwrap()
+    }
+
+    /// Index of a position, given by the squares of the pieces in the
+    /// order of self.pieces. The leading pawns come first, the one with the
+    /// highest pawn number at index 0.
+    fn index(&self, info: &TableInfo, squares: &mut [usize]) -> u64 {
+        let maps = maps();
+        // The leading piece goes on files a to d
+        if squares[0] % 8 > 3 {
+            squares.iter_mut().for_each(|sq| *sq ^= 7);
+        }
+
+        let mut idx;
+        if info.has_pawns {
+            let lead_pawns = self.group_len[0];
+            idx = maps.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|sq| maps.pawns[*sq]);
+            for (i, sq) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += maps.binomial[i][maps.pawns[*sq]];
+            }
+        } else {
+            // Then on ranks 1 to 4, and below the diagonal for the first
+            // leading piece off it
+            if squares[0] / 8 > 3 {
+                squares.iter_mut().for_each(|sq| *sq ^= 56);
+            }
+            for i in 0..self.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    squares[i..]
+                        .iter_mut()
+                        .for_each(|sq| *sq = ((*sq >> 3) | (*sq << 3)) & 63);
+                }
+                break;
+            }
+
+            if info.has_unique_pieces {
+                let rank = |sq: usize| (sq / 8) as u64;
+                let adjust1 = u64::from(squares[1] > squares[0]);
+                let adjust2 =
+                    u64::from(squares[2] > squares[0]) + u64::from(squares[2] > squares[1]);
+                idx = if off_diagonal(squares[0]) != 0 {
+                    (maps.a1d1d4[squares[0]] as u64 * 63 + squares[1] as u64 - adjust1) * 62
+                        + squares[2] as u64
+                        - adjust2
+                } else if off_diagonal(squares[1]) != 0 {
+                    (6 * 63 + rank(squares[0]) * 28 + maps.b1h1h7[squares[1]]) * 62
+                        + squares[2] as u64
+                        - adjust2
+                } else if off_diagonal(squares[2]) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank(squares[0]) * 7 * 28
+                        + (rank(squares[1]) - adjust1) * 28
+                        + maps.b1h1h7[squares[2]]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank(squares[0]) * 7 * 6
+                        + (rank(squares[1]) - adjust1) * 6
+                        + (rank(squares[2]) - adjust2)
+                };
+            } else {
+                idx = maps.kk[maps.a1d1d4[squares[0]]][squares[1]];
+            }
+        }
+        idx *= self.group_idx[0];
+
+        // The other groups by their squares in ascending order, less the
+        // squares of the earlier groups
+        let mut start = self.group_len[0];
+        let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        for (next, len) in self.group_len.iter().enumerate().skip(1) {
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..*len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|s| sq > **s).count();
+                n += maps.binomial[i + 1][sq - adjust - 8 * usize::from(remaining_pawns)];
+            }
+            remaining_pawns = false;
+            idx += n * self.group_idx[next];
+            start += len;
+        }
+        idx
+    }
+
+    /// Reads the sizes and the symbols of the compression, returns the
+    /// offset after them
+    fn set_sizes(&mut self, data: &[u8], mut pos: usize) -> Option<usize> {
+        self.flags = *data.get(pos)?;
+        if self.flags & SINGLE_VALUE != 0 {
+            self.min_sym_len = *data.get(pos + 1)?;
+            return Some(pos + 2);
+        }
+
+        self.block_size = 1usize.checked_shl(*data.get(pos + 1)? as u32)?;
+        self.span = 1u64.checked_shl(*data.get(pos + 2)? as u32)?;
+        self.sparse_index_size = self.size().div_ceil(self.span) as usize;
+        let padding = *data.get(pos + 3)? as usize;
+        self.blocks_num = read_le(data, pos + 4, 4)? as usize;
+        self.block_length_size = self.blocks_num + padding;
+        let max_sym_len = *data.get(pos + 8)? as usize;
+        self.min_sym_len = *data.get(pos + 9)?;
+        let min_sym_len = self.min_sym_len as usize;
+        pos += 10;
+        if min_sym_len == 0 || max_sym_len < min_sym_len || max_sym_len > 32 {
+            return None;
+        }
+
+        // Canonical Huffman code: longer codes have lower values, and the
+        // symbols of a length are consecutive
+        let lengths = max_sym_len - min_sym_len + 1;
+        self.lowest_sym = (0..lengths)
+            .map(|i| read_le(data, pos + 2 * i, 2))
+            .collect::<Option<_>>()?;
+        pos += 2 * lengths;
+        let mut base64 = vec![0u64; lengths];
+        for i in (0..lengths - 1).rev() {
+            base64[i] =
+                (base64[i + 1] + self.lowest_sym[i]).checked_sub(self.lowest_sym[i + 1])? / 2;
+        }
+        for (i, base) in base64.iter_mut().enumerate() {
+            *base = base.checked_shl((64 - i - min_sym_len) as u32).unwrap_or(0);
+        }
+        self.base64 = base64;
+
+        let symbols = read_le(data, pos, 2)? as usize;
+        pos += 2;
+        self.btree = pos;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(data, sym, &mut visited)?;
+            }
+        }
+        Some(pos + 3 * symbols + (symbols & 1))
+    }
+
+    /// Left and right symbols of a pair, the right one is 0xFFF for a
+    /// symbol standing for the value on the left
+    fn pair(&self, data: &[u8], sym: usize) -> Option<(usize, usize)> {
+        let lr = data.get(self.btree + 3 * sym..self.btree + 3 * sym + 3)?;
+        let left = ((lr[1] as usize & 0xF) << 8) | lr[0] as usize;
+        let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+        Some((left, right))
+    }
+
+    fn set_symlen(&mut self, data: &[u8], sym: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[sym] = true;
+        let (left, right) = self.pair(data, sym)?;
+        if right == 0xFFF {
+            return Some(0);
+        }
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                self.symlen[child] = self.set_symlen(data, child, visited)?;
+            }
+        }
+        Some(
+            self.symlen[left]
+                .wrapping_add(self.symlen[right])
+                .wrapping_add(1),
+        )
+    }
+
+    /// Value stored at idx
+    fn decompress(&self, data: &[u8], idx: u64) -> Option<u16> {
+        if self.flags & SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as u16);
+        }
+
+        // The sparse index gives the block and offset of the middle position
+        // of each span, from where the blocks are walked to idx
+        let k = (idx / self.span) as usize;
+        if k >= self.sparse_index_size {
+            return None;
+        }
+        let entry = self.sparse_index + 6 * k;
+        let mut block = read_le(data, entry, 4)? as usize;
+        let mut offset = read_le(data, entry + 4, 2)? as i64;
+        offset += (idx % self.span) as i64 - (self.span / 2) as i64;
+
+        let block_length = |block: usize| {
+            (block < self.block_length_size)
+                .then(|| read_le(data, self.block_length + 2 * block, 2))
+                .flatten()
+                .map(|len| len as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+        if block >= self.blocks_num {
+            return None;
+        }
+
+        let mut ptr = self.data + block * self.block_size;
+        let mut buf = read_be(data, ptr, 8);
+        ptr += 8;
+        let mut buf_size = 64;
+        let min_sym_len = self.min_sym_len as usize;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf < self.base64[len] {
+                len += 1;
+                if len == self.base64.len() {
+                    return None;
+                }
+            }
+            sym = ((buf - self.base64[len]) >> (64 - len - min_sym_len)) as usize
+                + self.lowest_sym[len] as usize;
+            let sym_values = *self.symlen.get(sym)? as i64 + 1;
+            if offset < sym_values {
+                break;
+            }
+            offset -= sym_values;
+            let code_len = len + min_sym_len;
+            buf <<= code_len;
+            buf_size -= code_len;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= read_be(data, ptr, 4) << (64 - buf_size);
+                ptr += 4;
+            }
+        }
+
+        // The value is in the left or right half of the pair
+        while self.symlen[sym] != 0 {
+            let (left, right) = self.pair(data, sym)?;
+            let left_values = *self.symlen.get(left)? as i64 + 1;
+            if offset < left_values {
+                sym = left;
+            } else {
+                offset -= left_values;
+                sym = right;
+            }
+        }
+        self.pair(data, sym).map(|(value, _)| value as u16)
+    }
+}
+
+/// A WDL or DTZ file read into memory
+#[derive(Debug)]
+struct Table {
+    info: TableInfo,
+    data: Vec<u8>,
+    // By file of the leading pawn, then by side to move for WDL tables of
+    // different pieces on each side
+    pairs: Vec<Vec<PairsData>>,
+    // Offset of the value maps of DTZ tables
+    dtz_map: usize,
+}
+
+impl Table {
+    /// Reads the file layout, None for a file that isn't a table of name
+    fn parse(table_type: TableType, name: &str, data: Vec<u8>) -> Option<Self> {
+        let info = TableInfo::of(name)?;
+        let magic = match table_type {
+            TableType::Wdl => WDL_MAGIC,
+            TableType::Dtz => DTZ_MAGIC,
+        };
+        if data.get(..4)? != magic {
+            return None;
+        }
+        let flags = *data.get(4)?;
+        if (flags & HAS_PAWNS != 0) != info.has_pawns || (flags & SPLIT != 0) == info.symmetric {
+            return None;
+        }
+        let sides = match table_type {
+            TableType::Wdl if !info.symmetric => 2,
+            _ => 1,
+        };
+        let both_pawns = info.has_pawns && info.pawn_count[1] > 0;
+
+        // The pieces of the position for white to move are in the low
+        // nibbles, for black to move in the high ones
+        let mut pos = 5;
+        let mut pairs = vec![];
+        for file in 0..info.files() {
+            let order = *data.get(pos)? as usize;
+            let pawns_order = match both_pawns {
+                true => *data.get(pos + 1)? as usize,
+                false => 0xFF,
+            };
+            pos += 1 + usize::from(both_pawns);
+            let piece_bytes = data.get(pos..pos + info.pieces)?;
+            pos += info.pieces;
+
+            let mut file_pairs = vec![];
+            for side in 0..sides {
+                let shift = 4 * side;
+                let pieces = piece_bytes.iter().map(|b| (b >> shift) & 0xF).collect();
+                let order = [(order >> shift) & 0xF, (pawns_order >> shift) & 0xF];
+                file_pairs.push(PairsData::new(&info, pieces, order, file)?);
+            }
+            pairs.push(file_pairs);
+        }
+        pos += pos & 1;
+
+        for pd in pairs.iter_mut().flatten() {
+            pos = pd.set_sizes(&data, pos)?;
+        }
+
+        let dtz_map = pos;
+        if table_type == TableType::Dtz {
+            for file_pairs in pairs.iter_mut() {
+                let pd = &mut file_pairs[0];
+                if pd.flags & MAPPED == 0 {
+                    continue;
+                }
+                if pd.flags & WIDE != 0 {
+                    pos += pos & 1;
+                    for i in 0..4 {
+                        pd.map_idx[i] = (pos - dtz_map) / 2 + 1;
+                        pos += 2 * read_le(&data, pos, 2)? as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        pd.map_idx[i] = pos - dtz_map + 1;
+                        pos += *data.get(pos)? as usize + 1;
+                    }
+                }
+            }
+            pos += pos & 1;
+        }
+
+        for pd in pairs.iter_mut().flatten() {
+            pd.sparse_index = pos;
+            pos += 6 * pd.sparse_index_size;
+        }
+        for pd in pairs.iter_mut().flatten() {
+            pd.block_length = pos;
+            pos += 2 * pd.block_length_size;
+        }
+        for pd in pairs.iter_mut().flatten() {
+            pos = (pos + 0x3F) & !0x3F;
+            pd.data = pos;
+            pos += pd.blocks_num * pd.block_size;
+        }
+        if pos > data.len() {
+            return None;
+        }
+
+        Some(Table {
+            info,
+            data,
+            pairs,
+            dtz_map,
+        })
+    }
+
+    /// DTZ tables only store one side to move
+    fn has_side_to_move(&self, stm: usize, file: usize) -> bool {
+        (self.pairs[file][0].flags & STM) as usize == stm
+            || (self.info.symmetric && !self.info.has_pawns)
+    }
+
+    /// Stored DTZ value converted to plies
+    fn dtz_plies(&self, file: usize, value: u16, wdl: Wdl) -> Option<i32> {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let pd = &self.pairs[file][0];
+        let mut value = value as usize;
+        if pd.flags & MAPPED != 0 {
+            let idx = pd.map_idx[WDL_MAP[(wdl as i32 + 2) as usize]] + value;
+            value = match pd.flags & WIDE != 0 {
+                true => read_le(&self.data, self.dtz_map + 2 * idx, 2)? as usize,
+                false => *self.data.get(self.dtz_map + idx)? as usize,
+            };
+        }
+
+        // Stored in moves unless the flags say plies
+        let in_moves = match wdl {
+            Wdl::Win => pd.flags & WIN_PLIES == 0,
+            Wdl::Loss => pd.flags & LOSS_PLIES == 0,
+            _ => true,
+        };
+        Some(value as i32 * if in_moves { 2 } else { 1 } + 1)
+    }
+}
+
+/// Pieces of a side in the table name order, such as KRP
+fn side_name(board: &Board, color: Color) -> String {
+    let mut name = String::from("K");
+    for (piece, letter) in [
+        (Piece::Queen, 'Q'),
+        (Piece::Rook, 'R'),
+        (Piece::Bishop, 'B'),
+        (Piece::Knight, 'N'),
+        (Piece::Pawn, 'P'),
+    ] {
+        for _ in board.data.iter().filter(|p| **p == Some((color, piece))) {
+            name.push(letter);
+        }
+    }
+    name
+}
+
+/// Value of a position in a table
+enum Lookup<'a> {
+    Value {
+        table: &'a Table,
+        table_file: usize,
+        value: u16,
+    },
+    // A DTZ table of the other side to move
+    ChangeStm,
+}
+
+/// Syzygy tables found in the directories of the SyzygyPath option. The
+/// files are read the first time the search probes them.
+#[derive(Default)]
+pub struct SyzygyTables {
+    // Files by table name, such as KRPvKR
+    pub wdl: BTreeMap<String, PathBuf>,
+    pub dtz: BTreeMap<String, PathBuf>,
+    // Tables read so far, None for files that turned out not to be tables
+    wdl_tables: BTreeMap<String, OnceLock<Option<Table>>>,
+    dtz_tables: BTreeMap<String, OnceLock<Option<Table>>>,
+    max_pieces: usize,
+}
+
+/// Number of pieces of a table name such as KRPvKR, None for other names
+pub fn table_pieces(name: &str) -> Option<usize> {
+    let is_side = |side: &str| {
+        side.strip_prefix('K')
+            .is_some_and(|rest| rest.chars().all(|c| "QRBNP".contains(c)))
+    };
+    let (white, black) = name.split_once('v')?;
+    (is_side(white) && is_side(black)).then_some(white.len() + black.len())
+}
+
+impl SyzygyTables {
+    /// Indexes the tables in path, a list of directories separated as in the
+    /// PATH environment variable
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut tables = SyzygyTables::default();
+        for dir in env::split_paths(path) {
+            let entries =
+                fs::read_dir(&dir).map_err(|e| format!("Can't read {}: {}", dir.display(), e))?;
+            for file in entries.flatten().map(|entry| entry.path()) {
+                let name = file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+                let (magic, table_files) = match file.extension().and_then(|e| e.to_str()) {
+                    Some("rtbw") => (WDL_MAGIC, &mut tables.wdl),
+                    Some("rtbz") => (DTZ_MAGIC, &mut tables.dtz),
+                    _ => continue,
+                };
+                if table_pieces(name).is_none() {
+                    continue;
+                }
+
+                let mut header = [0; 4];
+                File::open(&file)
+                    .and_then(|mut f| f.read_exact(&mut header))
+                    .map_err(|e| format!("Can't read {}: {}", file.display(), e))?;
+                if header != magic {
+                    return Err(format!("{} is not a Syzygy table", file.display()));
+                }
+                table_files.insert(name.to_string(), file);
+            }
+        }
+
+        tables.wdl_tables = tables
+            .wdl
+            .keys()
+            .map(|name| (name.clone(), OnceLock::new()))
+            .collect();
+        tables.dtz_tables = tables
+            .dtz
+            .keys()
+            .map(|name| (name.clone(), OnceLock::new()))
+            .collect();
+        tables.max_pieces = tables
+            .wdl
+            .keys()
+            .filter_map(|name| table_pieces(name))
+            .max()
+            .unwrap_or(0);
+        Ok(tables)
+    }
+
+    /// Pieces of the largest WDL table, 0 without tables
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn table(&self, table_type: TableType, name: &str) -> Option<&Table> {
+        let (files, tables) = match table_type {
+            TableType::Wdl => (&self.wdl, &self.wdl_tables),
+            TableType::Dtz => (&self.dtz, &self.dtz_tables),
+        };
+        tables
+            .get(name)?
+            .get_or_init(|| Table::parse(table_type, name, fs::read(files.get(name)?).ok()?))
+            .as_ref()
+    }
+
+    /// Looks the position up in the table of its pieces. Tables are stored
+    /// with the stronger side as white, so positions where black is the
+    /// stronger side are looked up with the colors swapped.
+    fn lookup(&self, table_type: TableType, board: &Board) -> Option<Lookup<'_>> {
+        let (white, black) = (
+            side_name(board, Color::White),
+            side_name(board, Color::Black),
+        );
+        let files = match table_type {
+            TableType::Wdl => &self.wdl,
+            TableType::Dtz => &self.dtz,
+        };
+        let white_name = format!("{}v{}", white, black);
+        let (name, black_stronger) = match files.contains_key(&white_name) {
+            true => (white_name, false),
+            false => (format!("{}v{}", black, white), true),
+        };
+        let table = self.table(table_type, &name)?;
+
+        // Symmetric tables only store white to move
+        let flip = black_stronger || (table.info.symmetric && board.black_to_move);
+        let (flip_color, flip_squares) = if flip { (8, 56) } else { (0, 0) };
+        let stm = usize::from(flip != board.black_to_move);
+        let pieces_on =
+            |sq: usize| board.data[sq].map(|(color, piece)| piece_code(color, piece) ^ flip_color);
+
+        let mut squares = vec![];
+        let mut pieces = vec![];
+        let mut table_file = 0;
+        let lead_piece = table.pairs[0][0].pieces[0];
+        if table.info.has_pawns {
+            for sq in (0..64).filter(|sq| pieces_on(*sq) == Some(lead_piece)) {
+                squares.push(sq ^ flip_squares);
+                pieces.push(lead_piece);
+            }
+            let maps = maps();
+            let lead = (1..squares.len()).fold(0, |best, i| {
+                if maps.pawns[squares[i]] > maps.pawns[squares[best]] {
+                    i
+                } else {
+                    best
+                }
+            });
+            squares.swap(0, lead);
+            table_file = squares[0] % 8;
+            if table_file > 3 {
+                table_file = 7 - table_file;
+            }
+        }
+        let lead_pawns = squares.len();
+
+        if table_type == TableType::Dtz && !table.has_side_to_move(stm, table_file) {
+            return Some(Lookup::ChangeStm);
+        }
+
+        for sq in 0..64 {
+            match pieces_on(sq) {
+                Some(piece) if !(table.info.has_pawns && piece == lead_piece) => {
+                    squares.push(sq ^ flip_squares);
+                    pieces.push(piece);
+                }
+                _ => (),
+            }
+        }
+
+        // The order of the pieces in the table
+        let pd = &table.pairs[table_file][stm % table.pairs[table_file].len()];
+        for i in lead_pawns..pieces.len().saturating_sub(1) {
+            if let Some(j) = (i + 1..pieces.len()).find(|j| pieces[*j] == pd.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        if pieces != pd.pieces {
+            return None;
+        }
+
+        let idx = pd.index(&table.info, &mut squares);
+        if idx >= pd.size() {
+            return None;
+        }
+        let value = pd.decompress(&table.data, idx)?;
+        Some(Lookup::Value {
+            table,
+            table_file,
+            value,
+        })
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Option<Wdl> {
+        if board.pieces_w.len() + board.pieces_b.len() == 2 {
+            return Some(Wdl::Draw);
+        }
+        match self.lookup(TableType::Wdl, board)? {
+            Lookup::Value { value, .. } => Wdl::from_value(value as i32 - 2),
+            Lookup::ChangeStm => None,
+        }
+    }
+
+    /// Result of the position, searching the captures first, and the pawn
+    /// moves too with zeroing_moves. The tables store any value for
+    /// positions won by a capture and don't cover en passant captures. Also
+    /// whether the best move is one of the searched moves, for which DTZ
+    /// tables store any value.
+    fn search(&self, board: &mut Board, zeroing_moves: bool) -> Option<(Wdl, bool)> {
+        let moves = board.get_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for m in &moves {
+            if m.capture.is_none() && !(zeroing_moves && m.piece == Piece::Pawn) {
+                continue;
+            }
+            searched += 1;
+
+            board.make_move(m);
+            let value = self.search(board, false);
+            board.unmake_move(m);
+            let value = value?.0.opposite();
+
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        let no_more_moves = searched > 0 && searched == moves.len();
+        let value = match no_more_moves {
+            true => best,
+            false => self.probe_wdl_table(board)?,
+        };
+        if best >= value {
+            Some((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    /// Result of the position for the side to move, None when a table is
+    /// missing
+    pub fn probe_wdl(&self, board: &mut Board) -> Option<Wdl> {
+        self.search(board, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Plies to the next capture or pawn move of the winning line, or to
+    /// mate, positive for wins and negative for losses. 0 for draws and
+    /// None when a table is missing.
+    pub fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+        let (wdl, zeroing_best) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        if let Lookup::Value {
+            table,
+            table_file,
+            value,
+        } = self.lookup(TableType::Dtz, board)?
+        {
+            let plies = table.dtz_plies(table_file, value, wdl)?;
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((plies + if cursed { 100 } else { 0 }) * wdl.sign());
+        }
+
+        // The table stores the other side to move, so the best move is
+        // found by probing the positions after each move
+        let mut min_dtz = i32::MAX;
+        for m in board.get_legal_moves() {
+            let zeroing = m.capture.is_some() || m.piece == Piece::Pawn;
+            board.make_move(&m);
+            let dtz = match zeroing {
+                true => self
+                    .search(board, false)
+                    .map(|(wdl, _)| -dtz_before_zeroing(wdl)),
+                false => self.probe_dtz(board).map(|dtz| -dtz),
+            };
+            let mates =
+                dtz == Some(1) && board.is_player_mated() && board.get_legal_moves().is_empty();
+            board.unmake_move(&m);
+            let mut dtz = dtz?;
+
+            if mates {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.sign() {
+                min_dtz = dtz;
+            }
+        }
+        // Mated
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    /// The root moves with the best rank by DTZ: the quickest win, or any
+    /// draw, or the slowest loss. None when a table is missing.
+    pub fn best_root_moves(&self, board: &mut Board, moves: &[MoveData]) -> Option<Vec<MoveData>> {
+        let mut ranked = vec![];
+        for m in moves {
+            board.make_move(m);
+            // Counted from the root position
+            let dtz = match board.half_moves {
+                0 => self
+                    .probe_wdl(board)
+                    .map(|wdl| dtz_before_zeroing(wdl.opposite())),
+                _ => self.probe_dtz(board).map(|dtz| -dtz - dtz.signum()),
+            };
+            let mates = board.is_player_mated() && board.get_legal_moves().is_empty();
+            board.unmake_move(m);
+
+            let dtz = match dtz? {
+                2 if mates => 1,
+                dtz => dtz,
+            };
+            let rank = match dtz {
+                dtz if dtz > 0 => (2, -dtz),
+                0 => (1, 0),
+                dtz => (0, -dtz),
+            };
+            ranked.push((rank, *m));
+        }
+
+        let best = ranked.iter().map(|(rank, _)| *rank).max()?;
+        Some(
+            ranked
+                .into_iter()
+                .filter(|(rank, _)| *rank == best)
+                .map(|(_, m)| m)
+                .collect(),
+        )
+    }
+}
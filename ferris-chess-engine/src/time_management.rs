@@ -0,0 +1,84 @@
+use crate::GoCommand;
+use std::time::{Duration, Instant};
+
+// Moves left assumed when the GUI doesn't send movestogo
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+pub const DEFAULT_MOVE_OVERHEAD: u64 = 10;
+
+/// Decides how long a search may run. The soft limit is checked between
+/// iterations (no new depth is started after it), the hard limit aborts
+/// the search. While pondering neither limit applies until ponderhit.
+#[derive(Clone)]
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    pondering: bool,
+}
+
+impl TimeManager {
+    pub fn new(go_cmd: &GoCommand, black_to_move: bool, move_overhead: u64) -> Self {
+        let (time, inc) = match black_to_move {
+            false => (go_cmd.wtime, go_cmd.winc),
+            true => (go_cmd.btime, go_cmd.binc),
+        };
+
+        let (soft_limit, hard_limit) = if go_cmd.infinite {
+            (None, None)
+        } else if let Some(movetime) = go_cmd.movetime {
+            let limit = movetime.saturating_sub(move_overhead).max(1);
+            (Some(limit), Some(limit))
+        } else if let Some(time) = time {
+            let moves_to_go = go_cmd
+                .movestogo
+                .unwrap_or(DEFAULT_MOVES_TO_GO)
+                .clamp(1, DEFAULT_MOVES_TO_GO);
+
+            // Never plan to use more than what is left on the clock
+            let available = time.saturating_sub(move_overhead).max(1);
+            let ideal = (available / moves_to_go + inc * 3 / 4).min(available);
+
+            let hard = (ideal * 3).min(available);
+
+            // Time left after the last move before the time control is of no use
+            let soft = match moves_to_go {
+                1 => hard,
+                _ => ideal * 6 / 10,
+            };
+
+            (Some(soft), Some(hard))
+        } else {
+            (None, None)
+        };
+
+        TimeManager {
+            start: Instant::now(),
+            soft_limit: soft_limit.map(Duration::from_millis),
+            hard_limit: hard_limit.map(Duration::from_millis),
+            pondering: go_cmd.ponder,
+        }
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering
+    }
+
+    /// The opponent played the expected move: our clock starts now
+    pub fn ponderhit(&mut self) {
+        self.pondering = false;
+        self.start = Instant::now();
+    }
+
+    pub fn elapsed_ms(&self) -> usize {
+        self.start.elapsed().as_millis() as usize
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        !self.pondering && self.soft_limit.is_some_and(|l| self.start.elapsed() >= l)
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        !self.pondering && self.hard_limit.is_some_and(|l| self.start.elapsed() >= l)
+    }
+}
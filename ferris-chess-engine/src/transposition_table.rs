@@ -1,4 +1,26 @@
-use ferris_chess_board::MoveData;
+use ferris_chess_board::{MoveData, MoveType, Piece};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+// Default size in MB, matches the default of the UCI Hash option
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// Number of entries sharing one index. The entries in a bucket compete
+// for a slot using the depth-plus-age replacement scheme in insert.
+const BUCKET_SIZE: usize = 4;
+
+// Number of entries looked at when estimating hashfull
+const HASHFULL_SAMPLE: usize = 1000;
+
+// Layout of the 64 bit packed entry data
+const MOVE_SHIFT: u64 = 32;
+const DEPTH_SHIFT: u64 = 53;
+const NODE_TYPE_SHIFT: u64 = 59;
+const AGE_SHIFT: u64 = 61;
+const MOVE_MASK: u64 = (1 << 21) - 1;
+const MAX_STORED_DEPTH: usize = 63;
+// Ages wrap within the 3 bits stored per entry
+const AGE_MASK: u8 = 7;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum NodeType {
@@ -16,82 +38,272 @@ pub struct TTableData {
     pub node_type: NodeType,
 }
 
+/// One table slot, shared between search threads without locking. The key
+/// is stored xor-ed with the data, so a slot that is torn by two threads
+/// writing at once no longer matches any position and is ignored.
+#[derive(Default)]
+struct TTableEntry {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+#[derive(Default)]
+struct Bucket {
+    entries: [TTableEntry; BUCKET_SIZE],
+}
+
 pub struct TranspositonTable {
-    pub data: Vec<Option<TTableData>>,
-    pub entries: u64,
+    buckets: Vec<Bucket>,
+    // Bucket count is a power of two so the index is the low bits of the hash
+    mask: usize,
+    // Generation of the current search
+    age: AtomicU8,
 }
 
-const TABLE_SIZE: usize = 100_000;
+fn piece_to_bits(piece: Piece) -> u64 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight => 2,
+        Piece::Bishop => 3,
+        Piece::Rook => 4,
+        Piece::Queen => 5,
+        Piece::King => 6,
+    }
+}
+
+fn piece_from_bits(bits: u64) -> Option<Piece> {
+    match bits {
+        1 => Some(Piece::Pawn),
+        2 => Some(Piece::Knight),
+        3 => Some(Piece::Bishop),
+        4 => Some(Piece::Rook),
+        5 => Some(Piece::Queen),
+        6 => Some(Piece::King),
+        _ => None,
+    }
+}
+
+/// Packs a move in 21 bits: start 6, end 6, move type 3, piece 3, capture 3.
+/// No move packs to 0, which no real move does as start and end differ.
+fn pack_move(m: Option<MoveData>) -> u64 {
+    let Some(m) = m else {
+        return 0;
+    };
+    let move_type = match m.move_type {
+        MoveType::Regular => 0,
+        MoveType::Castling => 1,
+        MoveType::EnPassant => 2,
+        MoveType::QueenPromotion => 3,
+        MoveType::RookPromotion => 4,
+        MoveType::BishopPromotion => 5,
+        MoveType::KnightPromotion => 6,
+    };
+    m.start_pos as u64
+        | (m.end_pos as u64) << 6
+        | move_type << 12
+        | piece_to_bits(m.piece) << 15
+        | m.capture.map_or(0, piece_to_bits) << 18
+}
+
+fn unpack_move(bits: u64) -> Option<MoveData> {
+    if bits == 0 {
+        return None;
+    }
+    let move_type = match (bits >> 12) & 7 {
+        0 => MoveType::Regular,
+        1 => MoveType::Castling,
+        2 => MoveType::EnPassant,
+        3 => MoveType::QueenPromotion,
+        4 => MoveType::RookPromotion,
+        5 => MoveType::BishopPromotion,
+        _ => MoveType::KnightPromotion,
+    };
+    Some(MoveData {
+        start_pos: (bits & 63) as usize,
+        end_pos: ((bits >> 6) & 63) as usize,
+        piece: piece_from_bits((bits >> 15) & 7)?,
+        move_type,
+        capture: piece_from_bits((bits >> 18) & 7),
+    })
+}
+
+/// Packs the data of an entry in 64 bits: score 32, move 21, depth 6,
+/// node type 2 and age 3. The node type is never 0, so an empty slot
+/// (all zeros) is not a valid entry.
+fn pack(data: &TTableData, age: u8) -> u64 {
+    let node_type = match data.node_type {
+        NodeType::Exact => 1,
+        NodeType::LowerBound => 2,
+        NodeType::UpperBound => 3,
+    };
+    data.score as u32 as u64
+        | pack_move(data.best_move) << MOVE_SHIFT
+        | (data.depth.min(MAX_STORED_DEPTH) as u64) << DEPTH_SHIFT
+        | node_type << NODE_TYPE_SHIFT
+        | ((age & AGE_MASK) as u64) << AGE_SHIFT
+}
+
+fn unpack(zobrist: u64, bits: u64) -> Option<TTableData> {
+    let node_type = match (bits >> NODE_TYPE_SHIFT) & 3 {
+        1 => NodeType::Exact,
+        2 => NodeType::LowerBound,
+        3 => NodeType::UpperBound,
+        _ => return None,
+    };
+    Some(TTableData {
+        zobrist,
+        best_move: unpack_move((bits >> MOVE_SHIFT) & MOVE_MASK),
+        depth: ((bits >> DEPTH_SHIFT) & 63) as usize,
+        score: bits as u32 as i32,
+        node_type,
+    })
+}
+
+fn age_of(bits: u64) -> u8 {
+    (bits >> AGE_SHIFT) as u8 & AGE_MASK
+}
+
+impl TTableEntry {
+    fn load(&self) -> (u64, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.key.load(Ordering::Relaxed) ^ data;
+        (key, data)
+    }
+
+    fn store(&self, zobrist: u64, data: u64) {
+        self.key.store(zobrist ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    fn is_empty(data: u64) -> bool {
+        (data >> NODE_TYPE_SHIFT) & 3 == 0
+    }
+}
 
 impl TranspositonTable {
-    pub fn new() -> Self {
-        let data: Vec<Option<TTableData>> = vec![None; TABLE_SIZE as usize];
+    pub fn new(size_mb: usize) -> Self {
+        let bucket_count = Self::bucket_count(size_mb);
 
-        TranspositonTable { data, entries: 0 }
+        TranspositonTable {
+            buckets: (0..bucket_count).map(|_| Bucket::default()).collect(),
+            mask: bucket_count - 1,
+            age: AtomicU8::new(0),
+        }
     }
 
-    pub fn get(&self, zobrist_hash: u64, depth: usize) -> Option<TTableData> {
-        if let Some(info) = self.data[zobrist_hash as usize % TABLE_SIZE] {
-            if info.zobrist == zobrist_hash && depth <= info.depth {
-                // Clone for now. Todo: Optimize
-                Some(info.clone())
-            } else {
-                None
-            }
-        } else {
-            None
+    /// Largest power of two number of buckets that fits in size_mb (at least one)
+    fn bucket_count(size_mb: usize) -> usize {
+        let max_buckets = (size_mb.max(1) * 1024 * 1024) / size_of::<Bucket>();
+        if max_buckets <= 1 {
+            return 1;
         }
+        1 << (usize::BITS - 1 - max_buckets.leading_zeros())
+    }
+
+    fn bucket(&self, zobrist_hash: u64) -> &Bucket {
+        &self.buckets[zobrist_hash as usize & self.mask]
+    }
+
+    fn age(&self) -> u8 {
+        self.age.load(Ordering::Relaxed)
+    }
+
+    fn find(&self, zobrist_hash: u64) -> Option<TTableData> {
+        self.bucket(zobrist_hash)
+            .entries
+            .iter()
+            .map(|e| e.load())
+            .find(|(key, data)| *key == zobrist_hash && !TTableEntry::is_empty(*data))
+            .and_then(|(key, data)| unpack(key, data))
+    }
+
+    pub fn get(&self, zobrist_hash: u64, depth: usize) -> Option<TTableData> {
+        self.find(zobrist_hash).filter(|info| depth <= info.depth)
     }
 
     pub fn get_pv_move_data(&self, zobrist_hash: u64) -> Option<TTableData> {
-        if let Some(info) = self.data[zobrist_hash as usize % TABLE_SIZE] {
-            if info.zobrist == zobrist_hash {
-                // Clone for now. Todo: Optimize
-                Some(info.clone())
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+        self.find(zobrist_hash)
     }
 
     pub fn get_perft_data(&self, zobrist_hash: u64, depth: usize) -> Option<TTableData> {
-        if let Some(info) = self.data[zobrist_hash as usize % TABLE_SIZE] {
-            if info.zobrist == zobrist_hash && depth == info.depth {
-                // Clone for now. Todo: Optimize
-                Some(info.clone())
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+        self.find(zobrist_hash).filter(|info| depth == info.depth)
     }
 
-    pub fn clear(&mut self) {
-        self.data = vec![None; TABLE_SIZE];
-        self.entries = 0;
+    /// Starts a new search generation. Entries from older generations are
+    /// preferred for replacement and are not counted by hashfull.
+    pub fn new_search(&self) {
+        self.age
+            .store(self.age().wrapping_add(1) & AGE_MASK, Ordering::Relaxed);
     }
 
-    pub fn insert(&mut self, data: TTableData) {
+    pub fn clear(&self) {
+        for entry in self.buckets.iter().flat_map(|b| b.entries.iter()) {
+            entry.store(0, 0);
+        }
+        self.age.store(0, Ordering::Relaxed);
+    }
 
-        let idx = data.zobrist as usize % TABLE_SIZE;
+    /// Permill of sampled entries that were written by the current search
+    pub fn hashfull(&self) -> usize {
+        let age = self.age();
+        let sampled = self
+            .buckets
+            .iter()
+            .flat_map(|b| b.entries.iter())
+            .take(HASHFULL_SAMPLE);
 
-        // If current node is Exact (PV) then always overwrite,
-        // otherwise don't overwrite existing Exact (PV) nodes
-        if data.node_type != NodeType::Exact {
-            if let Some(cached_data) = self.data[idx] {
-                if cached_data.node_type == NodeType::Exact {
-                    return;
-                }
+        let mut total = 0;
+        let mut used = 0;
+        for entry in sampled {
+            total += 1;
+            let (_, data) = entry.load();
+            if !TTableEntry::is_empty(data) && age_of(data) == age {
+                used += 1;
             }
         }
 
-        if self.data[idx].is_none() {
-            self.entries += 1;
+        used * 1000 / total
+    }
+
+    pub fn insert(&self, data: TTableData) {
+        let age = self.age();
+        let bucket = self.bucket(data.zobrist);
+
+        // Same position already stored: keep the deeper result unless it is stale
+        let cached = bucket.entries.iter().find_map(|e| {
+            let (key, bits) = e.load();
+            match key == data.zobrist {
+                true => Some((e, unpack(key, bits)?, age_of(bits))),
+                false => None,
+            }
+        });
+
+        if let Some((entry, cached, cached_age)) = cached {
+            if data.depth >= cached.depth
+                || data.node_type == NodeType::Exact
+                || cached_age != age
+            {
+                let best_move = data.best_move.or(cached.best_move);
+                entry.store(data.zobrist, pack(&TTableData { best_move, ..data }, age));
+            }
+            return;
         }
 
-        self.data[idx] = Some(data);
+        // Otherwise use an empty slot, or replace the entry with the lowest
+        // depth, where entries from earlier searches count as shallower
+        let victim = bucket
+            .entries
+            .iter()
+            .min_by_key(|e| {
+                let (_, bits) = e.load();
+                if TTableEntry::is_empty(bits) {
+                    return i32::MIN;
+                }
+                let age_diff = age.wrapping_sub(age_of(bits)) & AGE_MASK;
+                ((bits >> DEPTH_SHIFT) & 63) as i32 - 8 * age_diff as i32
+            })
+            .unwrap();
+
+        victim.store(data.zobrist, pack(&data, age));
     }
 }
@@ -0,0 +1,252 @@
+use crate::eval_params::EvalParams;
+use crate::pesto::PestoEvaluator;
+use crate::Engine;
+use ferris_chess_board::Board;
+use std::{fs, thread};
+
+/// A position labeled with the result of the game it was played in
+#[derive(Clone)]
+pub struct TuningPosition {
+    pub board: Board,
+    // 1.0 for a white win, 0.5 for a draw and 0.0 for a black win
+    pub result: f64,
+}
+
+fn parse_result(result: &str) -> Result<f64, String> {
+    let result = result.trim().trim_end_matches(';').trim_matches('"');
+    match result {
+        "1-0" => Ok(1.0),
+        "0-1" => Ok(0.0),
+        "1/2-1/2" => Ok(0.5),
+        _ => match result.parse::<f64>() {
+            Ok(r) if (0.0..=1.0).contains(&r) => Ok(r),
+            _ => Err(format!("Invalid game result {}", result)),
+        },
+    }
+}
+
+/// Board from a FEN string, or from the first four fields of an EPD
+/// record with the move clocks left out
+pub(crate) fn parse_board(fen: &str) -> Result<Board, String> {
+    let mut fields: Vec<&str> = fen.split_whitespace().take(6).collect();
+    if fields.len() < 4 || !["w", "b"].contains(&fields[1]) {
+        return Err(format!("Invalid FEN {}", fen.trim()));
+    }
+    if fields.len() < 6 {
+        fields.truncate(4);
+        fields.extend(["0", "1"]);
+    }
+    Ok(Board::from_fen(&fields.join(" ")))
+}
+
+impl TuningPosition {
+    /// Parses an EPD record with the result in a c9 opcode, as in
+    /// `<epd> c9 "1-0";`, or a `<fen>;<result>` or `<fen>;<score>;<result>`
+    /// line. Results are 1-0, 0-1, 1/2-1/2 or a number from 0.0 to 1.0, all
+    /// from the point of view of white.
+    pub fn parse(line: &str) -> Result<TuningPosition, String> {
+        let (fen, result) = if let Some((epd, opcodes)) = line.split_once(" c9 ") {
+            let result = opcodes.split(';').next().unwrap_or_default();
+            (epd, result)
+        } else if let Some((fen, rest)) = line.split_once(';') {
+            // Self-play data has the search score before the result
+            (fen, rest.rsplit(';').next().unwrap_or_default())
+        } else {
+            return Err(format!("No game result in {}", line.trim()));
+        };
+
+        Ok(TuningPosition {
+            result: parse_result(result)?,
+            board: parse_board(fen)?,
+        })
+    }
+}
+
+/// Reads one position per line, skipping empty lines and # comments
+pub fn load_positions(path: &str) -> Result<Vec<TuningPosition>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path, e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            TuningPosition::parse(line).map_err(|e| format!("{}:{}: {}", path, i + 1, e))
+        })
+        .collect()
+}
+
+/// Expected result from the point of view of white for a score in centipawns
+pub fn sigmoid(score: i32, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score as f64 / 400.0))
+}
+
+/// The tuned evaluation parameters in the order of EvalParams::fields_mut.
+/// Both kings are always on the board, so their piece values cancel out and
+/// are left out.
+fn tuned_values(params: &mut EvalParams) -> Vec<&mut i32> {
+    params
+        .fields_mut()
+        .into_iter()
+        .flat_map(|(name, values)| match name {
+            "mg_piece_values" | "eg_piece_values" => &mut values[..5],
+            _ => values,
+        })
+        .collect()
+}
+
+fn to_vector(params: &mut EvalParams) -> Vec<i32> {
+    tuned_values(params)
+        .into_iter()
+        .map(|value| *value)
+        .collect()
+}
+
+fn from_vector(params: &mut EvalParams, vector: &[i32]) {
+    for (value, new_value) in tuned_values(params).into_iter().zip(vector) {
+        *value = *new_value;
+    }
+}
+
+/// Texel tuning: fits the evaluation parameters to game results by
+/// minimizing the mean squared error between the results and the sigmoid of
+/// the quiescence search scores of the positions
+pub struct Tuner {
+    positions: Vec<TuningPosition>,
+    // One engine per thread, each evaluating a share of the positions. Their
+    // PeSTO evaluators are given the parameters of every error computed.
+    engines: Vec<Engine>,
+}
+
+impl Tuner {
+    pub fn new(positions: Vec<TuningPosition>, threads: usize) -> Self {
+        let mut engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        // The scores come from the tuned terms alone, without the endgame
+        // knowledge replacing or scaling them
+        let mut evaluator = PestoEvaluator::new();
+        evaluator.set_endgames(false);
+        engine.set_evaluator(Box::new(evaluator));
+        Tuner {
+            positions,
+            engines: vec![engine; threads.max(1)],
+        }
+    }
+
+    pub fn positions(&self) -> &[TuningPosition] {
+        &self.positions
+    }
+
+    /// Quiescence search scores of all positions from the point of view of white
+    pub fn scores(&mut self, params: &EvalParams) -> Vec<i32> {
+        let chunk_size = self.positions.len().div_ceil(self.engines.len()).max(1);
+        thread::scope(|s| {
+            let handles: Vec<_> = self
+                .engines
+                .iter_mut()
+                .zip(self.positions.chunks(chunk_size))
+                .map(|(engine, positions)| {
+                    engine.evaluator.set_params(params);
+                    s.spawn(move || {
+                        positions
+                            .iter()
+                            .map(|position| {
+                                engine.set_position(position.board.clone());
+                                let score = engine.quiesce_eval();
+                                match position.board.black_to_move {
+                                    true => -score,
+                                    false => score,
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("Tuning thread panicked"))
+                .collect()
+        })
+    }
+
+    /// Mean squared error of the predicted results for the given scores
+    pub fn error_of_scores(&self, scores: &[i32], k: f64) -> f64 {
+        let sum: f64 = self
+            .positions
+            .iter()
+            .zip(scores)
+            .map(|(position, score)| (position.result - sigmoid(*score, k)).powi(2))
+            .sum();
+        sum / self.positions.len().max(1) as f64
+    }
+
+    pub fn error(&mut self, params: &EvalParams, k: f64) -> f64 {
+        let scores = self.scores(params);
+        self.error_of_scores(&scores, k)
+    }
+
+    /// Scaling constant K of the sigmoid with the least error for the
+    /// given parameters, searched to three decimals
+    pub fn fit_k(&mut self, params: &EvalParams) -> f64 {
+        let scores = self.scores(params);
+        let mut best_k = 1.0;
+        let mut best_error = self.error_of_scores(&scores, best_k);
+
+        for step in [1.0, 0.1, 0.01, 0.001] {
+            let center = best_k;
+            for i in -10..=10 {
+                let k = center + i as f64 * step;
+                if k <= 0.0 {
+                    continue;
+                }
+                let error = self.error_of_scores(&scores, k);
+                if error < best_error {
+                    best_k = k;
+                    best_error = error;
+                }
+            }
+        }
+        best_k
+    }
+
+    /// Local search over the parameter vector: every parameter is moved up or
+    /// down by step while that lowers the error, for at most passes passes
+    /// over all parameters. on_pass is called with the pass number, the error
+    /// and the parameters after each pass. Returns the final error.
+    pub fn tune<F>(
+        &mut self,
+        params: &mut EvalParams,
+        k: f64,
+        step: i32,
+        passes: usize,
+        mut on_pass: F,
+    ) -> f64
+    where
+        F: FnMut(usize, f64, &EvalParams),
+    {
+        let mut vector = to_vector(params);
+        let mut best_error = self.error(params, k);
+
+        for pass in 1..=passes {
+            let mut improved = false;
+            for i in 0..vector.len() {
+                let original = vector[i];
+                for candidate in [original + step, original - step] {
+                    vector[i] = candidate;
+                    from_vector(params, &vector);
+                    let error = self.error(params, k);
+                    if error < best_error {
+                        best_error = error;
+                        improved = true;
+                        break;
+                    }
+                    vector[i] = original;
+                }
+            }
+            from_vector(params, &vector);
+            on_pass(pass, best_error, params);
+
+            if !improved {
+                break;
+            }
+        }
+        best_error
+    }
+}
@@ -0,0 +1,95 @@
+use ferris_chess_board::{Board, Color, Piece};
+use ferris_chess_engine::bitbase::kpk;
+use ferris_chess_engine::endgame::KNOWN_WIN;
+use ferris_chess_engine::Engine;
+
+/// Bitbase result for a KPK position given as FEN
+fn is_win(fen: &str) -> bool {
+    let board = Board::from_fen(fen);
+    let strong = match board.pieces_w.len() {
+        2 => Color::White,
+        _ => Color::Black,
+    };
+    let weak = match strong {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    };
+    let pawn = (0..64)
+        .find(|sq| board.data[*sq] == Some((strong, Piece::Pawn)))
+        .unwrap();
+    kpk().is_win(
+        strong,
+        board.black_to_move == (strong == Color::Black),
+        board.get_king_pos(strong).unwrap(),
+        board.get_king_pos(weak).unwrap(),
+        pawn,
+    )
+}
+
+#[test]
+fn king_on_a_key_square_wins_with_either_side_to_move() {
+    assert!(is_win("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"));
+    assert!(is_win("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"));
+    assert!(is_win("8/8/4k3/8/4K3/8/4P3/8 w - - 0 1"));
+    assert!(is_win("8/8/4k3/8/4K3/8/4P3/8 b - - 0 1"));
+}
+
+#[test]
+fn the_opposition_decides_with_the_king_in_front_of_the_pawn() {
+    assert!(!is_win("8/8/8/4k3/8/4K3/4P3/8 w - - 0 1"));
+    assert!(is_win("8/8/8/4k3/8/4K3/4P3/8 b - - 0 1"));
+}
+
+#[test]
+fn known_draws() {
+    // Black is stalemated, while with white to move Kd6 wins
+    assert!(!is_win("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"));
+    assert!(is_win("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1"));
+    // The defending king reaches the corner in front of a rook pawn
+    assert!(!is_win("k7/8/8/8/8/8/P7/7K w - - 0 1"));
+    assert!(!is_win("8/8/8/8/8/1k6/P7/K7 w - - 0 1"));
+    // The pawn is lost
+    assert!(!is_win("8/8/8/8/8/8/3kP3/7K b - - 0 1"));
+}
+
+#[test]
+fn the_square_of_the_pawn_depends_on_the_side_to_move() {
+    assert!(is_win("8/8/8/8/P7/5k2/8/4K3 w - - 0 1"));
+    assert!(!is_win("8/8/8/8/P7/5k2/8/4K3 b - - 0 1"));
+}
+
+#[test]
+fn mirrored_positions_have_the_same_result() {
+    for (fen, mirrored) in [
+        // Files mirrored
+        (
+            "8/8/8/4k3/8/4K3/4P3/8 b - - 0 1",
+            "8/8/8/3k4/8/3K4/3P4/8 b - - 0 1",
+        ),
+        (
+            "8/8/8/8/P7/5k2/8/4K3 w - - 0 1",
+            "8/8/8/8/7P/2k5/8/3K4 w - - 0 1",
+        ),
+        // Colors swapped
+        (
+            "8/8/8/4k3/8/4K3/4P3/8 b - - 0 1",
+            "8/4p3/4k3/8/4K3/8/8/8 w - - 0 1",
+        ),
+        (
+            "8/8/8/4k3/8/4K3/4P3/8 w - - 0 1",
+            "8/4p3/4k3/8/4K3/8/8/8 b - - 0 1",
+        ),
+    ] {
+        assert_eq!(is_win(fen), is_win(mirrored), "{}", fen);
+    }
+}
+
+#[test]
+fn static_eval_scores_kpk_as_won_or_drawn() {
+    assert!(Engine::new("8/8/8/4k3/8/4K3/4P3/8 b - - 0 1").static_eval() < -KNOWN_WIN);
+    assert_eq!(
+        Engine::new("8/8/8/4k3/8/4K3/4P3/8 w - - 0 1").static_eval(),
+        0
+    );
+    assert!(Engine::new("8/4p3/4k3/8/4K3/8/8/8 w - - 0 1").static_eval() < -KNOWN_WIN);
+}
@@ -0,0 +1,82 @@
+use ferris_chess_board::Board;
+use ferris_chess_engine::datagen::{generate, DataPosition, DatagenOptions};
+use ferris_chess_engine::tuner::TuningPosition;
+use ferris_chess_engine::Engine;
+
+fn play(options: &DatagenOptions) -> Vec<Vec<DataPosition>> {
+    let engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let mut games = vec![];
+    generate(&engine, options, |game| games.push(game)).unwrap();
+    games
+}
+
+fn short_games() -> DatagenOptions {
+    DatagenOptions {
+        games: 2,
+        nodes: 300,
+        threads: 2,
+        random_plies: 4,
+        max_plies: 30,
+        ..DatagenOptions::default()
+    }
+}
+
+#[test]
+fn recorded_positions_are_quiet_and_share_the_game_result() {
+    let games = play(&short_games());
+    assert_eq!(games.len(), 2);
+
+    for game in games {
+        assert!(!game.is_empty());
+        let result = game[0].result;
+        assert!([0.0, 0.5, 1.0].contains(&result));
+
+        for position in game {
+            assert_eq!(position.result, result);
+            assert!(!Board::from_fen(&position.fen).is_player_mated());
+
+            let parsed = TuningPosition::parse(&position.to_line()).unwrap();
+            assert_eq!(parsed.result, result);
+        }
+    }
+}
+
+#[test]
+fn decided_games_are_adjudicated() {
+    let options = DatagenOptions {
+        games: 1,
+        book: vec!["4k3/8/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1".to_string()],
+        random_plies: 0,
+        win_plies: 2,
+        ..short_games()
+    };
+    let game = &play(&options)[0];
+    assert!(!game.is_empty() && game.len() <= 2);
+    assert!(game.iter().all(|p| p.result == 1.0 && p.score >= 1000));
+}
+
+#[test]
+fn level_games_are_adjudicated_as_draws() {
+    let options = DatagenOptions {
+        games: 1,
+        book: vec!["4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1".to_string()],
+        random_plies: 0,
+        draw_score: 1000,
+        draw_plies: 3,
+        draw_min_ply: 0,
+        ..short_games()
+    };
+    let game = &play(&options)[0];
+    assert!(!game.is_empty() && game.len() <= 3);
+    assert!(game.iter().all(|p| p.result == 0.5));
+}
+
+#[test]
+fn invalid_books_are_reported() {
+    let options = DatagenOptions {
+        book: vec!["not a position".to_string()],
+        ..short_games()
+    };
+    let engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    assert!(generate(&engine, &options, |_| ()).is_err());
+}
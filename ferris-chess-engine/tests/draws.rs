@@ -0,0 +1,49 @@
+use ferris_chess_board::{Board, MoveData};
+use ferris_chess_engine::{Engine, GoCommand, Score};
+
+#[test]
+fn losing_side_repeats_position_from_game_history() {
+    let fen = "3q3k/6pp/8/8/8/8/6PP/6NK w - - 0 1";
+    let mut engine = Engine::new(fen);
+    engine.set_position(Board::from_fen(fen));
+
+    for uci_move in ["g1f3", "h8g8", "f3g1", "g8h8"] {
+        let m = MoveData::from_uci(&uci_move.to_string(), &engine.board);
+        engine.make_game_move(&m);
+    }
+
+    // Ng1-f3 repeats the position after White's first move, which is a draw
+    let go_cmd = GoCommand::new("go wtime 2000 btime 2000 movestogo 1");
+    let result = engine
+        .iter_deepening(&go_cmd)
+        .unwrap()
+        .to_uci_move(&engine.board);
+    assert_eq!(result, "g1f3".to_string());
+}
+
+#[test]
+fn losing_side_reaches_fifty_move_draw() {
+    let mut engine = Engine::new("3q3k/6pp/8/8/8/n7/1P6/7K w - - 99 80");
+
+    // Any quiet king move completes 50 moves without capture or pawn move,
+    // which is better than winning the knight on a3
+    let go_cmd = GoCommand::new("go wtime 2000 btime 2000 movestogo 1");
+    let result = engine
+        .iter_deepening(&go_cmd)
+        .unwrap()
+        .to_uci_move(&engine.board);
+    assert!(result.starts_with("h1"));
+}
+
+#[test]
+fn mate_on_the_hundredth_half_move_is_not_a_draw() {
+    let mut engine = Engine::new("7k/8/6K1/8/8/8/8/R7 w - - 99 80");
+
+    let go_cmd = GoCommand::new("go depth 3");
+    let result = engine
+        .iter_deepening(&go_cmd)
+        .unwrap()
+        .to_uci_move(&engine.board);
+    assert_eq!(result, "a1a8");
+    assert_eq!(engine.search_info().unwrap().score, Score::Mate(1));
+}
@@ -0,0 +1,128 @@
+use ferris_chess_board::{Board, Color};
+use ferris_chess_engine::endgame::{Endgame, EndgameKind, MaterialSignature, KNOWN_WIN};
+use ferris_chess_engine::evaluator::Evaluator;
+use ferris_chess_engine::pesto::PestoEvaluator;
+use ferris_chess_engine::Engine;
+
+fn probe(fen: &str) -> Option<Endgame> {
+    Endgame::probe(&Board::from_fen(fen))
+}
+
+fn static_eval(fen: &str) -> i32 {
+    Engine::new(fen).static_eval()
+}
+
+/// PeSTO evaluation without the endgame knowledge
+fn pesto_eval(fen: &str) -> i32 {
+    let mut pesto = PestoEvaluator::new();
+    pesto.set_endgames(false);
+    pesto.evaluate(&Board::from_fen(fen))
+}
+
+#[test]
+fn material_signature_names_the_pieces() {
+    let board = Board::from_fen("8/8/4k3/8/8/2b5/1n6/4K2R w - - 0 1");
+    let signature = MaterialSignature::of(&board);
+    assert_eq!(signature.to_string(), "KRKBN");
+    assert_eq!(signature.flipped().to_string(), "KBNKR");
+}
+
+#[test]
+fn endgames_are_recognized_for_either_color() {
+    let kbnk = Endgame {
+        kind: EndgameKind::Kbnk,
+        strong: Color::Black,
+    };
+    assert_eq!(probe("8/8/4k3/8/8/2b5/1n6/4K3 w - - 0 1"), Some(kbnk));
+    assert_eq!(
+        probe("8/8/4k3/8/8/8/8/4K2R b - - 0 1").map(|e| (e.kind, e.strong)),
+        Some((EndgameKind::Kxk, Color::White))
+    );
+    assert_eq!(
+        probe("8/8/4k3/8/8/8/P7/4K3 w - - 0 1").map(|e| e.kind),
+        Some(EndgameKind::Kpk)
+    );
+    assert_eq!(
+        probe("r7/8/4k3/8/8/8/8/4K2R w - - 0 1").map(|e| e.kind),
+        Some(EndgameKind::Krkr)
+    );
+    // Bishops on squares of the same color are not opposite bishops
+    assert_eq!(probe("8/4kb2/8/8/8/8/4B3/4K3 w - - 0 1"), None);
+    assert_eq!(
+        probe("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+        None
+    );
+}
+
+#[test]
+fn kxk_drives_the_lone_king_to_the_edge() {
+    let centre = static_eval("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+    let edge = static_eval("3k4/8/8/8/8/8/8/R3K3 w - - 0 1");
+    assert!(centre > KNOWN_WIN && edge > centre);
+    // Scores of the side to move
+    assert!(static_eval("3k4/8/8/8/8/8/8/R3K3 b - - 0 1") < -KNOWN_WIN);
+    // Two knights can't force mate
+    assert_eq!(static_eval("3k4/8/8/8/8/8/8/1NN1K3 w - - 0 1"), 0);
+    assert!(static_eval("3k4/8/8/8/8/8/P7/1NN1K3 w - - 0 1") > 0);
+    // Nor can two bishops on squares of the same color
+    assert_eq!(static_eval("3k4/8/8/8/8/4B3/8/2B1K3 w - - 0 1"), 0);
+    assert!(static_eval("3k4/8/8/8/8/8/8/2B1KB2 w - - 0 1") > KNOWN_WIN);
+}
+
+#[test]
+fn kbnk_drives_the_king_to_a_corner_of_the_bishop_color() {
+    // The bishop on c1 controls the dark squares, a1 and h8
+    let right_corner = static_eval("7k/8/5K2/8/8/8/8/2B1N3 w - - 0 1");
+    let wrong_corner = static_eval("k7/8/2K5/8/8/8/8/2B1N3 w - - 0 1");
+    assert!(wrong_corner > KNOWN_WIN && right_corner > wrong_corner);
+}
+
+#[test]
+fn kpk_pawn_outside_the_square_of_the_king_wins() {
+    assert!(static_eval("8/8/8/8/P7/5k2/8/4K3 w - - 0 1") > KNOWN_WIN);
+    // With black to move the king catches the pawn
+    assert!(static_eval("8/8/8/8/P7/5k2/8/4K3 b - - 0 1") > -KNOWN_WIN);
+    assert!(static_eval("8/8/8/2k5/P7/8/8/4K3 w - - 0 1") < KNOWN_WIN);
+}
+
+#[test]
+fn drawish_endgames_are_scaled_down() {
+    let krkr = "r7/4k3/8/8/8/8/8/4K2R w - - 0 1";
+    assert!(static_eval(krkr).abs() <= pesto_eval(krkr).abs() / 8);
+
+    let opposite_bishops = "8/4kb2/4p3/8/8/3P4/2PB4/4K3 w - - 0 1";
+    assert_ne!(pesto_eval(opposite_bishops), 0);
+    assert!(static_eval(opposite_bishops).abs() < pesto_eval(opposite_bishops).abs());
+}
+
+#[test]
+fn kbpk_with_the_wrong_rook_pawn_is_a_draw() {
+    // The dark squared bishop doesn't control a8 and the king holds the corner
+    assert_eq!(static_eval("k7/8/P7/8/8/8/3B4/4K3 w - - 0 1"), 0);
+    assert!(static_eval("k7/8/P7/8/8/8/4B3/4K3 w - - 0 1") > 0);
+    assert!(static_eval("8/8/P7/8/8/5k2/3B4/4K3 w - - 0 1") > 0);
+}
+
+#[test]
+fn eval_trace_names_the_endgame() {
+    let fen = "r7/4k3/8/8/8/8/8/4K2R b - - 0 1";
+    let mut engine = Engine::new(fen);
+    let board = engine.board.clone();
+    let trace = engine.eval_trace(&board);
+    assert_eq!(trace.endgame, Some("KRKR"));
+    assert_eq!(trace.score, static_eval(fen));
+    assert!(trace.to_string().contains("Endgame: KRKR"));
+}
+
+#[test]
+fn endgame_knowledge_switches_off_on_the_pesto_evaluator() {
+    let board = Board::from_fen("r7/4k3/8/8/8/8/8/4K2R b - - 0 1");
+    let mut pesto = PestoEvaluator::new();
+    pesto.set_endgames(false);
+    assert_eq!(pesto.trace(&board).endgame, None);
+    assert_eq!(pesto.trace(&board).score, pesto.evaluate(&board));
+    assert_ne!(
+        pesto.evaluate(&board),
+        static_eval("r7/4k3/8/8/8/8/8/4K2R b - - 0 1")
+    );
+}
@@ -0,0 +1,58 @@
+use ferris_chess_board::Board;
+use ferris_chess_engine::eval_params::EvalParams;
+use ferris_chess_engine::evaluator::Evaluator;
+use ferris_chess_engine::pesto::PestoEvaluator;
+use std::env;
+
+#[test]
+fn params_survive_a_text_round_trip() {
+    let mut params = EvalParams::default();
+    params.mg_piece_values[1] = 350;
+    params.eg_knight_table[63] = -77;
+    params.tempo = [20, 0];
+
+    let parsed = EvalParams::from_text(&params.to_text()).unwrap();
+    assert_eq!(parsed, params);
+}
+
+#[test]
+fn missing_params_keep_their_defaults() {
+    let text = "# Only the bishop pair changes\nbishop_pair = [40, 60]\n";
+    let params = EvalParams::from_text(text).unwrap();
+    assert_eq!(params.bishop_pair, [40, 60]);
+    assert_eq!(params.mg_pawn_table, EvalParams::default().mg_pawn_table);
+    assert_eq!(EvalParams::from_text("").unwrap(), EvalParams::default());
+}
+
+#[test]
+fn tables_may_span_several_lines() {
+    let rows = ["1, 2, 3, 4, 5, 6, 7, 8"; 8].join(",\n    ");
+    let text = format!("mg_rook_table = [\n    {},\n]\n", rows);
+    let params = EvalParams::from_text(&text).unwrap();
+    assert_eq!(params.mg_rook_table[8..16], [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn invalid_params_are_rejected() {
+    assert!(EvalParams::from_text("no_such_param = [1, 2]").is_err());
+    assert!(EvalParams::from_text("tempo = [1, 2, 3]").is_err());
+    assert!(EvalParams::from_text("tempo = [1, x]").is_err());
+    assert!(EvalParams::from_text("tempo = [1,").is_err());
+    assert!(EvalParams::load("/nonexistent/eval.toml").is_err());
+}
+
+#[test]
+fn saved_params_load_into_the_evaluator() {
+    let mut params = EvalParams::default();
+    params.mg_piece_values[4] += 300;
+    params.eg_piece_values[4] += 300;
+    let path = env::temp_dir().join("ferris_chess_eval_params_test.toml");
+    let path = path.to_str().unwrap();
+    params.save(path).unwrap();
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+    let mut tuned = PestoEvaluator::with_params(EvalParams::load(path).unwrap());
+    let mut default = PestoEvaluator::new();
+    assert_eq!(tuned.evaluate(&board), default.evaluate(&board) + 300);
+    assert_eq!(tuned.params(), &params);
+}
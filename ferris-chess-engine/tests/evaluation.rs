@@ -1,8 +1,328 @@
-use ferris_chess_engine::Engine;
+use ferris_chess_board::{Board, Square};
+use ferris_chess_engine::eval_params::EvalParams;
+use ferris_chess_engine::evaluator::{Evaluator, MaterialEvaluator};
+use ferris_chess_engine::king_safety::evaluate_king_safety;
+use ferris_chess_engine::mobility::{evaluate_mobility, Mobility};
+use ferris_chess_engine::pawn_structure::{evaluate_pawns, passed_pawn_path, PawnEntry, PawnTable};
+use ferris_chess_engine::pesto::PestoEvaluator;
+use ferris_chess_engine::positional::{evaluate_positional, PositionalTerms};
+use ferris_chess_engine::{Engine, GoCommand};
 
 #[test]
 fn evalation_for_startpos_within_50_centipawns() {
-    let engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let mut engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
     let w_eval = engine.static_eval();
     assert!(w_eval >= -50 && w_eval <= 50);
 }
+
+fn pawns(fen: &str) -> PawnEntry {
+    evaluate_pawns(&Board::from_fen(fen), &EvalParams::default())
+}
+
+fn mobility(fen: &str) -> Mobility {
+    evaluate_mobility(&Board::from_fen(fen), &EvalParams::default())
+}
+
+#[test]
+fn symmetric_pawns_score_zero() {
+    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let pawns = evaluate_pawns(&board, &EvalParams::default());
+    assert_eq!((pawns.mg, pawns.eg, pawns.passed), (0, 0, 0));
+}
+
+#[test]
+fn advanced_passed_pawn_scores_higher() {
+    let advanced = pawns("4k3/8/3P4/8/8/8/8/4K3 w - - 0 1");
+    let behind = pawns("4k3/8/8/8/8/3P4/8/4K3 w - - 0 1");
+    assert_eq!(advanced.passed, 1 << Square::D6);
+    assert!(advanced.mg > behind.mg && advanced.eg > behind.eg);
+
+    let black = pawns("4k3/8/8/8/8/3p4/8/4K3 w - - 0 1");
+    assert_eq!(black.eg, -advanced.eg);
+}
+
+#[test]
+fn doubled_isolated_pawns_score_lower_than_connected() {
+    let doubled = pawns("4k3/2p1p3/8/8/8/2P5/2P5/4K3 w - - 0 1");
+    let connected = pawns("4k3/2p1p3/8/8/8/3P4/2P5/4K3 w - - 0 1");
+    assert!(doubled.mg < connected.mg);
+    assert!(doubled.eg < connected.eg);
+}
+
+#[test]
+fn backward_pawn_is_penalized() {
+    // The d3 pawn can't be defended and black's e5 pawn controls d4
+    let backward = pawns("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1");
+    let defended = pawns("4k3/8/8/4p3/8/2PP4/8/4K3 w - - 0 1");
+    assert!(backward.mg < defended.mg);
+}
+
+#[test]
+fn pawn_table_returns_the_stored_score() {
+    let board = Board::from_fen("4k3/pp3p2/8/3P4/8/8/P4PP1/4K3 w - - 0 1");
+    let mut table = PawnTable::new();
+    assert_eq!(
+        table.probe(&board, &EvalParams::default()),
+        evaluate_pawns(&board, &EvalParams::default())
+    );
+    assert_eq!(
+        table.probe(&board, &EvalParams::default()),
+        evaluate_pawns(&board, &EvalParams::default())
+    );
+}
+
+#[test]
+fn only_passed_pawns_with_a_free_path_get_the_path_bonus() {
+    let params = EvalParams::default();
+    let free = Board::from_fen("4k3/8/8/3P4/8/8/8/4K2N w - - 0 1");
+    let (mg, eg) = passed_pawn_path(&free, evaluate_pawns(&free, &params).passed, &params);
+    assert!(mg > 0 && eg > 0);
+
+    let blocked = Board::from_fen("4k3/8/3n4/3P4/8/8/8/4K3 w - - 0 1");
+    assert_eq!(
+        passed_pawn_path(&blocked, evaluate_pawns(&blocked, &params).passed, &params),
+        (0, 0)
+    );
+}
+
+#[test]
+fn mobility_is_symmetric_at_startpos() {
+    let params = EvalParams::default();
+    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let mobility = evaluate_mobility(&board, &params);
+    assert_eq!((mobility.mg, mobility.eg), (0, 0));
+    assert_eq!(
+        evaluate_king_safety(&board, &mobility.king_attacks, &params),
+        (0, 0)
+    );
+}
+
+#[test]
+fn squares_attacked_by_enemy_pawns_are_not_safe() {
+    // Both knights reach the same squares, but black pawns cover those of the d4 knight
+    let free = mobility("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+    let covered = mobility("4k3/3p4/p5p1/8/3N4/8/8/4K3 w - - 0 1");
+    assert!(free.mg > covered.mg && free.eg > covered.eg);
+}
+
+#[test]
+fn pieces_attacking_the_king_zone_are_counted() {
+    let params = EvalParams::default();
+    let board = Board::from_fen("6k1/5ppp/8/8/8/3B4/6Q1/4K3 w - - 0 1");
+    let mobility = evaluate_mobility(&board, &params);
+    assert_eq!(mobility.king_attacks[1].attackers, 2);
+    assert_eq!(mobility.king_attacks[0].attackers, 0);
+
+    let (mg, _) = evaluate_king_safety(&board, &mobility.king_attacks, &params);
+    let safe = Board::from_fen("6k1/5ppp/8/8/8/8/3B4/Q3K3 w - - 0 1");
+    let safe_mobility = evaluate_mobility(&safe, &params);
+    let (safe_mg, _) = evaluate_king_safety(&safe, &safe_mobility.king_attacks, &params);
+    assert!(mg > safe_mg);
+}
+
+#[test]
+fn pawn_shield_beats_open_king() {
+    let params = EvalParams::default();
+    let sheltered = Board::from_fen("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+    let open = Board::from_fen("6k1/8/8/8/8/5PPP/8/6K1 w - - 0 1");
+    let no_attacks = Default::default();
+    let (sheltered_mg, _) = evaluate_king_safety(&sheltered, &no_attacks, &params);
+    let (open_mg, _) = evaluate_king_safety(&open, &no_attacks, &params);
+    assert!(sheltered_mg > open_mg);
+
+    let stormed = Board::from_fen("6k1/8/8/8/6p1/8/5PPP/6K1 w - - 0 1");
+    let (stormed_mg, _) = evaluate_king_safety(&stormed, &no_attacks, &params);
+    assert!(stormed_mg < sheltered_mg);
+}
+
+fn positional_mg(fen: &str, terms: PositionalTerms) -> i32 {
+    evaluate_positional(&Board::from_fen(fen), &terms, &EvalParams::default()).0
+}
+
+#[test]
+fn positional_terms_are_off_when_switched_off() {
+    let fen = "r3k3/1P6/8/3N4/8/8/8/R1B1KB2 b - - 0 1";
+    assert_eq!(positional_mg(fen, PositionalTerms::none()), 0);
+    assert!(positional_mg(fen, PositionalTerms::default()) != 0);
+}
+
+#[test]
+fn bishop_pair_bonus() {
+    let terms = PositionalTerms {
+        bishop_pair: true,
+        ..PositionalTerms::none()
+    };
+    assert!(positional_mg("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", terms) > 0);
+    assert_eq!(positional_mg("4k3/8/8/8/8/8/8/2N1KB2 w - - 0 1", terms), 0);
+}
+
+#[test]
+fn rook_on_open_file_beats_semi_open_file() {
+    let terms = PositionalTerms {
+        open_files: true,
+        ..PositionalTerms::none()
+    };
+    let open = positional_mg("4k3/p7/8/8/8/8/P7/3RK3 w - - 0 1", terms);
+    let semi_open = positional_mg("4k3/3p4/8/8/8/8/P7/3RK3 w - - 0 1", terms);
+    let closed = positional_mg("4k3/3p4/8/8/8/8/3P4/3RK3 w - - 0 1", terms);
+    assert!(open > semi_open && semi_open > closed);
+    assert_eq!(closed, 0);
+}
+
+#[test]
+fn rook_on_seventh_rank_with_king_on_eighth() {
+    let terms = PositionalTerms {
+        rook_on_seventh: true,
+        ..PositionalTerms::none()
+    };
+    assert!(positional_mg("4k3/R7/8/8/8/8/8/4K3 w - - 0 1", terms) > 0);
+    assert_eq!(positional_mg("8/R7/4k3/8/8/8/8/4K3 w - - 0 1", terms), 0);
+}
+
+#[test]
+fn knight_outpost_needs_pawn_support_and_safety() {
+    let terms = PositionalTerms {
+        outposts: true,
+        ..PositionalTerms::none()
+    };
+    assert!(positional_mg("4k3/8/8/3N4/2P5/8/8/4K3 w - - 0 1", terms) > 0);
+    // Unsupported, or can be chased away by the c7 pawn
+    assert_eq!(positional_mg("4k3/8/8/3N4/8/8/8/4K3 w - - 0 1", terms), 0);
+    assert_eq!(
+        positional_mg("4k3/2p5/8/3N4/2P5/8/8/4K3 w - - 0 1", terms),
+        0
+    );
+}
+
+#[test]
+fn threats_and_hanging_pieces_are_penalized() {
+    let terms = PositionalTerms {
+        threats: true,
+        ..PositionalTerms::none()
+    };
+    // The black pawn attacks the knight
+    assert!(positional_mg("4k3/8/8/2p5/3N4/8/8/4K3 w - - 0 1", terms) < 0);
+    // The rooks attack each other, only the black one is undefended
+    assert!(positional_mg("4k3/8/8/3r4/8/8/8/3RK3 w - - 0 1", terms) > 0);
+    assert_eq!(
+        positional_mg("4k3/8/4p3/3r4/8/8/8/3RK3 w - - 0 1", terms),
+        0
+    );
+}
+
+#[test]
+fn tempo_favors_the_side_to_move() {
+    let terms = PositionalTerms {
+        tempo: true,
+        ..PositionalTerms::none()
+    };
+    assert!(positional_mg("4k3/8/8/8/8/8/8/4K3 w - - 0 1", terms) > 0);
+    assert!(positional_mg("4k3/8/8/8/8/8/8/4K3 b - - 0 1", terms) < 0);
+}
+
+#[test]
+fn engine_evaluates_with_pesto_by_default() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let mut engine = Engine::new(fen);
+    let mut pesto = PestoEvaluator::new();
+    assert_eq!(engine.static_eval(), pesto.evaluate(&Board::from_fen(fen)));
+}
+
+#[test]
+fn material_evaluator_counts_piece_values_for_the_side_to_move() {
+    let mut engine = Engine::new("4k3/8/8/8/8/8/3Q4/R3K3 b - - 0 1");
+    engine.set_evaluator(Box::new(MaterialEvaluator));
+    assert_eq!(engine.static_eval(), -1400);
+
+    // The search runs with any evaluator
+    let m = engine.iter_deepening(&GoCommand::new("go depth 3")).unwrap();
+    assert!(engine.board.get_pseudo_legal_moves().contains(&m));
+}
+
+#[test]
+fn positional_terms_switch_on_the_pesto_evaluator() {
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+    let mut all_terms = PestoEvaluator::new();
+    let mut no_terms = PestoEvaluator::new();
+    no_terms.set_positional_terms(PositionalTerms::none());
+    assert!(all_terms.evaluate(&board) > no_terms.evaluate(&board));
+}
+
+#[test]
+fn pesto_with_new_params_evaluates_like_a_new_evaluator() {
+    let boards = [
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "4k3/p1p3p1/8/8/8/8/P2P2PP/4K3 w - - 0 1",
+    ]
+    .map(Board::from_fen);
+
+    let mut params = EvalParams::default();
+    params.mg_piece_values[1] += 50;
+    params.isolated = [-40, -60];
+
+    // The pawn table is filled with the scores of the old parameters first
+    let mut reused = PestoEvaluator::new();
+    for board in &boards {
+        reused.evaluate(board);
+    }
+    reused.set_params(&params);
+    let mut new = PestoEvaluator::with_params(params.clone());
+    for board in &boards {
+        assert_eq!(reused.evaluate(board), new.evaluate(board));
+    }
+    assert_eq!(reused.params(), &params);
+}
+
+#[test]
+fn eval_trace_adds_up_to_the_evaluation() {
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "r4rk1/1pp2ppp/p1n5/3Pp3/2B5/5N2/PP3PPP/R4RK1 b - - 0 15",
+        "8/5k2/8/2P5/8/8/5K2/8 b - - 0 1",
+    ] {
+        let board = Board::from_fen(fen);
+        // The terms add up to the evaluation before any endgame knowledge
+        let mut pesto = PestoEvaluator::new();
+        pesto.set_endgames(false);
+        let trace = pesto.trace(&board);
+        assert_eq!(trace.score, pesto.evaluate(&board));
+        assert_eq!(
+            trace.white_score,
+            if board.black_to_move {
+                -trace.score
+            } else {
+                trace.score
+            }
+        );
+
+        let (mg_phase, eg_phase) = trace.phase.unwrap();
+        let (mg, eg) = trace.total();
+        assert_eq!(trace.white_score, (mg * mg_phase + eg * eg_phase) / 24);
+    }
+}
+
+#[test]
+fn eval_trace_lists_only_the_enabled_positional_terms() {
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+    let mut pesto = PestoEvaluator::new();
+    let names = |trace: ferris_chess_engine::eval_trace::EvalTrace| {
+        trace.terms.iter().map(|t| t.name).collect::<Vec<_>>()
+    };
+    assert!(names(pesto.trace(&board)).contains(&"Bishop pair"));
+
+    pesto.set_positional_terms(PositionalTerms::none());
+    assert!(!names(pesto.trace(&board)).contains(&"Bishop pair"));
+    assert_eq!(pesto.trace(&board).score, pesto.evaluate(&board));
+}
+
+#[test]
+fn evaluators_without_terms_trace_the_score() {
+    let mut engine = Engine::new("4k3/8/8/8/8/8/3Q4/R3K3 b - - 0 1");
+    engine.set_evaluator(Box::new(MaterialEvaluator));
+    let board = engine.board.clone();
+    let trace = engine.eval_trace(&board);
+    assert!(trace.terms.is_empty() && trace.phase.is_none());
+    assert_eq!((trace.score, trace.white_score), (-1400, 1400));
+    assert!(trace.to_string().contains("Evaluation: 1400 (white side)"));
+}
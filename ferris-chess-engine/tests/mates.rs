@@ -1,5 +1,5 @@
 use ferris_chess_board::{MoveData, MoveType, Piece, Square};
-use ferris_chess_engine::{Engine, GoCommand};
+use ferris_chess_engine::{search_params::SearchParams, Engine, GoCommand, Score, MATED_VALUE};
 
 #[test]
 fn mate_in_1_for_black_using_rooks_() {
@@ -7,7 +7,7 @@ fn mate_in_1_for_black_using_rooks_() {
     let go_input = "go wtime 2000 btime 2000 movestogo 1".to_string();
 
     let go_cmd = GoCommand::new(&go_input);
-    let result = engine.iter_deepening(&go_cmd).to_uci_move(&engine.board);
+    let result = engine.iter_deepening(&go_cmd).unwrap().to_uci_move(&engine.board);
     assert_eq!(result, "b6b1".to_string());
 }
 
@@ -17,7 +17,7 @@ fn mate_in_1_for_white_using_bishop_queen() {
     let go_input = "go wtime 2000 btime 2000 movestogo 1".to_string();
 
     let go_cmd = GoCommand::new(&go_input);
-    let result = engine.iter_deepening(&go_cmd).to_uci_move(&engine.board);
+    let result = engine.iter_deepening(&go_cmd).unwrap().to_uci_move(&engine.board);
     assert_eq!(result, "g7c7".to_string());
 }
 
@@ -27,7 +27,7 @@ fn mate_in_1_for_black_using_rooks_with_capture() {
     let go_input = "go wtime 2000 btime 2000 movestogo 1".to_string();
 
     let go_cmd = GoCommand::new(&go_input);
-    let result = engine.iter_deepening(&go_cmd).to_uci_move(&engine.board);
+    let result = engine.iter_deepening(&go_cmd).unwrap().to_uci_move(&engine.board);
     assert_eq!(result, "c6c1".to_string());
 }
 
@@ -37,7 +37,7 @@ fn mate_in_1_for_white_using_queen_with_capture() {
     let go_input = "go wtime 2000 btime 2000 movestogo 1".to_string();
 
     let go_cmd = GoCommand::new(&go_input);
-    let result = engine.iter_deepening(&go_cmd).to_uci_move(&engine.board);
+    let result = engine.iter_deepening(&go_cmd).unwrap().to_uci_move(&engine.board);
     assert_eq!(result, "g1a7".to_string());
 }
 
@@ -47,18 +47,38 @@ fn mate_in_2_for_white_using_queen() {
     let go_input = "go wtime 2000 btime 2000 movestogo 1".to_string();
     let go_cmd = GoCommand::new(&go_input);
 
-    let w_move_1 = engine.iter_deepening(&go_cmd);
+    let w_move_1 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(w_move_1.to_uci_move(&engine.board), "f2f7".to_string());
     engine.board.make_move(&w_move_1);
 
-    let b_move_1 = engine.iter_deepening(&go_cmd);
+    let b_move_1 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(b_move_1.to_uci_move(&engine.board), "g8h8".to_string());
     engine.board.make_move(&b_move_1);
 
-    let w_move_2 = engine.iter_deepening(&go_cmd);
+    let w_move_2 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(w_move_2.to_uci_move(&engine.board), "f7g7".to_string());
 }
 
+#[test]
+fn check_extensions_find_mate_in_3_at_a_lower_depth() {
+    let engine = Engine::new("4r3/pk3pb1/1pNp2p1/3P1q2/2Qp1B1P/8/PPP2PP1/2K5 w - - 6 24");
+    let go_cmd = GoCommand::new("go depth 4");
+
+    let mut extended = engine.clone();
+    let m = extended.iter_deepening(&go_cmd).unwrap();
+    assert_eq!(m.to_uci_move(&extended.board), "c6a5".to_string());
+    assert_eq!(extended.search_info().unwrap().score, Score::Mate(3));
+
+    let mut not_extended = engine.clone();
+    not_extended.set_hash_size(1);
+    not_extended.set_search_params(SearchParams {
+        max_extensions: 0,
+        ..SearchParams::default()
+    });
+    not_extended.iter_deepening(&go_cmd);
+    assert_ne!(not_extended.search_info().unwrap().score, Score::Mate(3));
+}
+
 #[test]
 fn mate_in_2_for_black_knight_rook() {
     let mut engine = Engine::new("3k4/8/3r4/b7/5n2/8/PPP5/2K5 b - - 0 1");
@@ -66,15 +86,15 @@ fn mate_in_2_for_black_knight_rook() {
 
     let go_cmd = GoCommand::new(&go_input);
 
-    let b_move_1 = engine.iter_deepening(&go_cmd);
+    let b_move_1 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(b_move_1.to_uci_move(&engine.board), "f4e2".to_string());
     engine.board.make_move(&b_move_1);
 
-    let w_move_1 = engine.iter_deepening(&go_cmd);
+    let w_move_1 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(w_move_1.to_uci_move(&engine.board), "c1b1".to_string());
     engine.board.make_move(&w_move_1);
 
-    let b_move_2 = engine.iter_deepening(&go_cmd);
+    let b_move_2 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(b_move_2.to_uci_move(&engine.board), "d6d1".to_string());
 }
 
@@ -85,7 +105,7 @@ fn mate_in_3_for_black_with_rooks() {
 
     let go_cmd = GoCommand::new(&go_input);
 
-    let b_move_1 = engine.iter_deepening(&go_cmd);
+    let b_move_1 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(b_move_1.to_uci_move(&engine.board), "c6c2".to_string());
     engine.board.make_move(&b_move_1);
 
@@ -100,14 +120,14 @@ fn mate_in_3_for_black_with_rooks() {
     };
     engine.board.make_move(&w_move_1);
 
-    let b_move_2 = engine.iter_deepening(&go_cmd);
+    let b_move_2 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(b_move_2.to_uci_move(&engine.board), "b7b3".to_string());
     engine.board.make_move(&b_move_2);
 
-    let w_move_2 = engine.iter_deepening(&go_cmd);
+    let w_move_2 = engine.iter_deepening(&go_cmd).unwrap();
     engine.board.make_move(&w_move_2);
 
-    let b_move_3 = engine.iter_deepening(&go_cmd);
+    let b_move_3 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(b_move_3.to_uci_move(&engine.board), "b3b1".to_string());
 }
 
@@ -120,7 +140,7 @@ fn mate_in_3_for_white_with_knight_bishop_queen() {
 
     let go_cmd = GoCommand::new(&go_input);
 
-    let w_move_1 = engine.iter_deepening(&go_cmd);
+    let w_move_1 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(w_move_1.to_uci_move(&engine.board), "c6a5".to_string());
     engine.board.make_move(&w_move_1);
 
@@ -135,7 +155,7 @@ fn mate_in_3_for_white_with_knight_bishop_queen() {
     };
     engine.board.make_move(&b_move_1);
 
-    let w_move_2 = engine.iter_deepening(&go_cmd);
+    let w_move_2 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(w_move_2.to_uci_move(&engine.board), "f4d6".to_string());
     engine.board.make_move(&w_move_2);
 
@@ -148,6 +168,25 @@ fn mate_in_3_for_white_with_knight_bishop_queen() {
     };
     engine.board.make_move(&b_move_2);
 
-    let w_move_3 = engine.iter_deepening(&go_cmd);
+    let w_move_3 = engine.iter_deepening(&go_cmd).unwrap();
     assert_eq!(w_move_3.to_uci_move(&engine.board), "c4c6".to_string());
 }
+
+#[test]
+fn quiescence_search_sees_mate_at_depth_1() {
+    // Without evasions in check the mated side would stand pat
+    let mut engine = Engine::new("2k5/6Q1/8/8/8/6B1/8/3K4 w - - 0 1");
+    let go_cmd = GoCommand::new("go depth 1");
+    let result = engine.iter_deepening(&go_cmd).unwrap().to_uci_move(&engine.board);
+    assert_eq!(result, "g7c7".to_string());
+    assert_eq!(engine.search_info().unwrap().score, Score::Mate(1));
+}
+
+#[test]
+fn mate_scores_convert_to_full_moves_beyond_50_plies() {
+    // Side to move mates on ply 59 of the search, or gets mated on ply 60
+    assert_eq!(Score::from_value(-MATED_VALUE - 59), Score::Mate(30));
+    assert_eq!(Score::from_value(MATED_VALUE + 60), Score::Mate(-30));
+    assert_eq!(Score::from_value(-MATED_VALUE - 1), Score::Mate(1));
+    assert_eq!(Score::from_value(250), Score::CentiPawns(250));
+}
@@ -0,0 +1,128 @@
+use ferris_chess_board::{Board, MoveData};
+use ferris_chess_engine::evaluator::{load_evaluator, Evaluator};
+use ferris_chess_engine::nnue::{Network, NnueEvaluator, FEATURES};
+use ferris_chess_engine::{Engine, GoCommand};
+use std::env;
+
+// Not a multiple of the SIMD width, so the scalar remainder is covered too
+const HIDDEN: usize = 40;
+
+/// Small network with pseudo random weights
+fn network() -> Network {
+    let mut state: u32 = 12345;
+    let mut next = |range: i32| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        ((state >> 16) as i32 % (2 * range + 1) - range) as i16
+    };
+    let feature_weights = (0..FEATURES * HIDDEN).map(|_| next(40)).collect();
+    let feature_biases = (0..HIDDEN).map(|_| next(100)).collect();
+    let output_weights = (0..2 * HIDDEN).map(|_| next(60)).collect();
+    Network::new(HIDDEN, feature_weights, feature_biases, output_weights, 500).unwrap()
+}
+
+#[test]
+fn networks_survive_a_file_round_trip() {
+    let network = network();
+    let bytes = network.to_bytes();
+    assert_eq!(&bytes[..4], b"FCNN");
+    assert_eq!(bytes.len(), 12 + 2 * (FEATURES + 3) * HIDDEN + 4);
+    assert_eq!(Network::from_bytes(&bytes).unwrap(), network);
+
+    assert!(Network::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Network::from_bytes(&[bytes.clone(), vec![0]].concat()).is_err());
+    assert!(Network::from_bytes(b"mg_piece_values = [1]").is_err());
+}
+
+#[test]
+fn incremental_updates_match_a_refresh() {
+    // En passant, castling on both sides and a promotion with capture
+    let mut board = Board::from_fen("r3k2r/1P6/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1");
+    let mut evaluator = NnueEvaluator::new(network());
+    let start_score = evaluator.evaluate(&board);
+    assert_eq!(start_score, evaluator.evaluate_from_scratch(&board));
+
+    let mut made = vec![];
+    for uci_move in ["e5d6", "e8g8", "b7a8q", "f8a8", "e1c1"] {
+        let m = MoveData::from_uci(&uci_move.to_string(), &board);
+        evaluator.make_move(&board, &m);
+        board.make_move(&m);
+        made.push(m);
+        assert_eq!(
+            evaluator.evaluate(&board),
+            evaluator.evaluate_from_scratch(&board),
+            "after {}",
+            uci_move
+        );
+    }
+
+    while let Some(m) = made.pop() {
+        board.unmake_move(&m);
+        evaluator.unmake_move(&board, &m);
+        assert_eq!(
+            evaluator.evaluate(&board),
+            evaluator.evaluate_from_scratch(&board)
+        );
+    }
+    assert_eq!(evaluator.evaluate(&board), start_score);
+}
+
+#[test]
+fn new_positions_are_recognized_without_the_move_hooks() {
+    let mut evaluator = NnueEvaluator::new(network());
+    for fen in [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+    ] {
+        let board = Board::from_fen(fen);
+        assert_eq!(
+            evaluator.evaluate(&board),
+            evaluator.evaluate_from_scratch(&board)
+        );
+    }
+}
+
+#[test]
+fn simd_output_matches_the_scalar_fallback() {
+    let network = network();
+    let us: Vec<i16> = (0..HIDDEN as i16).map(|i| i * 17 - 200).collect();
+    let them: Vec<i16> = (0..HIDDEN as i16).map(|i| 300 - i * 11).collect();
+    assert_eq!(
+        network.output(&us, &them),
+        network.output_scalar(&us, &them)
+    );
+}
+
+#[test]
+fn eval_files_load_networks_and_pesto_params() {
+    let board = Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    let network_path = env::temp_dir().join("ferris_nnue_test.nnue");
+    let network_path = network_path.to_str().unwrap();
+    network().save(network_path).unwrap();
+
+    let mut loaded = load_evaluator(network_path).unwrap();
+    let expected = NnueEvaluator::new(network()).evaluate_from_scratch(&board);
+    assert_eq!(loaded.evaluate(&board), expected);
+
+    let params_path = env::temp_dir().join("ferris_nnue_test_params.txt");
+    let params_path = params_path.to_str().unwrap();
+    std::fs::write(params_path, "tempo = [15, 0]\n").unwrap();
+    assert!(load_evaluator(params_path).is_ok());
+
+    assert!(load_evaluator("/nonexistent/net.nnue").is_err());
+}
+
+#[test]
+fn engine_searches_with_a_network() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let mut engine = Engine::new(fen);
+    engine.set_evaluator(Box::new(NnueEvaluator::new(network())));
+
+    let expected = NnueEvaluator::new(network()).evaluate_from_scratch(&engine.board);
+    assert_eq!(engine.static_eval(), expected);
+
+    engine.iter_deepening(&GoCommand::new("go depth 4"));
+    assert!(engine.search_info().is_some());
+    // The search path is unwound back to the root position
+    assert_eq!(engine.static_eval(), expected);
+}
@@ -0,0 +1,272 @@
+use ferris_chess_board::MoveData;
+use ferris_chess_engine::{search_params::SearchParams, Engine, GoCommand, Score};
+use std::{
+    sync::atomic::Ordering,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn go_infinite_searches_until_stopped() {
+    let mut engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let stop = engine.stop_handle();
+
+    let start = Instant::now();
+    let stopper = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        stop.store(true, Ordering::Relaxed);
+    });
+
+    let go_cmd = GoCommand::new("go infinite");
+    let m = engine.iter_deepening(&go_cmd).unwrap();
+    stopper.join().unwrap();
+
+    assert!(start.elapsed() >= Duration::from_millis(500));
+    assert!(!m.to_uci_move(&engine.board).is_empty());
+}
+
+#[test]
+fn go_command_parses_all_limits() {
+    let go_cmd = GoCommand::new(
+        "go wtime 1000 btime 2000 winc 10 binc 20 movestogo 5 searchmoves e2e4 d2d4 depth 3",
+    );
+    assert_eq!(go_cmd.wtime, Some(1000));
+    assert_eq!(go_cmd.btime, Some(2000));
+    assert_eq!(go_cmd.winc, 10);
+    assert_eq!(go_cmd.binc, 20);
+    assert_eq!(go_cmd.movestogo, Some(5));
+    assert_eq!(
+        go_cmd.searchmoves,
+        vec!["e2e4".to_string(), "d2d4".to_string()]
+    );
+    assert_eq!(go_cmd.depth, Some(3));
+    assert!(!go_cmd.infinite);
+
+    let go_cmd = GoCommand::new("go movetime 100 nodes 5000 mate 2 ponder");
+    assert_eq!(go_cmd.movetime, Some(100));
+    assert_eq!(go_cmd.nodes, Some(5000));
+    assert_eq!(go_cmd.mate, Some(2));
+    assert!(go_cmd.ponder);
+
+    assert!(GoCommand::new("go").infinite);
+}
+
+#[test]
+fn go_with_less_than_one_second_left_returns_in_time() {
+    let mut engine =
+        Engine::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+
+    let start = Instant::now();
+    let go_cmd = GoCommand::new("go wtime 300 btime 300 winc 0 binc 0");
+    engine.iter_deepening(&go_cmd);
+    assert!(start.elapsed() < Duration::from_millis(300));
+}
+
+#[test]
+fn go_searchmoves_restricts_root_moves() {
+    let mut engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let go_cmd = GoCommand::new("go depth 3 searchmoves a2a3 h2h3");
+    let result = engine
+        .iter_deepening(&go_cmd)
+        .unwrap()
+        .to_uci_move(&engine.board);
+    assert!(result == "a2a3" || result == "h2h3");
+}
+
+#[test]
+fn go_mate_finds_mate_in_1() {
+    let mut engine = Engine::new("8/4k3/1r6/8/8/8/r7/4K3 b - - 0 1");
+    let go_cmd = GoCommand::new("go mate 1");
+    let result = engine
+        .iter_deepening(&go_cmd)
+        .unwrap()
+        .to_uci_move(&engine.board);
+    assert_eq!(result, "b6b1".to_string());
+}
+
+#[test]
+fn go_without_legal_moves_returns_no_move() {
+    // Checkmated, then stalemated
+    for fen in [
+        "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1",
+        "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+    ] {
+        let mut engine = Engine::new(fen);
+        assert_eq!(engine.iter_deepening(&GoCommand::new("go depth 3")), None);
+        assert!(engine.search_info().is_none());
+    }
+}
+
+#[test]
+fn go_ponder_waits_for_ponderhit_then_uses_time_control() {
+    let mut engine =
+        Engine::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    let pondering = engine.ponder_handle();
+    pondering.store(true, Ordering::Relaxed);
+
+    let start = Instant::now();
+    let ponderhit = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(500));
+        pondering.store(false, Ordering::Relaxed);
+    });
+
+    // On its own the time control would end the search well before 500ms
+    let go_cmd = GoCommand::new("go ponder wtime 300 btime 300 movestogo 10");
+    engine.iter_deepening(&go_cmd);
+    ponderhit.join().unwrap();
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(500));
+    assert!(elapsed < Duration::from_millis(1500));
+}
+
+#[test]
+fn ponder_move_is_the_reply_in_the_pv() {
+    let mut engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    let go_cmd = GoCommand::new("go depth 4");
+    let m = engine.iter_deepening(&go_cmd).unwrap();
+
+    let reply = engine.ponder_move(&m).expect("No ponder move");
+    engine.board.make_move(&m);
+    assert!(engine.board.get_pseudo_legal_moves().contains(&reply));
+    engine.board.make_move(&reply);
+    assert!(!engine.board.is_king_left_in_check());
+}
+
+#[test]
+fn multi_pv_reports_best_root_moves_sorted_by_score() {
+    let mut engine = Engine::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    engine.set_multi_pv(3);
+    let go_cmd = GoCommand::new("go depth 3");
+    let m = engine.iter_deepening(&go_cmd).unwrap();
+
+    let info = engine.search_info().unwrap();
+    assert_eq!(info.lines.len(), 3);
+    assert_eq!(info.lines[0].moves[0], m);
+
+    let scores: Vec<i32> = info
+        .lines
+        .iter()
+        .map(|line| match line.score {
+            Score::CentiPawns(cp) => cp,
+            Score::Mate(_) => panic!("Unexpected mate score"),
+        })
+        .collect();
+    assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+
+    let first_moves: Vec<MoveData> = info.lines.iter().map(|line| line.moves[0]).collect();
+    assert!(first_moves[0] != first_moves[1] && first_moves[1] != first_moves[2]);
+    assert!(first_moves[0] != first_moves[2]);
+}
+
+#[test]
+fn multi_pv_reports_other_moves_besides_mate() {
+    let mut engine = Engine::new("8/4k3/1r6/8/8/8/r7/4K3 b - - 0 1");
+    engine.set_multi_pv(2);
+    let go_cmd = GoCommand::new("go depth 3");
+    engine.iter_deepening(&go_cmd);
+
+    let info = engine.search_info().unwrap();
+    assert_eq!(info.lines[0].score, Score::Mate(1));
+    assert_eq!(info.lines[0].moves[0].to_uci_move(&engine.board), "b6b1");
+    // Only Rb1 mates at once, the second line is the best other move
+    assert_ne!(info.lines[1].moves[0].to_uci_move(&engine.board), "b6b1");
+    assert_ne!(info.lines[1].score, Score::Mate(1));
+}
+
+#[test]
+fn pv_is_complete_and_legal_with_a_filled_transposition_table() {
+    let mut engine =
+        Engine::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    let go_cmd = GoCommand::new("go depth 4");
+
+    // The second search finds exact entries from the first one along the PV
+    for _ in 0..2 {
+        engine.iter_deepening(&go_cmd);
+
+        // Extended lines can be longer than the search depth
+        let pv = engine.search_info().unwrap().lines[0].moves.clone();
+        assert!(pv.len() >= 4);
+
+        for m in &pv {
+            assert!(engine.board.get_pseudo_legal_moves().contains(m));
+            engine.board.make_move(m);
+            assert!(!engine.board.is_king_left_in_check());
+        }
+        for m in pv.iter().rev() {
+            engine.board.unmake_move(m);
+        }
+    }
+}
+
+#[test]
+fn threads_search_together_and_report_a_legal_move() {
+    let mut engine =
+        Engine::new("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    engine.set_threads(4);
+    let go_cmd = GoCommand::new("go depth 4");
+    let m = engine.iter_deepening(&go_cmd).unwrap();
+
+    let info = engine.search_info().unwrap();
+    assert!(info.depth >= 4);
+    assert_eq!(info.lines[0].moves[0], m);
+    assert!(engine.board.get_pseudo_legal_moves().contains(&m));
+    engine.board.make_move(&m);
+    assert!(!engine.board.is_king_left_in_check());
+}
+
+#[test]
+fn helper_threads_search_nodes_of_their_own() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let mut engine = Engine::new(fen);
+    engine.iter_deepening(&GoCommand::new("go depth 4"));
+    assert_eq!(engine.helper_nodes(), 0);
+
+    // Helpers always complete their first iteration, however soon the main
+    // thread stops them
+    let mut engine = Engine::new(fen);
+    engine.set_threads(4);
+    engine.iter_deepening(&GoCommand::new("go depth 1"));
+    assert!(engine.helper_nodes() >= 3 * 20);
+    assert!(engine.search_info().unwrap().nodes > engine.helper_nodes());
+}
+
+/// Nodes a new engine, with a transposition table of its own, searches with
+/// params. Also checks the best move found is a move of the position.
+fn nodes_searched(fen: &str, params: SearchParams, go_cmd: &str) -> usize {
+    let mut engine = Engine::new(fen);
+    engine.set_search_params(params);
+    let m = engine.iter_deepening(&GoCommand::new(go_cmd)).unwrap();
+    assert!(engine.board.get_pseudo_legal_moves().contains(&m));
+    engine.search_info().unwrap().nodes
+}
+
+#[test]
+fn static_eval_pruning_searches_fewer_nodes() {
+    let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    let full = nodes_searched(fen, SearchParams::no_pruning(), "go depth 7");
+    let pruned = nodes_searched(fen, SearchParams::default(), "go depth 7");
+    assert!(pruned < full);
+}
+
+#[test]
+fn internal_iterative_reduction_searches_fewer_nodes() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let no_iir = SearchParams {
+        iir_min_plies: usize::MAX,
+        ..SearchParams::default()
+    };
+    let full = nodes_searched(fen, no_iir, "go depth 5");
+    let reduced = nodes_searched(fen, SearchParams::default(), "go depth 5");
+    assert!(reduced < full);
+}
+
+#[test]
+fn internal_iterative_reduction_never_reaches_the_quiescence_search() {
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let params = SearchParams {
+        iir_min_plies: 0,
+        ..SearchParams::default()
+    };
+    assert!(nodes_searched(fen, params, "go depth 4") > 0);
+}
@@ -0,0 +1,269 @@
+use ferris_chess_board::{Board, Color, MoveData, Piece};
+use ferris_chess_engine::bitbase::kpk;
+use ferris_chess_engine::syzygy::{table_pieces, SyzygyTables, Wdl, TB_WIN};
+use ferris_chess_engine::{Engine, GoCommand, Score, MAX_PLY};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+use syzygy_gen::generate_tables;
+
+mod syzygy_gen;
+
+// Tables of a king and a piece or pawn against a lone king, written by
+// generate_the_test_tables
+const TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+
+fn tables() -> SyzygyTables {
+    SyzygyTables::load(TABLES).unwrap()
+}
+
+/// A directory of its own for each test, as the tests run in parallel
+fn temp_dir(test: &str) -> PathBuf {
+    env::temp_dir().join(format!("ferris_syzygy_{}_{}", process::id(), test))
+}
+
+fn probe_wdl(fen: &str) -> Option<Wdl> {
+    tables().probe_wdl(&mut Board::from_fen(fen))
+}
+
+fn probe_dtz(fen: &str) -> Option<i32> {
+    tables().probe_dtz(&mut Board::from_fen(fen))
+}
+
+#[test]
+#[ignore]
+fn generate_the_test_tables() {
+    generate_tables(Path::new(TABLES)).unwrap();
+}
+
+#[test]
+fn table_names_give_the_piece_count() {
+    assert_eq!(table_pieces("KvK"), Some(2));
+    assert_eq!(table_pieces("KRPvKR"), Some(5));
+    assert_eq!(table_pieces("KQvKk"), None);
+    assert_eq!(table_pieces("README"), None);
+}
+
+#[test]
+fn tables_are_found_in_every_directory_of_the_path() {
+    let dirs = [temp_dir("found_1"), temp_dir("found_2")];
+    for dir in &dirs {
+        fs::create_dir_all(dir).unwrap();
+    }
+    let wdl = [0x71, 0xe8, 0x23, 0x5d, 0];
+    let dtz = [0xd7, 0x66, 0x0c, 0xa5, 0];
+    fs::write(dirs[0].join("KQvK.rtbw"), wdl).unwrap();
+    fs::write(dirs[0].join("KQvK.rtbz"), dtz).unwrap();
+    fs::write(dirs[1].join("KRPvKR.rtbw"), wdl).unwrap();
+    fs::write(dirs[1].join("notes.txt"), "not a table").unwrap();
+
+    let path = env::join_paths(&dirs).unwrap();
+    let tables = SyzygyTables::load(path.to_str().unwrap()).unwrap();
+    assert_eq!(tables.wdl.keys().collect::<Vec<_>>(), ["KQvK", "KRPvKR"]);
+    assert_eq!(tables.dtz.keys().collect::<Vec<_>>(), ["KQvK"]);
+    assert_eq!(tables.max_pieces(), 5);
+
+    // A WDL header in a DTZ file
+    fs::write(dirs[1].join("KRPvKR.rtbz"), wdl).unwrap();
+    assert!(SyzygyTables::load(path.to_str().unwrap()).is_err());
+
+    for dir in &dirs {
+        fs::remove_dir_all(dir).unwrap();
+    }
+    assert!(SyzygyTables::load(path.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn truncated_tables_are_not_probed() {
+    let dir = temp_dir("truncated");
+    fs::create_dir_all(&dir).unwrap();
+    let table = fs::read(Path::new(TABLES).join("KQvK.rtbw")).unwrap();
+    fs::write(dir.join("KQvK.rtbw"), &table[..table.len() / 2]).unwrap();
+
+    let tables = SyzygyTables::load(dir.to_str().unwrap()).unwrap();
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+    assert_eq!(tables.probe_wdl(&mut board), None);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn wdl_agrees_with_the_kpk_bitbase() {
+    let tables = tables();
+    let bitbase = kpk();
+    for pawn in [8, 19, 28, 38, 49, 55] {
+        for fen in ["8/8/8/8/8/8/8/8 w - - 0 1", "8/8/8/8/8/8/8/8 b - - 0 1"] {
+            let mut board = Board::from_fen(fen);
+            board.set_piece(pawn, Some((Color::White, Piece::Pawn)));
+            for white_king in (0..64).filter(|sq| *sq != pawn) {
+                board.set_piece(white_king, Some((Color::White, Piece::King)));
+                for black_king in (0..64).filter(|sq| *sq != pawn && *sq != white_king) {
+                    board.set_piece(black_king, Some((Color::Black, Piece::King)));
+                    if !board.is_king_left_in_check() {
+                        let white_to_move = !board.black_to_move;
+                        let wins = bitbase.is_win(
+                            Color::White,
+                            white_to_move,
+                            white_king,
+                            black_king,
+                            pawn,
+                        );
+                        let expected = match (wins, white_to_move) {
+                            (false, _) => Wdl::Draw,
+                            (true, true) => Wdl::Win,
+                            (true, false) => Wdl::Loss,
+                        };
+                        assert_eq!(
+                            tables.probe_wdl(&mut board),
+                            Some(expected),
+                            "{}",
+                            board.to_fen()
+                        );
+                    }
+                    board.set_piece(black_king, None);
+                }
+                board.set_piece(white_king, None);
+            }
+        }
+    }
+}
+
+#[test]
+fn wdl_of_pieces_against_a_lone_king() {
+    assert_eq!(probe_wdl("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"), Some(Wdl::Loss));
+    assert_eq!(probe_wdl("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), Some(Wdl::Win));
+    assert_eq!(
+        probe_wdl("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"),
+        Some(Wdl::Draw)
+    );
+    assert_eq!(
+        probe_wdl("4k3/8/8/8/8/8/8/2N1K3 b - - 0 1"),
+        Some(Wdl::Draw)
+    );
+    assert_eq!(probe_wdl("8/8/8/8/8/8/8/K1k5 w - - 0 1"), Some(Wdl::Draw));
+    // Black takes the queen
+    assert_eq!(probe_wdl("8/8/8/8/8/8/1k6/Q3K3 b - - 0 1"), Some(Wdl::Draw));
+    // Stalemate
+    assert_eq!(probe_wdl("k7/8/1QK5/8/8/8/8/8 b - - 0 1"), Some(Wdl::Draw));
+    // No table for two rooks
+    assert_eq!(probe_wdl("4k3/8/8/8/8/8/8/R3K2R w - - 0 1"), None);
+}
+
+#[test]
+fn wdl_is_the_same_with_the_colors_swapped() {
+    assert_eq!(probe_wdl("3qk3/8/8/8/8/8/8/4K3 w - - 0 1"), Some(Wdl::Loss));
+    assert_eq!(probe_wdl("r3k3/8/8/8/8/8/8/4K3 b - - 0 1"), Some(Wdl::Win));
+    assert_eq!(
+        probe_wdl("4k3/8/8/8/8/4p3/8/4K3 w - - 0 1"),
+        Some(Wdl::Draw)
+    );
+    assert_eq!(
+        probe_wdl("8/8/8/8/8/3k4/4p3/5K2 w - - 0 1"),
+        Some(Wdl::Draw)
+    );
+    // Stalemate, and with black to move the king takes the opposition
+    assert_eq!(
+        probe_wdl("8/8/8/8/8/4k3/4p3/4K3 w - - 0 1"),
+        Some(Wdl::Draw)
+    );
+    assert_eq!(probe_wdl("8/8/8/8/8/4k3/4p3/4K3 b - - 0 1"), Some(Wdl::Win));
+}
+
+#[test]
+fn dtz_counts_the_plies_to_mate() {
+    // Qb8 mates
+    assert_eq!(probe_dtz("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1"), Some(1));
+    assert_eq!(probe_dtz("1Q5k/8/6K1/8/8/8/8/8 b - - 0 1"), Some(-1));
+    assert_eq!(probe_dtz("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"), Some(0));
+    // Promoting is a pawn move
+    assert_eq!(probe_dtz("8/4P3/8/8/8/8/k7/4K3 w - - 0 1"), Some(1));
+
+    let tables = tables();
+    for fen in [
+        "8/8/8/8/8/2k5/8/2K2Q2 w - - 0 1",
+        "k7/8/2K5/8/8/8/8/7R w - - 0 1",
+        "7k/8/5K2/8/8/8/8/1R6 b - - 0 1",
+    ] {
+        let mut board = Board::from_fen(fen);
+        let dtz = tables.probe_dtz(&mut board).unwrap();
+
+        // Without pawns the tables count the plies to mate
+        let mut engine = Engine::new(fen);
+        engine.set_print_info(false);
+        let go = GoCommand::new(&format!("go depth {}", dtz.unsigned_abs() + 1));
+        engine.iter_deepening(&go).unwrap();
+        let info = engine.search_info().unwrap().clone();
+        let mate = (dtz.abs() + 1) / 2;
+        let expected = if dtz > 0 { mate } else { -mate };
+        assert_eq!(info.score, Score::Mate(expected), "{}", fen);
+    }
+}
+
+#[test]
+fn longest_wins_are_the_known_mates() {
+    // Mate in 10 with the queen and in 16 with the rook, found by scanning
+    // the positions with the black king in the a1-d1-d4 triangle
+    let tables = tables();
+    for (piece, longest) in [(Piece::Queen, 19), (Piece::Rook, 31)] {
+        let mut board = Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1");
+        let mut max_dtz = 0;
+        for black_king in [0, 1, 2, 3, 9, 10, 11, 18, 19, 27] {
+            board.set_piece(black_king, Some((Color::Black, Piece::King)));
+            for white_king in (0..64).filter(|sq| *sq != black_king) {
+                board.set_piece(white_king, Some((Color::White, Piece::King)));
+                for square in (0..64).filter(|sq| *sq != black_king && *sq != white_king) {
+                    board.set_piece(square, Some((Color::White, piece)));
+                    if !board.is_king_left_in_check() {
+                        max_dtz = max_dtz.max(tables.probe_dtz(&mut board).unwrap());
+                    }
+                    board.set_piece(square, None);
+                }
+                board.set_piece(white_king, None);
+            }
+            board.set_piece(black_king, None);
+        }
+        assert_eq!(max_dtz, longest, "{:?}", piece);
+    }
+}
+
+#[test]
+fn search_scores_positions_won_in_the_tables() {
+    let mut engine = Engine::new("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+    engine.set_print_info(false);
+    engine.set_syzygy_tables(Some(tables()));
+    engine
+        .iter_deepening(&GoCommand::new("go depth 3"))
+        .unwrap();
+    let info = engine.search_info().unwrap().clone();
+    match info.score {
+        Score::CentiPawns(score) => assert!(score >= TB_WIN - MAX_PLY as i32),
+        Score::Mate(_) => panic!("{:?}", info.score),
+    }
+}
+
+#[test]
+fn root_moves_keep_the_quickest_wins() {
+    let tables = tables();
+    let mut board = Board::from_fen("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1");
+    let moves = board.get_legal_moves();
+    let best = tables.best_root_moves(&mut board, &moves).unwrap();
+    let uci = |moves: &[MoveData]| {
+        let mut uci: Vec<_> = moves.iter().map(|m| m.to_uci_move(&board)).collect();
+        uci.sort();
+        uci
+    };
+    assert_eq!(uci(&best), ["e6d6", "e6f6"]);
+
+    // A mate in one is kept over the slower wins
+    let mut board = Board::from_fen("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1");
+    let moves = board.get_legal_moves();
+    let best = tables.best_root_moves(&mut board, &moves).unwrap();
+    assert_eq!(uci(&best), ["b1b8"]);
+
+    let mut engine = Engine::new("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1");
+    engine.set_print_info(false);
+    engine.set_syzygy_tables(Some(tables));
+    engine
+        .iter_deepening(&GoCommand::new("go depth 2"))
+        .unwrap();
+    let info = engine.search_info().unwrap().clone();
+    assert!(["e6d6", "e6f6"].contains(&info.move_data.to_uci_move(&engine.board).as_str()));
+}
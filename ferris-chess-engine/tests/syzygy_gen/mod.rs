@@ -0,0 +1,579 @@
+//! Writes Syzygy tables of a king and a piece or pawn against a lone king
+//! for the tests. The files follow the layout of the Syzygy generator but
+//! share no code with the reader in the engine, so the tests check one
+//! against the other.
+
+use ferris_chess_board::{Board, Color, MoveType, Piece};
+use ferris_chess_engine::syzygy::{SyzygyTables, Wdl};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs;
+use std::path::Path;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags of the file header
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// Flags of each compressed table, the DTZ ones store plies for white to
+// move
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const SINGLE_VALUE: u8 = 128;
+
+#[derive(Clone, Copy, PartialEq)]
+enum TableType {
+    Wdl,
+    Dtz,
+}
+
+// Positions of a king and a piece against a lone king, by side to move and
+// the squares of the white king, the piece and the black king
+const POSITIONS: usize = 2 * 64 * 64 * 64;
+
+// Sizes of the compressed blocks and of the spans of the sparse index, as
+// powers of two
+const BLOCK_BITS: u8 = 6;
+const SPAN_BITS: u8 = 10;
+
+fn index(white_to_move: bool, white_king: usize, piece: usize, black_king: usize) -> usize {
+    ((usize::from(!white_to_move) * 64 + white_king) * 64 + piece) * 64 + black_king
+}
+
+/// Result for the side to move, with the plies to mate or to the capture
+/// or pawn move the winning side goes for
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value {
+    Invalid,
+    Unknown,
+    Draw,
+    Win(u8),
+    Loss(u8),
+}
+
+/// A position still to be resolved
+struct Node {
+    idx: usize,
+    // Positions reached by moves that don't capture or move a pawn
+    next: Vec<u32>,
+    // Some capture or pawn move wins, or draws
+    zeroing_win: bool,
+    zeroing_draw: bool,
+}
+
+/// Squares are mirrored so that the first square is on files a to d
+fn mirror_files(squares: &mut [usize]) {
+    if squares[0] % 8 > 3 {
+        squares.iter_mut().for_each(|sq| *sq ^= 7);
+    }
+}
+
+/// Index of the piece, the white king and the black king in a table
+/// without pawns. The piece goes to the a1-d1-d4 triangle, and the first
+/// square off the a1-h8 diagonal below it.
+fn pieces_index(mut squares: [usize; 3]) -> usize {
+    mirror_files(&mut squares);
+    if squares[0] / 8 > 3 {
+        squares.iter_mut().for_each(|sq| *sq ^= 56);
+    }
+    let (rank, file) = (|sq: usize| sq / 8, |sq: usize| sq % 8);
+    if let Some(sq) = squares.iter().find(|sq| rank(**sq) != file(**sq)) {
+        if rank(*sq) > file(*sq) {
+            squares
+                .iter_mut()
+                .for_each(|sq| *sq = file(*sq) * 8 + rank(*sq));
+        }
+    }
+
+    // The triangle squares below the diagonal come before the ones on it
+    const TRIANGLE: [usize; 10] = [1, 2, 3, 10, 11, 19, 0, 9, 18, 27];
+    let below = |sq: usize| (0..sq).filter(|s| rank(*s) < file(*s)).count();
+    let [piece, white_king, black_king] = squares;
+    let white_king_skip = usize::from(white_king > piece);
+    let black_king_skip = usize::from(black_king > piece) + usize::from(black_king > white_king);
+    let on_diagonal = |sq: usize| rank(sq) == file(sq);
+    if !on_diagonal(piece) {
+        let triangle = TRIANGLE.iter().position(|sq| *sq == piece).unwrap();
+        (triangle * 63 + white_king - white_king_skip) * 62 + black_king - black_king_skip
+    } else if !on_diagonal(white_king) {
+        (6 * 63 + rank(piece) * 28 + below(white_king)) * 62 + black_king - black_king_skip
+    } else if !on_diagonal(black_king) {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + (rank(piece) * 7 + rank(white_king) - white_king_skip) * 28
+            + below(black_king)
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + (rank(piece) * 7 + rank(white_king) - white_king_skip) * 6
+            + rank(black_king)
+            - black_king_skip
+    }
+}
+
+/// Index of the pawn, the white king and the black king in the table of
+/// the file of the pawn, from a to d
+fn pawn_index(mut squares: [usize; 3]) -> usize {
+    mirror_files(&mut squares);
+    let [pawn, white_king, black_king] = squares;
+    let white_king = white_king - usize::from(white_king > pawn);
+    let black_king =
+        black_king - usize::from(black_king > pawn) - usize::from(black_king > squares[1]);
+    pawn / 8 - 1 + 6 * white_king + 6 * 63 * black_king
+}
+
+/// Squares the piece can stand on
+fn piece_squares(piece: Piece) -> Vec<usize> {
+    match piece {
+        Piece::Pawn => (8..56).collect(),
+        _ => (0..64).collect(),
+    }
+}
+
+/// Results of the positions of a king and piece against a lone king, from
+/// the tables solved before for the pieces a pawn promotes to. Like the KPK
+/// bitbase, positions are resolved back from mates and from the moves that
+/// leave the table, one ply further each round, and positions never
+/// resolved are draws.
+fn solve(piece: Piece, promotions: &[(Piece, Vec<Value>)]) -> Vec<Value> {
+    let mut values = vec![Value::Invalid; POSITIONS];
+    let mut boards = [
+        Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1"),
+        Board::from_fen("8/8/8/8/8/8/8/8 b - - 0 1"),
+    ];
+
+    // Pawn moves lead to positions with the pawn further up, which are
+    // solved first
+    let phases: Vec<Vec<usize>> = match piece {
+        Piece::Pawn => (1..7)
+            .rev()
+            .map(|rank| (rank * 8..rank * 8 + 8).collect())
+            .collect(),
+        _ => vec![piece_squares(piece)],
+    };
+    for phase in phases {
+        let mut nodes = vec![];
+        for (board, white_to_move) in boards.iter_mut().zip([true, false]) {
+            for &square in &phase {
+                board.set_piece(square, Some((Color::White, piece)));
+                for white_king in (0..64).filter(|sq| *sq != square) {
+                    board.set_piece(white_king, Some((Color::White, Piece::King)));
+                    for black_king in (0..64).filter(|sq| *sq != square && *sq != white_king) {
+                        board.set_piece(black_king, Some((Color::Black, Piece::King)));
+
+                        let idx = index(white_to_move, white_king, square, black_king);
+                        if !board.is_king_left_in_check() {
+                            let moves = board.get_legal_moves();
+                            let mut node = Node {
+                                idx,
+                                next: vec![],
+                                zeroing_win: false,
+                                zeroing_draw: false,
+                            };
+                            for m in &moves {
+                                board.make_move(m);
+                                let kings = (
+                                    board.get_king_pos(Color::White).unwrap(),
+                                    board.get_king_pos(Color::Black).unwrap(),
+                                );
+                                board.unmake_move(m);
+
+                                let promoted = match m.move_type {
+                                    MoveType::QueenPromotion => Some(Piece::Queen),
+                                    MoveType::RookPromotion => Some(Piece::Rook),
+                                    MoveType::BishopPromotion => Some(Piece::Bishop),
+                                    MoveType::KnightPromotion => Some(Piece::Knight),
+                                    _ => None,
+                                };
+                                let zeroing = if m.capture.is_some() {
+                                    // Only the kings are left
+                                    Value::Draw
+                                } else if let Some(promoted) = promoted {
+                                    let (_, table) =
+                                        promotions.iter().find(|(p, _)| *p == promoted).unwrap();
+                                    table[index(false, kings.0, m.end_pos, kings.1)]
+                                } else if m.piece == Piece::Pawn {
+                                    values[index(false, kings.0, m.end_pos, kings.1)]
+                                } else {
+                                    let square = if m.piece == piece { m.end_pos } else { square };
+                                    node.next.push(
+                                        index(!white_to_move, kings.0, square, kings.1) as u32
+                                    );
+                                    continue;
+                                };
+                                match zeroing {
+                                    Value::Loss(_) => node.zeroing_win = true,
+                                    Value::Draw => node.zeroing_draw = true,
+                                    _ => (),
+                                }
+                            }
+
+                            values[idx] = if moves.is_empty() && board.is_player_mated() {
+                                Value::Loss(0)
+                            } else if moves.is_empty() {
+                                Value::Draw
+                            } else {
+                                nodes.push(node);
+                                Value::Unknown
+                            };
+                        }
+
+                        board.set_piece(black_king, None);
+                    }
+                    board.set_piece(white_king, None);
+                }
+                board.set_piece(square, None);
+            }
+        }
+
+        // Round n finds the wins in n plies and the losses in n plies, from
+        // the results of the earlier rounds only
+        let mut plies = 1;
+        loop {
+            let mut resolved = vec![];
+            for node in &nodes {
+                let next = node.next.iter().map(|n| values[*n as usize]);
+                if node.zeroing_win {
+                    resolved.push((node.idx, Value::Win(1)));
+                } else if next.clone().any(|v| v == Value::Loss(plies - 1)) {
+                    resolved.push((node.idx, Value::Win(plies)));
+                } else if !node.zeroing_draw && next.clone().all(|v| matches!(v, Value::Win(_))) {
+                    let longest = next
+                        .map(|v| match v {
+                            Value::Win(d) => d,
+                            _ => 0,
+                        })
+                        .max()
+                        .unwrap_or(0);
+                    resolved.push((node.idx, Value::Loss(longest + 1)));
+                }
+            }
+            if resolved.is_empty() {
+                break;
+            }
+            for (idx, value) in resolved {
+                values[idx] = value;
+            }
+            nodes.retain(|node| values[node.idx] == Value::Unknown);
+            plies += 1;
+        }
+        for node in nodes {
+            values[node.idx] = Value::Draw;
+        }
+    }
+    values
+}
+
+/// Value stored in the tables for a position, None for positions they
+/// don't need
+fn stored_value(table_type: TableType, value: Value) -> Option<u16> {
+    match (table_type, value) {
+        (_, Value::Invalid | Value::Unknown) => None,
+        (TableType::Wdl, Value::Win(_)) => Some(4),
+        (TableType::Wdl, Value::Loss(_)) => Some(0),
+        (TableType::Wdl, _) => Some(2),
+        (TableType::Dtz, Value::Win(plies)) => Some(plies as u16 - 1),
+        (TableType::Dtz, Value::Loss(plies)) => Some(plies.max(1) as u16 - 1),
+        (TableType::Dtz, _) => None,
+    }
+}
+
+/// Huffman code lengths of the values
+fn code_lengths(counts: &BTreeMap<u16, u64>) -> BTreeMap<u16, usize> {
+    let mut lengths: BTreeMap<u16, usize> = counts.keys().map(|v| (*v, 0)).collect();
+    let mut heap: BinaryHeap<_> = counts
+        .iter()
+        .map(|(value, count)| Reverse((*count, vec![*value])))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((count1, mut values1)) = heap.pop().unwrap();
+        let Reverse((count2, values2)) = heap.pop().unwrap();
+        values1.extend(values2);
+        for value in &values1 {
+            *lengths.get_mut(value).unwrap() += 1;
+        }
+        heap.push(Reverse((count1 + count2, values1)));
+    }
+    lengths
+}
+
+/// The parts of a file for one compressed table
+#[derive(Default)]
+struct Compressed {
+    sizes: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_length: Vec<u8>,
+    blocks: Vec<u8>,
+}
+
+/// Huffman codes each value as a symbol of its own, without the pairing of
+/// symbols of the original generator
+fn compress(values: &[u16], flags: u8) -> Compressed {
+    let mut counts = BTreeMap::new();
+    for value in values {
+        *counts.entry(*value).or_insert(0) += 1;
+    }
+    if counts.len() == 1 {
+        return Compressed {
+            sizes: vec![flags | SINGLE_VALUE, values[0] as u8],
+            ..Compressed::default()
+        };
+    }
+
+    // Symbols are numbered from the longest codes, which take the lowest
+    // code values
+    let mut symbols: Vec<(usize, u16)> = code_lengths(&counts)
+        .into_iter()
+        .map(|(value, len)| (len, value))
+        .collect();
+    symbols.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    let (max_len, min_len) = (symbols[0].0, symbols[symbols.len() - 1].0);
+    let lowest: Vec<usize> = (min_len..=max_len)
+        .map(|len| symbols.iter().filter(|(l, _)| *l > len).count())
+        .collect();
+    let mut base = vec![0u64; max_len - min_len + 1];
+    for len in (min_len + 1..=max_len).rev() {
+        let count = symbols.iter().filter(|(l, _)| *l == len).count() as u64;
+        base[len - 1 - min_len] = (base[len - min_len] + count) / 2;
+    }
+    let codes: BTreeMap<u16, (u64, usize)> = symbols
+        .iter()
+        .enumerate()
+        .map(|(sym, (len, value))| {
+            let i = len - min_len;
+            (*value, (base[i] + (sym - lowest[i]) as u64, *len))
+        })
+        .collect();
+
+    // Codes are packed from the high bit, and don't cross blocks
+    let block_bits = 8 << BLOCK_BITS;
+    let mut blocks = vec![];
+    let mut block_values = vec![];
+    let mut bit = block_bits;
+    for value in values {
+        let (code, len) = codes[value];
+        if bit + len > block_bits {
+            blocks.resize(blocks.len() + (1 << BLOCK_BITS), 0);
+            block_values.push(0);
+            bit = 0;
+        }
+        let start = blocks.len() - (1 << BLOCK_BITS);
+        for i in (0..len).rev() {
+            if code >> i & 1 != 0 {
+                blocks[start + bit / 8] |= 0x80 >> (bit % 8);
+            }
+            bit += 1;
+        }
+        *block_values.last_mut().unwrap() += 1;
+    }
+
+    // Each entry gives the block and offset of the middle of its span, the
+    // last ones may be past the end
+    let starts: Vec<usize> = block_values
+        .iter()
+        .scan(0, |start, count| {
+            *start += count;
+            Some(*start - count)
+        })
+        .collect();
+    let span = 1 << SPAN_BITS;
+    let mut sparse_index = vec![];
+    for k in 0..values.len().div_ceil(span) {
+        let middle = k * span + span / 2;
+        let block = starts.partition_point(|start| *start <= middle) - 1;
+        sparse_index.extend((block as u32).to_le_bytes());
+        sparse_index.extend(((middle - starts[block]) as u16).to_le_bytes());
+    }
+    let block_length = block_values
+        .iter()
+        .flat_map(|count| (*count as u16 - 1).to_le_bytes())
+        .collect();
+
+    let mut sizes = vec![flags, BLOCK_BITS, SPAN_BITS, 0];
+    sizes.extend((block_values.len() as u32).to_le_bytes());
+    sizes.extend([max_len as u8, min_len as u8]);
+    for sym in &lowest {
+        sizes.extend((*sym as u16).to_le_bytes());
+    }
+    sizes.extend((symbols.len() as u16).to_le_bytes());
+    for (_, value) in &symbols {
+        sizes.extend([*value as u8, (value >> 8) as u8 | 0xF0, 0xFF]);
+    }
+    if symbols.len() % 2 == 1 {
+        sizes.push(0);
+    }
+
+    Compressed {
+        sizes,
+        sparse_index,
+        block_length,
+        blocks,
+    }
+}
+
+/// Writes the WDL or DTZ table of name, a king and piece against a lone
+/// king. DTZ tables only store white to move.
+fn write_table(
+    dir: &Path,
+    name: &str,
+    table_type: TableType,
+    piece: Piece,
+    values: &[Value],
+) -> Result<(), String> {
+    // Codes of the piece, the white king and the black king
+    let piece_code = match piece {
+        Piece::Pawn => 1,
+        Piece::Knight => 2,
+        Piece::Bishop => 3,
+        Piece::Rook => 4,
+        Piece::Queen => 5,
+        Piece::King => return Err(format!("{} is not a table of a piece", name)),
+    };
+    let pieces: [u8; 3] = [piece_code, 6, 14];
+    let has_pawns = piece == Piece::Pawn;
+    let (files, size) = match has_pawns {
+        true => (4, 6 * 63 * 62),
+        false => (1, 31332),
+    };
+    let (magic, sides, flags, extension) = match table_type {
+        TableType::Wdl => (WDL_MAGIC, 2, 0, "rtbw"),
+        TableType::Dtz => (DTZ_MAGIC, 1, WIN_PLIES | LOSS_PLIES, "rtbz"),
+    };
+
+    let mut compressed = vec![];
+    for file in 0..files {
+        for side in 0..sides {
+            let white_to_move = side == 0;
+            let mut table = vec![None; size];
+            for square in piece_squares(piece) {
+                if has_pawns && (square % 8).min(7 - square % 8) != file {
+                    continue;
+                }
+                for white_king in 0..64 {
+                    for black_king in 0..64 {
+                        let value = values[index(white_to_move, white_king, square, black_king)];
+                        let Some(value) = stored_value(table_type, value) else {
+                            continue;
+                        };
+                        let idx = match has_pawns {
+                            true => pawn_index([square, white_king, black_king]),
+                            false => pieces_index([square, white_king, black_king]),
+                        };
+                        match table[idx] {
+                            Some(v) if v != value => {
+                                return Err(format!(
+                                    "{} has different values for symmetric positions",
+                                    name
+                                ))
+                            }
+                            _ => table[idx] = Some(value),
+                        }
+                    }
+                }
+            }
+
+            // Positions that can't occur take the most common value
+            let mut counts = BTreeMap::new();
+            for value in table.iter().flatten() {
+                *counts.entry(*value).or_insert(0) += 1;
+            }
+            let common = counts
+                .iter()
+                .max_by_key(|(_, count)| **count)
+                .map_or(0, |(value, _)| *value);
+            let table: Vec<u16> = table.iter().map(|v| v.unwrap_or(common)).collect();
+            compressed.push(compress(&table, flags));
+        }
+    }
+
+    let mut data = magic.to_vec();
+    data.push(SPLIT | if has_pawns { HAS_PAWNS } else { 0 });
+    for _ in 0..files {
+        // The same pieces in the same order for both sides to move
+        data.push(0);
+        data.extend(pieces.iter().map(|p| p | p << 4));
+    }
+    data.resize(data.len() + (data.len() & 1), 0);
+    for c in &compressed {
+        data.extend(&c.sizes);
+    }
+    if table_type == TableType::Dtz {
+        data.resize(data.len() + (data.len() & 1), 0);
+    }
+    for c in &compressed {
+        data.extend(&c.sparse_index);
+    }
+    for c in &compressed {
+        data.extend(&c.block_length);
+    }
+    for c in &compressed {
+        data.resize((data.len() + 0x3F) & !0x3F, 0);
+        data.extend(&c.blocks);
+    }
+
+    let path = dir.join(format!("{}.{}", name, extension));
+    fs::write(&path, data).map_err(|e| format!("Can't write {}: {}", path.display(), e))
+}
+
+/// Solves and writes the WDL and DTZ tables of a king and a piece or pawn
+/// against a lone king to dir
+pub fn generate_tables(dir: &Path) -> Result<(), String> {
+    let mut solved = vec![];
+    for (piece, name) in [
+        (Piece::Knight, "KNvK"),
+        (Piece::Bishop, "KBvK"),
+        (Piece::Rook, "KRvK"),
+        (Piece::Queen, "KQvK"),
+        (Piece::Pawn, "KPvK"),
+    ] {
+        let values = solve(piece, &solved);
+        write_table(dir, name, TableType::Wdl, piece, &values)?;
+        write_table(dir, name, TableType::Dtz, piece, &values)?;
+        solved.push((piece, values));
+    }
+
+    // Probing the tables gives the results of the solved positions
+    let tables = SyzygyTables::load(dir.to_str().ok_or("Invalid path")?)?;
+    let mut boards = [
+        Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1"),
+        Board::from_fen("8/8/8/8/8/8/8/8 b - - 0 1"),
+    ];
+    for (piece, values) in &solved {
+        for (board, white_to_move) in boards.iter_mut().zip([true, false]) {
+            for square in piece_squares(*piece) {
+                board.set_piece(square, Some((Color::White, *piece)));
+                for white_king in (0..64).filter(|sq| *sq != square) {
+                    board.set_piece(white_king, Some((Color::White, Piece::King)));
+                    for black_king in (0..64).filter(|sq| *sq != square && *sq != white_king) {
+                        board.set_piece(black_king, Some((Color::Black, Piece::King)));
+                        let value = values[index(white_to_move, white_king, square, black_king)];
+                        let expected = match value {
+                            Value::Win(plies) => Some((Wdl::Win, plies as i32)),
+                            Value::Loss(plies) => Some((Wdl::Loss, -(plies.max(1) as i32))),
+                            Value::Draw => Some((Wdl::Draw, 0)),
+                            _ => None,
+                        };
+                        if let Some((wdl, dtz)) = expected {
+                            let probed = (tables.probe_wdl(board), tables.probe_dtz(board));
+                            if probed != (Some(wdl), Some(dtz)) {
+                                return Err(format!(
+                                    "{} probed {:?} solved {:?}",
+                                    board.to_fen(),
+                                    probed,
+                                    value
+                                ));
+                            }
+                        }
+                        board.set_piece(black_king, None);
+                    }
+                    board.set_piece(white_king, None);
+                }
+                board.set_piece(square, None);
+            }
+        }
+    }
+    Ok(())
+}
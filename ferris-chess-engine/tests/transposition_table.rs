@@ -1,6 +1,8 @@
 // Speed without transposition table for n=5: 3.16s
 // Speed with table for n=5:
 
+use ferris_chess_board::MoveData;
+use ferris_chess_engine::transposition_table::{NodeType, TTableData, TranspositonTable};
 use ferris_chess_engine::Engine;
 
 #[test]
@@ -178,3 +180,89 @@ fn tt_perft_pos_6_n_4() {
     let result = engine.tt_perft(4);
     assert_eq!(result, 3894594);
 }
+
+#[test]
+fn tt_keeps_entries_in_small_table() {
+    let t_table = TranspositonTable::new(1);
+    for zobrist in 0..8 {
+        t_table.insert(TTableData {
+            zobrist,
+            best_move: None,
+            depth: 4,
+            score: zobrist as i32,
+            node_type: NodeType::Exact,
+        });
+    }
+    assert_eq!(t_table.get(5, 4).unwrap().score, 5);
+    assert!(t_table.get(5, 5).is_none());
+}
+
+#[test]
+fn tt_hashfull_counts_current_search_only() {
+    let t_table = TranspositonTable::new(1);
+    for zobrist in 0..1000 {
+        t_table.insert(TTableData {
+            zobrist,
+            best_move: None,
+            depth: 1,
+            score: 0,
+            node_type: NodeType::Exact,
+        });
+    }
+    assert!(t_table.hashfull() > 0);
+
+    t_table.new_search();
+    assert_eq!(t_table.hashfull(), 0);
+}
+
+#[test]
+fn tt_entries_keep_moves_and_negative_scores() {
+    let engine = Engine::new("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1");
+    let t_table = TranspositonTable::new(1);
+    let promotion = MoveData::from_uci(&"b2a1q".to_string(), &engine.board);
+
+    t_table.insert(TTableData {
+        zobrist: engine.board.zobrist.hash,
+        best_move: Some(promotion),
+        depth: 7,
+        score: -1234,
+        node_type: NodeType::LowerBound,
+    });
+
+    let data = t_table.get(engine.board.zobrist.hash, 7).unwrap();
+    assert!(data.best_move == Some(promotion));
+    assert_eq!(data.score, -1234);
+    assert!(data.node_type == NodeType::LowerBound);
+    assert!(t_table.get(engine.board.zobrist.hash ^ 1, 0).is_none());
+}
+
+#[test]
+fn tt_is_shared_between_threads() {
+    let t_table = TranspositonTable::new(1);
+
+    std::thread::scope(|s| {
+        for thread_id in 0..4u64 {
+            let t_table = &t_table;
+            s.spawn(move || {
+                for i in 0..1000u64 {
+                    let zobrist = i * 4 + thread_id;
+                    t_table.insert(TTableData {
+                        zobrist,
+                        best_move: None,
+                        depth: 3,
+                        score: zobrist as i32,
+                        node_type: NodeType::Exact,
+                    });
+                }
+            });
+        }
+    });
+
+    // Whatever survived replacement must belong to the position probed
+    for zobrist in 0..4000u64 {
+        if let Some(data) = t_table.get(zobrist, 3) {
+            assert_eq!(data.score, zobrist as i32);
+        }
+    }
+    assert!(t_table.get(3999, 3).is_some());
+}
@@ -0,0 +1,99 @@
+use ferris_chess_engine::eval_params::EvalParams;
+use ferris_chess_engine::tuner::{load_positions, sigmoid, Tuner, TuningPosition};
+use std::env;
+
+const POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - c9 \"1/2-1/2\";",
+    "4k3/8/8/8/8/8/3QK3/8 w - - c9 \"1-0\";",
+    "4k3/3q4/8/8/8/8/4K3/8 b - - c9 \"0-1\";",
+    "4k3/pppp4/8/8/8/8/PPPPP3/4K3 w - - 0 1;1-0",
+    "4k3/ppppp3/8/8/8/8/PPPP4/4K3 b - - 0 1;0.0",
+    "4k3/8/8/8/8/8/8/R3K3 w - - 0 1;950;1.0",
+];
+
+fn positions() -> Vec<TuningPosition> {
+    POSITIONS
+        .iter()
+        .map(|line| TuningPosition::parse(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn results_are_read_from_epd_and_fen_lines() {
+    let results: Vec<f64> = positions().iter().map(|p| p.result).collect();
+    assert_eq!(results, vec![0.5, 1.0, 0.0, 1.0, 0.0, 1.0]);
+    assert!(positions()[2].board.black_to_move);
+
+    assert!(TuningPosition::parse("4k3/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+    assert!(TuningPosition::parse("4k3/8/8/8/8/8/8/4K3 w - - 0 1;2-0").is_err());
+    assert!(TuningPosition::parse("4k3/8/8/8/8/8/8/4K3;1-0").is_err());
+}
+
+#[test]
+fn position_files_skip_comments_and_report_bad_lines() {
+    let path = env::temp_dir().join("ferris_tuner_positions.epd");
+    let path = path.to_str().unwrap();
+
+    let text = format!("# Test positions\n\n{}\n", POSITIONS.join("\n"));
+    std::fs::write(path, text).unwrap();
+    assert_eq!(load_positions(path).unwrap().len(), POSITIONS.len());
+
+    std::fs::write(path, "4k3/8/8/8/8/8/8/4K3 w - - 0 1;1-0\nnot a position\n").unwrap();
+    let err = load_positions(path).err().unwrap();
+    assert!(err.contains(":2:"), "{}", err);
+}
+
+#[test]
+fn sigmoid_maps_scores_to_expected_results() {
+    assert_eq!(sigmoid(0, 1.0), 0.5);
+    assert!(sigmoid(400, 1.0) > 0.9);
+    assert!(sigmoid(-400, 1.0) < 0.1);
+    assert!(sigmoid(100, 2.0) > sigmoid(100, 1.0));
+}
+
+#[test]
+fn scores_resolve_captures_from_whites_point_of_view() {
+    // With white to move the pawn takes the queen, with black to move it escapes
+    let hanging = [
+        "4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1;1-0",
+        "4k3/8/8/3q4/4P3/8/8/4K3 b - - 0 1;1-0",
+    ];
+    let positions = hanging
+        .iter()
+        .map(|line| TuningPosition::parse(line).unwrap())
+        .collect();
+    let mut tuner = Tuner::new(positions, 2);
+    let scores = tuner.scores(&EvalParams::default());
+    assert!(scores[0] > 0, "{:?}", scores);
+    assert!(scores[1] < -500, "{:?}", scores);
+}
+
+#[test]
+fn fitted_k_has_the_least_error() {
+    let params = EvalParams::default();
+    let mut tuner = Tuner::new(positions(), 1);
+    let k = tuner.fit_k(&params);
+    assert!(k > 0.0);
+
+    let error = tuner.error(&params, k);
+    assert!(error <= tuner.error(&params, k * 2.0));
+    assert!(error <= tuner.error(&params, k / 2.0));
+}
+
+#[test]
+fn tuning_lowers_the_error() {
+    let mut params = EvalParams::default();
+    let mut tuner = Tuner::new(positions(), 2);
+    let k = tuner.fit_k(&params);
+    let initial_error = tuner.error(&params, k);
+
+    let mut passes = vec![];
+    let error = tuner.tune(&mut params, k, 5, 1, |pass, error, _| {
+        passes.push((pass, error))
+    });
+
+    assert!(error < initial_error);
+    assert_eq!(passes, vec![(1, error)]);
+    assert_ne!(params, EvalParams::default());
+    assert_eq!(tuner.error(&params, k), error);
+}